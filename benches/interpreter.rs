@@ -0,0 +1,66 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use wasm_mima::compiler::compiler::compile;
+use wasm_mima::mima::{Command, Instruction, Mima};
+
+// Counts to 10 million with six instructions per iteration.
+const COUNTING_LOOP: &str = "i: DS 0
+one: DS 1
+n: DS 10000000
+START: LDV i
+LOOP: ADD one
+STV i
+EQL n
+JMN DONE
+LDV i
+JMP LOOP
+DONE: HALT";
+const LOOP_INSTRUCTIONS: u64 = 10_000_000 * 6;
+
+fn decode(c: &mut Criterion) {
+    let words: Vec<usize> = (0..=u8::MAX as usize)
+        .filter_map(Instruction::from_opcode)
+        .map(|instruction| Command::new(instruction, 0x123 & ((1 << instruction.operand_bits()) - 1)).unwrap().to_usize())
+        .collect();
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(words.len() as u64));
+    group.bench_function("from_usize", |b| {
+        b.iter(|| words.iter().filter_map(|word| Command::from_usize(black_box(*word))).count())
+    });
+    group.finish();
+}
+
+fn step(c: &mut Criterion) {
+    // Never halts, so every step executes an instruction.
+    let mut mima = Mima::new();
+    mima.load(compile("i: DS 0\none: DS 1\nLOOP: LDV i\nADD one\nSTV i\nJMP LOOP").unwrap());
+    let mut group = c.benchmark_group("step");
+    group.throughput(Throughput::Elements(1000));
+    group.bench_function("1000 steps", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                black_box(mima.step());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn run(c: &mut Criterion) {
+    let program = compile(COUNTING_LOOP).unwrap();
+    let mut mima = Mima::new();
+    let mut group = c.benchmark_group("run");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(LOOP_INSTRUCTIONS));
+    group.bench_function("counting loop", |b| {
+        b.iter(|| {
+            mima.load(program.to_owned());
+            mima.run()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, step, run);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasm_mima::fuzzing::MemoryImage;
+
+const STEPS: usize = 1000;
+
+// Whatever the memory holds, stepping may halt the machine but never panic.
+fuzz_target!(|input: (MemoryImage, bool)| {
+    let (image, micro) = input;
+    let mut mima = image.machine();
+    for _ in 0..STEPS {
+        if micro {
+            mima.micro_step();
+        } else {
+            mima.step();
+        }
+    }
+});
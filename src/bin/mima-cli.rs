@@ -0,0 +1,153 @@
+use std::path::Path;
+use std::process::ExitCode;
+
+use wasm_mima::compiler::compiler::compile_source;
+use wasm_mima::compiler::{CompilerOptions, CompilerOutput};
+use wasm_mima::mima::{HaltKind, Mima, StepResult};
+
+const USAGE: &str = "Usage: mima-cli <command> <file> [options]
+
+Commands:
+  assemble <file>             Print the machine code as hexadecimal words
+  run <file>                  Run the program and print the registers
+  dump <file> <from> <to>     Run the program and print the memory between two adresses
+  trace <file>                Run the program and print every executed instruction
+
+Options:
+  --max-steps <n>             Stop after n instructions (default 1000000)";
+
+const DEFAULT_MAX_STEPS: usize = 1_000_000;
+
+// Command line arguments after the command and file name
+struct Options {
+    max_steps: usize,
+    positional: Vec<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options { max_steps: DEFAULT_MAX_STEPS, positional: vec![] };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-steps" => {
+                let value = args.next().ok_or("--max-steps needs a value")?;
+                options.max_steps = parse_number(value)?;
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{arg}'.")),
+            _ => options.positional.push(arg.to_owned()),
+        }
+    }
+    Ok(options)
+}
+
+// Numbers may be given in decimal or as hexadecimal with a 0x prefix.
+fn parse_number(text: &str) -> Result<usize, String> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("'{text}' is not a number."))
+}
+
+// INCLUDE directives are resolved relative to the directory of the compiled file.
+fn compile_file(path: &str) -> Result<CompilerOutput, String> {
+    let source = std::fs::read_to_string(path).map_err(|err| format!("Can't read '{path}': {err}"))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new("."));
+    let resolve = |include: &str| std::fs::read_to_string(directory.join(include)).ok();
+    compile_source(&source, &CompilerOptions::new(), &resolve).map_err(|err| err.to_string())
+}
+
+// Runs until the machine halts or the step limit is reached, calling `on_step` after every instruction.
+fn run(mima: &mut Mima, max_steps: usize, on_step: &mut dyn FnMut(&Mima, &StepResult)) -> Result<(), String> {
+    for _ in 0..max_steps {
+        let result = mima.step();
+        on_step(mima, &result);
+        if mima.get_debug().halt {
+            let reason = mima.get_debug().halt_reason;
+            return match reason.kind {
+                HaltKind::Halted => Ok(()),
+                kind => Err(format!("Machine stopped: {kind:?} at adress {:?}.", reason.adress)),
+            };
+        }
+    }
+    Err(format!("Machine didn't halt within {max_steps} steps."))
+}
+
+fn print_registers(mima: &Mima) {
+    let debug = mima.get_debug();
+    println!("AKKU {:06X}", debug.akku);
+    println!("IAR  {:05X}", debug.iar);
+    println!("SP   {:05X}", debug.sp);
+    println!("FP   {:05X}", debug.fp);
+}
+
+fn execute(args: &[String]) -> Result<(), String> {
+    let (command, file) = match args {
+        [command, file, ..] => (command.as_str(), file.as_str()),
+        _ => return Err(USAGE.to_string()),
+    };
+    let options = parse_options(&args[2..])?;
+    let output = compile_file(file)?;
+    if command == "assemble" {
+        for word in output.get_mima_code() {
+            println!("{word:06X}");
+        }
+        return Ok(());
+    }
+    let mut mima = Mima::new();
+    mima.load(output);
+    let result = match command {
+        "run" => run(&mut mima, options.max_steps, &mut |_, _| ()),
+        "dump" => {
+            let [from, to] = &options.positional[..] else {
+                return Err("dump needs a start and an end adress.".to_string());
+            };
+            let (from, to) = (parse_number(from)?, parse_number(to)?);
+            let result = run(&mut mima, options.max_steps, &mut |_, _| ());
+            for adress in from..=to {
+                match mima.read_adress(adress) {
+                    Some(value) => println!("{adress:05X}  {value:06X}"),
+                    None => break,
+                }
+            }
+            result
+        }
+        "trace" => run(&mut mima, options.max_steps, &mut |mima, result| {
+            if let Some(command) = result.get_command() {
+                println!("{:05X}  {:<12}  AKKU {:06X}", result.get_adress(), command.to_string(), mima.get_akku());
+            }
+        }),
+        _ => return Err(format!("Unknown command '{command}'.\n\n{USAGE}")),
+    };
+    if command == "run" {
+        print_registers(&mima);
+    }
+    result
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match execute(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_number, parse_options};
+
+    #[test]
+    fn option_parsing() {
+        let args: Vec<String> = ["0x10", "--max-steps", "50", "20"].iter().map(|arg| arg.to_string()).collect();
+        let options = parse_options(&args).unwrap();
+        assert_eq!(options.max_steps, 50);
+        assert_eq!(options.positional, vec!["0x10", "20"]);
+        assert_eq!(parse_number("0x1F"), Ok(31));
+        assert!(parse_number("ten").is_err());
+        assert!(parse_options(&["--fast".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeSet;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::{CompilerOutput, SymbolKind};
+use crate::mima::{Command, Instruction};
+use crate::report::{json_number, json_string};
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EdgeKind {
+    // To the next instruction in memory
+    FallThrough,
+    Jump,
+    // Taken by JMN if the akku is negative
+    Branch,
+    Call,
+}
+
+// Instructions at consecutive adresses that are always executed together
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    // Adress of the last instruction of the block
+    pub end: usize,
+    label: Option<String>,
+    // Source line of the first instruction, missing for programs without source
+    pub line: Option<usize>,
+    instructions: Vec<String>,
+    pub reachable: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl BasicBlock {
+    pub fn get_label(&self) -> Option<String> {
+        self.label.to_owned()
+    }
+    pub fn get_instructions(&self) -> Vec<String> {
+        self.instructions.to_owned()
+    }
+}
+
+// Blocks are referred to by their index in the graph.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/*
+ * The basic blocks of a compiled program ordered by adress and the jumps between them. RET, RTI
+ * and JIND leave their block to an adress only known at runtime, so they don't have edges. Blocks
+ * count as reachable from the start adress and exported labels, in programs using JIND also from
+ * every label as it may be stored in a jump table.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ControlFlowGraph {
+    blocks: Vec<BasicBlock>,
+    edges: Vec<Edge>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ControlFlowGraph {
+    pub fn get_blocks(&self) -> Vec<BasicBlock> {
+        self.blocks.to_owned()
+    }
+    pub fn get_edges(&self) -> Vec<Edge> {
+        self.edges.to_owned()
+    }
+    // Index of the block containing the adress
+    pub fn block_at(&self, adress: usize) -> Option<usize> {
+        self.blocks.iter().position(|block| (block.start..=block.end).contains(&adress))
+    }
+    // The graph as a JSON object with the arrays blocks and edges.
+    pub fn to_json(&self) -> String {
+        let blocks: Vec<String> = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let instructions: Vec<String> = block.instructions.iter().map(|instruction| json_string(instruction)).collect();
+                format!(
+                    r#"{{"start":{},"end":{},"label":{},"line":{},"instructions":[{}],"reachable":{}}}"#,
+                    block.start,
+                    block.end,
+                    block.label.as_deref().map_or("null".to_string(), json_string),
+                    json_number(block.line),
+                    instructions.join(","),
+                    block.reachable
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|edge| format!(r#"{{"from":{},"to":{},"kind":"{:?}"}}"#, edge.from, edge.to, edge.kind))
+            .collect();
+        format!(r#"{{"blocks":[{}],"edges":[{}]}}"#, blocks.join(","), edges.join(","))
+    }
+}
+
+// Splits the instructions of the program into basic blocks. Words of variables aren't code.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn analyze_cfg(program: &CompilerOutput) -> ControlFlowGraph {
+    let code = program.get_mima_code();
+    let symbols = program.get_symbols();
+    let is_data = |adress: usize| {
+        symbols.iter().any(|symbol| {
+            symbol.get_kind() == SymbolKind::Variable && (symbol.get_adress()..symbol.get_adress() + symbol.get_size()).contains(&adress)
+        })
+    };
+    let source_map = program.get_source_map();
+    // Outputs without source don't tell which words are used, all of them are treated as code.
+    let instructions: BTreeSet<usize> = match source_map.is_empty() {
+        true => (0..code.len()).collect(),
+        false => source_map.iter().map(|mapping| mapping.adress).collect(),
+    };
+    let instructions: BTreeSet<usize> = instructions.into_iter().filter(|adress| !is_data(*adress)).collect();
+    let command = |adress: usize| Command::from_usize(code[adress]);
+    let is_label = |adress: usize| symbols.iter().any(|symbol| symbol.get_kind() == SymbolKind::Label && symbol.get_adress() == adress);
+
+    let mut leaders: BTreeSet<usize> = instructions.iter().copied().filter(|adress| is_label(*adress)).collect();
+    leaders.extend(instructions.first());
+    leaders.insert(program.get_start_adress());
+    for adress in instructions.iter().copied() {
+        match command(adress) {
+            Some(command) if ends_block(command.instruction) => {
+                if command.instruction.is_jump() {
+                    leaders.insert(command.value);
+                }
+                leaders.insert(adress + 1);
+            }
+            Some(_) => (),
+            // Illegal instructions stop the machine.
+            None => {
+                leaders.insert(adress + 1);
+            }
+        }
+        // Gaps between ORG segments end a block as well.
+        if !instructions.contains(&(adress + 1)) {
+            leaders.insert(adress + 1);
+        }
+    }
+
+    let disassembly = program.disassemble();
+    let mut blocks: Vec<BasicBlock> = vec![];
+    for adress in instructions.iter().copied() {
+        match blocks.last_mut() {
+            Some(block) if block.end + 1 == adress && !leaders.contains(&adress) => {
+                block.end = adress;
+                block.instructions.push(disassembly[adress].to_owned());
+            }
+            _ => blocks.push(BasicBlock {
+                start: adress,
+                end: adress,
+                label: symbols
+                    .iter()
+                    .find(|symbol| symbol.get_kind() == SymbolKind::Label && symbol.get_adress() == adress)
+                    .map(|symbol| symbol.get_name()),
+                line: source_map.iter().find(|mapping| mapping.adress == adress).map(|mapping| mapping.line),
+                instructions: vec![disassembly[adress].to_owned()],
+                reachable: false,
+            }),
+        }
+    }
+
+    let block_of = |adress: usize| blocks.iter().position(|block| block.start == adress);
+    let mut edges = vec![];
+    for (index, block) in blocks.iter().enumerate() {
+        let Some(last) = command(block.end) else {
+            continue;
+        };
+        let next = block_of(block.end + 1);
+        let target = block_of(last.value);
+        let successors = match last.instruction {
+            Instruction::JMP => vec![(target, EdgeKind::Jump)],
+            Instruction::JMN => vec![(target, EdgeKind::Branch), (next, EdgeKind::FallThrough)],
+            // The subroutine returns to the next instruction.
+            Instruction::CALL => vec![(target, EdgeKind::Call), (next, EdgeKind::FallThrough)],
+            instruction if ends_block(instruction) => vec![],
+            _ => vec![(next, EdgeKind::FallThrough)],
+        };
+        for (to, kind) in successors {
+            // Jumps to adresses outside of the code don't lead to a block.
+            if let Some(to) = to {
+                edges.push(Edge { from: index, to, kind });
+            }
+        }
+    }
+
+    let indirect = instructions.iter().any(|adress| command(*adress).is_some_and(|command| command.instruction == Instruction::JIND));
+    let mut pending: Vec<usize> = blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| {
+            block.start == program.get_start_adress()
+                || program.get_entry_points().iter().any(|symbol| symbol.get_adress() == block.start)
+                || (indirect && block.label.is_some())
+        })
+        .map(|(index, _)| index)
+        .collect();
+    while let Some(index) = pending.pop() {
+        if blocks[index].reachable {
+            continue;
+        }
+        blocks[index].reachable = true;
+        pending.extend(edges.iter().filter(|edge| edge.from == index).map(|edge| edge.to));
+    }
+    ControlFlowGraph { blocks, edges }
+}
+
+// Instructions after which execution doesn't simply continue with the next adress
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::JMP | Instruction::JMN | Instruction::CALL | Instruction::HALT | Instruction::RET | Instruction::RTI | Instruction::JIND
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compiler, CompilerOutput};
+    use crate::mima::{Command, Instruction};
+
+    use super::{analyze_cfg, Edge, EdgeKind};
+
+    #[test]
+    fn control_flow_graph() {
+        let source = "n: DS 3\nSTART: LDV n\nLOOP: JMN DONE\nADC -1\nCALL DEC\nJMP LOOP\nDONE: HALT\nLDC 0\nDEC: RET";
+        let graph = analyze_cfg(&compiler::compile(source).unwrap());
+        let blocks: Vec<(usize, usize, Option<String>, bool)> =
+            graph.get_blocks().iter().map(|block| (block.start, block.end, block.get_label(), block.reachable)).collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (1, 1, Some("START".to_string()), true),
+                (2, 2, Some("LOOP".to_string()), true),
+                (3, 4, None, true),
+                (5, 5, None, true),
+                (6, 6, Some("DONE".to_string()), true),
+                (7, 7, None, false),
+                (8, 8, Some("DEC".to_string()), true),
+            ]
+        );
+        let edge = |from, to, kind| Edge { from, to, kind };
+        assert_eq!(
+            graph.get_edges(),
+            vec![
+                edge(0, 1, EdgeKind::FallThrough),
+                edge(1, 4, EdgeKind::Branch),
+                edge(1, 2, EdgeKind::FallThrough),
+                edge(2, 6, EdgeKind::Call),
+                edge(2, 3, EdgeKind::FallThrough),
+                edge(3, 1, EdgeKind::Jump),
+                edge(5, 6, EdgeKind::FallThrough),
+            ]
+        );
+        assert_eq!(graph.get_blocks()[2].get_instructions(), vec!["ADC 1048575", "CALL DEC"]);
+        assert_eq!((graph.block_at(4), graph.block_at(0)), (Some(2), None));
+        assert!(graph.to_json().starts_with(r#"{"blocks":[{"start":1,"end":1,"label":"START","line":2,"instructions":["START: LDV n"],"reachable":true}"#));
+        assert!(graph.to_json().ends_with(r#"{"from":5,"to":6,"kind":"FallThrough"}]}"#));
+        // Without source every word is code.
+        let code = vec![Command::new(Instruction::JMP, 2).unwrap().to_usize(), 0, Command::new(Instruction::HALT, 0).unwrap().to_usize()];
+        let graph = analyze_cfg(&CompilerOutput::new(code, 0));
+        assert_eq!(graph.get_blocks().iter().map(|block| block.reachable).collect::<Vec<bool>>(), vec![true, false, true]);
+    }
+}
@@ -1,26 +1,253 @@
-use lazy_static::lazy_static;
-use regex::Regex;
 use snafu::prelude::*;
 
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-use crate::mima::Instruction;
+use crate::dialect::Dialect;
+use crate::disassembler::{disassemble_with_symbols, disassemble_word};
+use crate::mima::{Command, Instruction, MachineProfile};
+use crate::tokenizer::tokenize_line;
 
-lazy_static! {
-    static ref VARIABLE_REGEX: Regex = Regex::new(r"([a-zA-Z]+): DS( ([0-9]+))?").unwrap();
-    static ref INSTRUCTION_REGEX: Regex =
-        Regex::new(r"\s*(([a-zA-Z]+):)?\s*([a-zA-Z]+)( ([0-9]+|[a-zA-Z]+))?").unwrap();
+// Decimal, hexadecimal (0x1F or $1F) and binary (0b1010) literals with an optional sign, used for
+// the highlighting and formatting of lines
+macro_rules! number_pattern {
+    () => {
+        r"-?(?:0[xX][0-9a-fA-F]+|\$[0-9a-fA-F]+|0[bB][01]+|[0-9]+)"
+    };
 }
+pub(crate) use number_pattern;
+
+// Names of symbols and macros: a letter followed by letters, digits or underscores
+macro_rules! identifier_pattern {
+    () => {
+        r"[a-zA-Z][a-zA-Z0-9_]*"
+    };
+}
+pub(crate) use identifier_pattern;
 
 // Struct reprasantation of the compiler output
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug)]
 pub struct CompilerOutput {
-    mima_code: Vec<usize>,
-    start_adress: usize,
+    pub(crate) mima_code: Vec<usize>,
+    pub(crate) start_adress: usize,
+    pub(crate) warnings: Vec<CompilerWarning>,
+    pub(crate) source_map: Vec<SourceMapping>,
+    // Listing lines ordered by adress, see get_listing
+    pub(crate) listing: Vec<String>,
+    pub(crate) symbols: Vec<Symbol>,
+    // Adresses of the instructions whose operand is the adress of a symbol
+    pub(crate) relocations: Vec<usize>,
+    // Adresses of variable words holding the adress of a symbol, like pointers and jump tables
+    pub(crate) data_relocations: Vec<usize>,
+    // Set by the TITLE directive
+    pub(crate) title: Option<String>,
+    // Label given to the END directive, execution starts there
+    pub(crate) entry: Option<String>,
+    // Amount of instructions and of words occupied by variables
+    pub(crate) code_length: usize,
+    pub(crate) data_length: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WarningKind {
+    CaseConflict,
+    UnusedVariable,
+    UnusedLabel,
+    UnreachableCode,
+}
+
+// Problems that don't prevent compilation, errors are returned as Err instead.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompilerWarning {
+    kind: WarningKind,
+    line: usize,
+    message: String,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CompilerWarning {
+    pub fn get_kind(&self) -> WarningKind {
+        self.kind
+    }
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+    pub fn get_message(&self) -> String {
+        self.message.to_owned()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Label,
+}
+
+/*
+ * Variables declared with "DS INPUT" are filled by a harness or the user before the run, the ones
+ * declared with "DS OUTPUT" hold the results afterwards.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Parameter {
+    Input,
+    Output,
+}
+
+// A named adress of the compiled program
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    name: String,
+    adress: usize,
+    // Amount of memory words, labels always occupy one
+    size: usize,
+    kind: SymbolKind,
+    parameter: Option<Parameter>,
+    // False for variables declared without a value like "counter: DS"
+    initialized: bool,
+    // Listed in an EXPORT directive
+    exported: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Symbol {
+    pub fn get_name(&self) -> String {
+        self.name.to_owned()
+    }
+    pub fn get_adress(&self) -> usize {
+        self.adress
+    }
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+    pub fn get_kind(&self) -> SymbolKind {
+        self.kind
+    }
+    pub fn get_parameter(&self) -> Option<Parameter> {
+        self.parameter
+    }
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+    pub fn is_exported(&self) -> bool {
+        self.exported
+    }
+}
+
+impl Symbol {
+    pub fn relocated(&self, base: usize) -> Symbol {
+        Symbol {
+            adress: self.adress + base,
+            ..self.to_owned()
+        }
+    }
+}
+
+// Maps a memory word of the compiled program back to the source line it was generated from.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SourceMapping {
+    pub adress: usize,
+    // Line in the source (starting at 1), for expanded macros the line of the invocation
+    pub line: usize,
+    // Line of the macro definition the word was expanded from
+    pub macro_line: Option<usize>,
+}
+
+// A line of source code together with its origin
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLine {
+    pub text: String,
+    pub line: usize,
+    pub macro_line: Option<usize>,
+}
+
+fn listing_line(adress: usize, word: usize, decoded: &str, source: Option<&SourceLine>) -> String {
+    let binary = format!("{word:024b}")
+        .as_bytes()
+        .chunks(4)
+        .map(|nibble| String::from_utf8_lossy(nibble).into_owned())
+        .collect::<Vec<String>>()
+        .join(" ");
+    let line = format!("{adress:05X}  {word:06X}  {binary}  {decoded:<12}");
+    match source {
+        Some(source) => format!("{line}  {:>4}: {}", source.line, source.text.trim()),
+        None => line.trim_end().to_string(),
+    }
+}
+
+// Splits the source into numbered lines without comments and blank lines.
+pub fn split_lines(input: &str) -> Vec<SourceLine> {
+    input
+        .split("\n")
+        .enumerate()
+        .map(|(index, line)| SourceLine {
+            text: strip_comment(line).to_string(),
+            line: index + 1,
+            macro_line: None,
+        })
+        .filter(|line| !line.text.trim().is_empty())
+        .collect()
+}
+
+// Removes a comment starting with ';' anywhere in the line.
+fn strip_comment(line: &str) -> &str {
+    split_comment(line).0
+}
+
+// Splits a line into code and comment, a ';' inside a string or character literal doesn't start a comment.
+pub(crate) fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut escaped = false;
+    for (offset, character) in line.char_indices() {
+        match (quote, character) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(open), _) if character == open => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(character),
+            (None, ';') => return (&line[..offset], Some(&line[offset..])),
+            (None, _) => (),
+        }
+    }
+    (line, None)
+}
+
+// Options changing how the source is interpreted.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug)]
+pub struct CompilerOptions {
+    // Whether 'counter' and 'Counter' are different symbols
+    pub case_sensitive: bool,
+    // Instruction set the program is written for
+    pub profile: MachineProfile,
+    pub dialect: Dialect,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CompilerOptions {
+    pub fn new() -> CompilerOptions {
+        CompilerOptions {
+            case_sensitive: true,
+            profile: MachineProfile::default(),
+            dialect: Dialect::default(),
+        }
+    }
+}
+
+impl Default for CompilerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // We can't make the attributes public because of wasm and need to manually write getters.
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl CompilerOutput {
     pub fn get_mima_code(&self) -> Vec<usize> {
         self.mima_code.to_owned()
@@ -28,193 +255,1313 @@ impl CompilerOutput {
     pub fn get_start_adress(&self) -> usize {
         self.start_adress.to_owned()
     }
+    pub fn get_warnings(&self) -> Vec<CompilerWarning> {
+        self.warnings.to_owned()
+    }
+    pub fn get_source_map(&self) -> Vec<SourceMapping> {
+        self.source_map.to_owned()
+    }
+    // Variables and labels ordered by adress
+    pub fn get_symbols(&self) -> Vec<Symbol> {
+        self.symbols.to_owned()
+    }
+    // Variables declared with DS INPUT ordered by adress
+    pub fn get_inputs(&self) -> Vec<Symbol> {
+        self.symbols.iter().filter(|symbol| symbol.parameter == Some(Parameter::Input)).cloned().collect()
+    }
+    // Variables declared with DS OUTPUT ordered by adress
+    pub fn get_outputs(&self) -> Vec<Symbol> {
+        self.symbols.iter().filter(|symbol| symbol.parameter == Some(Parameter::Output)).cloned().collect()
+    }
+    // Exported labels ordered by adress, each of them can be run on its own with Mima::call.
+    pub fn get_entry_points(&self) -> Vec<Symbol> {
+        self.symbols.iter().filter(|symbol| symbol.exported && symbol.kind == SymbolKind::Label).cloned().collect()
+    }
+    // Disassembles the program using the symbol names of the source.
+    pub fn disassemble(&self) -> Vec<String> {
+        disassemble_with_symbols(&self.mima_code, &self.symbols)
+    }
+    /*
+     * Returns the assembler listing: adress, hex and binary encoding, the decoded word and the
+     * source line it originates from. Outputs created without source only list the words.
+     */
+    pub fn get_listing(&self) -> String {
+        let header = format!("{:<5}  {:<6}  {:<29}  {:<12}  SOURCE", "ADDR", "WORD", "BINARY", "DECODED");
+        let lines = if self.listing.is_empty() {
+            self.mima_code
+                .iter()
+                .enumerate()
+                .map(|(adress, word)| listing_line(adress, *word, &disassemble_word(*word), None))
+                .collect()
+        } else {
+            self.listing.to_owned()
+        };
+        std::iter::once(header).chain(lines).collect::<Vec<String>>().join("\n")
+    }
     pub fn new(mima_code: Vec<usize>, start_adress: usize) -> CompilerOutput {
         CompilerOutput {
-            mima_code,
             start_adress,
+            warnings: vec![],
+            source_map: vec![],
+            listing: vec![],
+            symbols: vec![],
+            relocations: vec![],
+            data_relocations: vec![],
+            title: None,
+            entry: None,
+            // Without source, code and data can't be told apart.
+            code_length: mima_code.len(),
+            data_length: 0,
+            mima_code,
         }
     }
+    pub fn get_relocations(&self) -> Vec<usize> {
+        self.relocations.to_owned()
+    }
+    pub fn get_data_relocations(&self) -> Vec<usize> {
+        self.data_relocations.to_owned()
+    }
+    pub fn get_title(&self) -> Option<String> {
+        self.title.to_owned()
+    }
+    pub fn get_entry(&self) -> Option<String> {
+        self.entry.to_owned()
+    }
+    pub fn get_code_length(&self) -> usize {
+        self.code_length
+    }
+    pub fn get_data_length(&self) -> usize {
+        self.data_length
+    }
 }
 
-#[derive(Debug, Snafu)]
+impl CompilerOutput {
+    /*
+     * Returns the code as it has to be placed at the given base adress. Fails with the adress of
+     * the first instruction or data word whose relocated value doesn't fit anymore.
+     */
+    pub fn relocated_code(&self, base: usize) -> Result<Vec<usize>, usize> {
+        let mut code = self.mima_code.to_owned();
+        for adress in self.relocations.iter() {
+            let command = Command::from_usize(code[*adress]).ok_or(*adress)?;
+            code[*adress] = command.with_value(command.value + base).ok_or(*adress)?.to_usize();
+        }
+        // Data words hold the adress in the whole word.
+        for adress in self.data_relocations.iter() {
+            code[*adress] += base;
+            if code[*adress] >> compiler::WORD_BITS != 0 {
+                return Err(*adress);
+            }
+        }
+        Ok(code)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Snafu)]
 pub enum CompilerError {
     #[snafu(display("Invalid instruction in line '{line}'."))]
     InvalidLine{line: usize},
-    #[snafu(display("Invalid reference in line '{line}'"))]
-    InvalidReference{line: usize},
-    #[snafu(display("Couldn't find variable '{name}'"))]
-    UnknownVariable{name: String},
-    #[snafu(display("Couldn't find label '{name}'"))]
-    UnknownLabel{name: String},
-    #[snafu(display("Couldn't parse instruction: '{name}'."))]
-    UnknownInstruction{name: String},
+    #[snafu(display("Couldn't find variable '{name}' in line '{line}'.{}", did_you_mean(suggestion)))]
+    UnknownVariable{name: String, line: usize, suggestion: Option<String>},
+    #[snafu(display("Couldn't find label '{name}' in line '{line}'.{}", did_you_mean(suggestion)))]
+    UnknownLabel{name: String, line: usize, suggestion: Option<String>},
+    #[snafu(display("Couldn't parse instruction '{name}' in line '{line}'.{}", did_you_mean(suggestion)))]
+    UnknownInstruction{name: String, line: usize, suggestion: Option<String>},
+    #[snafu(display("Literal '{value}' in line '{line}' is out of range."))]
+    LiteralOutOfRange{line: usize, value: String},
+    #[snafu(display("Constant '{name}' in line '{line}' is already defined."))]
+    ConstantRedefinition{line: usize, name: String},
+    #[snafu(display("Adress '{adress}' in line '{line}' is occupied more than once."))]
+    OverlappingAdress{adress: usize, line: usize},
+    #[snafu(display("Macro '{name}' in line '{line}' is missing ENDM."))]
+    UnterminatedMacro{name: String, line: usize},
+    #[snafu(display("Macro '{name}' in line '{line}' expects {expected} arguments but got {found}."))]
+    MacroArity{name: String, line: usize, expected: usize, found: usize},
+    #[snafu(display("Macro '{name}' in line '{line}' expands recursively."))]
+    MacroRecursion{name: String, line: usize},
+    #[snafu(display("Couldn't resolve included file '{path}' in line '{line}'."))]
+    UnresolvedInclude{path: String, line: usize},
+    #[snafu(display("File '{path}' in line '{line}' includes itself."))]
+    IncludeRecursion{path: String, line: usize},
+    #[snafu(display("Operand '{value}' in line '{line}' doesn't fit into {bits} bits."))]
+    OperandOutOfRange{line: usize, value: usize, bits: u32},
+    #[snafu(display("Instruction '{name}' in line '{line}' needs an operand."))]
+    MissingOperand{line: usize, name: String},
+    #[snafu(display("Instruction '{name}' in line '{line}' doesn't take an operand."))]
+    UnexpectedOperand{line: usize, name: String},
+    #[snafu(display("Variable '{name}' in line '{line}' can't be used as a jump target."))]
+    VariableAsJumpTarget{name: String, line: usize},
+    #[snafu(display("Label '{name}' in line '{line}' can't be used as a data operand."))]
+    LabelAsDataOperand{name: String, line: usize},
+    #[snafu(display("Symbol '{name}' in line '{line}' is already defined in line '{first_line}'."))]
+    DuplicateSymbol{name: String, line: usize, first_line: usize},
+    #[snafu(display("Symbol '{name}' in line '{line}' can't be named like an instruction."))]
+    ReservedName{name: String, line: usize},
+    #[snafu(display("Instruction '{name}' in line '{line}' isn't available on the {profile:?} profile."))]
+    UnsupportedInstruction{name: String, line: usize, profile: MachineProfile},
+    #[snafu(display("Exported symbol '{name}' in line '{line}' isn't defined."))]
+    UnknownExport{name: String, line: usize},
+    #[snafu(display("Symbol '{name}' is exported by more than one object."))]
+    DuplicateExport{name: String},
+    #[snafu(display("Symbol '{name}' in line '{line}' isn't exported by any object."))]
+    UnresolvedImport{name: String, line: usize},
+    #[snafu(display("Unexpected character '{character}' in line '{line}' at column '{column}'."))]
+    UnexpectedCharacter{line: usize, column: usize, character: char},
+    #[snafu(display("Unexpected '{found}' in line '{line}' at column '{column}'."))]
+    UnexpectedToken{line: usize, column: usize, found: String},
+    #[snafu(display("Invalid literal '{value}' in line '{line}' at column '{column}'."))]
+    InvalidLiteral{line: usize, column: usize, value: String},
+    #[snafu(display("Invalid identifier '{name}' in line '{line}' at column '{column}'."))]
+    InvalidIdentifier{line: usize, column: usize, name: String},
+    #[snafu(display("String in line '{line}' starting at column '{column}' isn't terminated."))]
+    UnterminatedString{line: usize, column: usize},
+    #[snafu(display("Expression '{expression}' in line '{line}' overflows."))]
+    ExpressionOverflow{line: usize, expression: String},
+    #[snafu(display("Division by zero in expression '{expression}' in line '{line}'."))]
+    DivisionByZero{line: usize, expression: String},
+    #[snafu(display("Expression '{expression}' in line '{line}' must be constant."))]
+    NotConstant{line: usize, expression: String},
+    #[snafu(display("Constant '{name}' in line '{line}' is defined in terms of itself."))]
+    CyclicConstant{name: String, line: usize},
+    #[snafu(display("Relocated operand doesn't fit into the instruction at adress '{adress}'."))]
+    RelocationOutOfRange{adress: usize},
+    #[snafu(display("Line '{line}' follows the END directive."))]
+    CodeAfterEnd{line: usize},
+    #[snafu(display("Entry label '{name}' in line '{line}' isn't defined."))]
+    UnknownEntry{name: String, line: usize},
+}
+
+fn did_you_mean(suggestion: &Option<String>) -> String {
+    suggestion.as_ref().map(|name| format!(" Did you mean '{name}'?")).unwrap_or_default()
 }
 
+impl CompilerError {
+    // Stable name of the kind of error for frontends, see MimaError
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompilerError::InvalidLine { .. } => "INVALID_LINE",
+            CompilerError::UnknownVariable { .. } => "UNKNOWN_VARIABLE",
+            CompilerError::UnknownLabel { .. } => "UNKNOWN_LABEL",
+            CompilerError::UnknownInstruction { .. } => "UNKNOWN_INSTRUCTION",
+            CompilerError::LiteralOutOfRange { .. } => "LITERAL_OUT_OF_RANGE",
+            CompilerError::ConstantRedefinition { .. } => "CONSTANT_REDEFINITION",
+            CompilerError::OverlappingAdress { .. } => "OVERLAPPING_ADDRESS",
+            CompilerError::UnterminatedMacro { .. } => "UNTERMINATED_MACRO",
+            CompilerError::MacroArity { .. } => "MACRO_ARITY",
+            CompilerError::MacroRecursion { .. } => "MACRO_RECURSION",
+            CompilerError::UnresolvedInclude { .. } => "UNRESOLVED_INCLUDE",
+            CompilerError::IncludeRecursion { .. } => "INCLUDE_RECURSION",
+            CompilerError::OperandOutOfRange { .. } => "OPERAND_OUT_OF_RANGE",
+            CompilerError::MissingOperand { .. } => "MISSING_OPERAND",
+            CompilerError::UnexpectedOperand { .. } => "UNEXPECTED_OPERAND",
+            CompilerError::VariableAsJumpTarget { .. } => "VARIABLE_AS_JUMP_TARGET",
+            CompilerError::LabelAsDataOperand { .. } => "LABEL_AS_DATA_OPERAND",
+            CompilerError::DuplicateSymbol { .. } => "DUPLICATE_SYMBOL",
+            CompilerError::ReservedName { .. } => "RESERVED_NAME",
+            CompilerError::UnsupportedInstruction { .. } => "UNSUPPORTED_INSTRUCTION",
+            CompilerError::UnknownExport { .. } => "UNKNOWN_EXPORT",
+            CompilerError::DuplicateExport { .. } => "DUPLICATE_EXPORT",
+            CompilerError::UnresolvedImport { .. } => "UNRESOLVED_IMPORT",
+            CompilerError::UnexpectedCharacter { .. } => "UNEXPECTED_CHARACTER",
+            CompilerError::UnexpectedToken { .. } => "UNEXPECTED_TOKEN",
+            CompilerError::InvalidLiteral { .. } => "INVALID_LITERAL",
+            CompilerError::InvalidIdentifier { .. } => "INVALID_IDENTIFIER",
+            CompilerError::UnterminatedString { .. } => "UNTERMINATED_STRING",
+            CompilerError::ExpressionOverflow { .. } => "EXPRESSION_OVERFLOW",
+            CompilerError::DivisionByZero { .. } => "DIVISION_BY_ZERO",
+            CompilerError::NotConstant { .. } => "NOT_CONSTANT",
+            CompilerError::CyclicConstant { .. } => "CYCLIC_CONSTANT",
+            CompilerError::RelocationOutOfRange { .. } => "RELOCATION_OUT_OF_RANGE",
+            CompilerError::CodeAfterEnd { .. } => "CODE_AFTER_END",
+            CompilerError::UnknownEntry { .. } => "UNKNOWN_ENTRY",
+        }
+    }
+
+    // The name an unknown instruction or symbol was probably meant to be
+    pub fn suggestion(&self) -> Option<String> {
+        match self {
+            CompilerError::UnknownVariable { suggestion, .. }
+            | CompilerError::UnknownLabel { suggestion, .. }
+            | CompilerError::UnknownInstruction { suggestion, .. } => suggestion.to_owned(),
+            _ => None,
+        }
+    }
+
+    // Line of the source the error is about, None for errors of the linker
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            CompilerError::InvalidLine { line }
+            | CompilerError::UnknownVariable { line, .. }
+            | CompilerError::UnknownLabel { line, .. }
+            | CompilerError::UnknownInstruction { line, .. }
+            | CompilerError::LiteralOutOfRange { line, .. }
+            | CompilerError::ConstantRedefinition { line, .. }
+            | CompilerError::OverlappingAdress { line, .. }
+            | CompilerError::UnterminatedMacro { line, .. }
+            | CompilerError::MacroArity { line, .. }
+            | CompilerError::MacroRecursion { line, .. }
+            | CompilerError::UnresolvedInclude { line, .. }
+            | CompilerError::IncludeRecursion { line, .. }
+            | CompilerError::OperandOutOfRange { line, .. }
+            | CompilerError::MissingOperand { line, .. }
+            | CompilerError::UnexpectedOperand { line, .. }
+            | CompilerError::VariableAsJumpTarget { line, .. }
+            | CompilerError::LabelAsDataOperand { line, .. }
+            | CompilerError::DuplicateSymbol { line, .. }
+            | CompilerError::ReservedName { line, .. }
+            | CompilerError::UnsupportedInstruction { line, .. }
+            | CompilerError::UnknownExport { line, .. }
+            | CompilerError::UnresolvedImport { line, .. }
+            | CompilerError::UnexpectedCharacter { line, .. }
+            | CompilerError::UnexpectedToken { line, .. }
+            | CompilerError::InvalidLiteral { line, .. }
+            | CompilerError::InvalidIdentifier { line, .. }
+            | CompilerError::UnterminatedString { line, .. }
+            | CompilerError::ExpressionOverflow { line, .. }
+            | CompilerError::DivisionByZero { line, .. }
+            | CompilerError::NotConstant { line, .. }
+            | CompilerError::CyclicConstant { line, .. }
+            | CompilerError::CodeAfterEnd { line }
+            | CompilerError::UnknownEntry { line, .. } => Some(*line),
+            CompilerError::DuplicateExport { .. } | CompilerError::RelocationOutOfRange { .. } => None,
+        }
+    }
+
+    // Column of the first character of the offending text, only known to the parser (starting at 1)
+    fn column(&self) -> Option<usize> {
+        match self {
+            CompilerError::UnexpectedCharacter { column, .. }
+            | CompilerError::UnexpectedToken { column, .. }
+            | CompilerError::InvalidLiteral { column, .. }
+            | CompilerError::InvalidIdentifier { column, .. }
+            | CompilerError::UnterminatedString { column, .. } => Some(*column),
+            _ => None,
+        }
+    }
+
+    // The text of the line the error is about, if the error names it
+    fn subject(&self) -> Option<String> {
+        match self {
+            CompilerError::UnknownVariable { name, .. }
+            | CompilerError::UnknownLabel { name, .. }
+            | CompilerError::UnknownInstruction { name, .. }
+            | CompilerError::ConstantRedefinition { name, .. }
+            | CompilerError::UnterminatedMacro { name, .. }
+            | CompilerError::MacroArity { name, .. }
+            | CompilerError::MacroRecursion { name, .. }
+            | CompilerError::MissingOperand { name, .. }
+            | CompilerError::UnexpectedOperand { name, .. }
+            | CompilerError::VariableAsJumpTarget { name, .. }
+            | CompilerError::LabelAsDataOperand { name, .. }
+            | CompilerError::DuplicateSymbol { name, .. }
+            | CompilerError::ReservedName { name, .. }
+            | CompilerError::UnsupportedInstruction { name, .. }
+            | CompilerError::UnknownExport { name, .. }
+            | CompilerError::UnresolvedImport { name, .. }
+            | CompilerError::InvalidIdentifier { name, .. }
+            | CompilerError::CyclicConstant { name, .. }
+            | CompilerError::UnknownEntry { name, .. } => Some(name.to_owned()),
+            CompilerError::LiteralOutOfRange { value, .. } | CompilerError::InvalidLiteral { value, .. } => Some(value.to_owned()),
+            CompilerError::UnresolvedInclude { path, .. } | CompilerError::IncludeRecursion { path, .. } => Some(path.to_owned()),
+            CompilerError::OperandOutOfRange { value, .. } => Some(value.to_string()),
+            CompilerError::UnexpectedCharacter { character, .. } => Some(character.to_string()),
+            CompilerError::UnexpectedToken { found, .. } => Some(found.to_owned()),
+            CompilerError::ExpressionOverflow { expression, .. }
+            | CompilerError::DivisionByZero { expression, .. }
+            | CompilerError::NotConstant { expression, .. } => Some(expression.to_owned()),
+            _ => None,
+        }
+    }
+
+    /*
+     * Locates the offending text of the error in the source it was compiled from. Without a column
+     * from the parser, the first token of the line matching the text is taken. If the text isn't
+     * on the line, e.g. because it comes from an included file, the code of the whole line is.
+     */
+    pub fn span(&self, source: &str) -> Option<ErrorSpan> {
+        let line = self.line()?;
+        let text = source.split('\n').nth(line.checked_sub(1)?)?;
+        let code = split_comment(text).0;
+        let subject = self.subject();
+        if let Some(column) = self.column() {
+            let start = (column - 1).min(code.len());
+            let end = subject.map_or(code.len(), |subject| (start + subject.len()).min(code.len()));
+            return Some(ErrorSpan::new(text, line, start, end));
+        }
+        let token = subject.as_ref().and_then(|subject| {
+            tokenize_line(text, line)
+                .into_iter()
+                .find(|token| token.get_text().eq_ignore_ascii_case(subject))
+        });
+        if let Some(token) = token {
+            return Some(ErrorSpan { line, start: token.get_start(), end: token.get_end() });
+        }
+        match subject.and_then(|subject| code.find(&subject).map(|start| (start, start + subject.len()))) {
+            Some((start, end)) => Some(ErrorSpan::new(text, line, start, end)),
+            None => Some(ErrorSpan::new(text, line, code.len() - code.trim_start().len(), code.trim_end().len())),
+        }
+    }
+}
+
+// Columns are offsets into the line in UTF-16 code units, like the spans of tokenize.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ErrorSpan {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ErrorSpan {
+    // Converts byte offsets into the line to columns.
+    fn new(text: &str, line: usize, start: usize, end: usize) -> ErrorSpan {
+        let column = |offset: usize| text.get(..offset).map_or(offset, |prefix| prefix.encode_utf16().count());
+        ErrorSpan { line, start: column(start), end: column(end) }
+    }
+}
+
+// The message of a compiler error together with the position it refers to
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompilerDiagnostic {
+    message: String,
+    span: Option<ErrorSpan>,
+    suggestion: Option<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CompilerDiagnostic {
+    pub fn get_message(&self) -> String {
+        self.message.to_owned()
+    }
+    pub fn get_span(&self) -> Option<ErrorSpan> {
+        self.span
+    }
+    pub fn get_suggestion(&self) -> Option<String> {
+        self.suggestion.to_owned()
+    }
+}
+
+#[allow(clippy::module_inception)]
 pub mod compiler {
+    #[cfg(feature = "wasm")]
     use wasm_bindgen::prelude::*;
-    use crate::compiler::{CompilerOutput, VARIABLE_REGEX, INSTRUCTION_REGEX, Instruction,};
-    use crate::mima::Command;
+    use crate::compiler::{listing_line, CompilerDiagnostic, CompilerOptions, CompilerOutput, CompilerWarning, WarningKind, SourceLine, Parameter, Symbol, SymbolKind, SourceMapping, split_lines, Instruction,};
+    use crate::parser::{parse_line, BinaryOperator, Expression, Initializer, Literal, Node, Span, Syntax};
+    use crate::linker::{Import, ObjectFile};
+    use crate::locale;
+    use crate::dialect::{convert_kit_line, Dialect};
+    use std::collections::BTreeMap;
+    use crate::include::expand_includes;
+    use crate::macros::expand_macros;
+    use crate::mima::{Command, MachineProfile};
+    use crate::error::MimaError;
 
     use super::CompilerError;
+
+    // Width of a memory word and of the constant operand of LDC
+    pub(crate) const WORD_BITS: u32 = 24;
+    const CONSTANT_BITS: u32 = 20;
+    const ADRESS_BITS: u32 = 20;
+    // Limits how deep constants may refer to other constants, deeper nesting is treated as a cycle.
+    const MAX_CONSTANT_DEPTH: usize = 64;
+    // Label where execution starts if END doesn't name one
+    const START_LABEL: &str = "START";
     /*
      * This is a very basic compiler. That is currently wip.
      * For now it only supports basic variable assignments and instructions.
      */
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn compile(input: &str) -> Result<CompilerOutput, MimaError> {
+        compile_with_options(input, CompilerOptions::new())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn compile_with_options(input: &str, options: CompilerOptions) -> Result<CompilerOutput, MimaError> {
+        compile_source(input, &options, &|_| None)
+    }
+
+    /*
+     * The resolver is called with the path of every INCLUDE directive and has to return the content
+     * of that file, or undefined if it doesn't exist.
+     */
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
-    pub fn compile(input: &str) -> Result<CompilerOutput, String> {
-        let parsed = parse_assembly(input).map_err(|err| err.to_string())?;
-        generate_machinecode(&parsed).map_err(|err| err.to_string())
+    pub fn compile_with_resolver(input: &str, options: CompilerOptions, resolver: &js_sys::Function) -> Result<CompilerOutput, MimaError> {
+        let resolve = |path: &str| {
+            resolver
+                .call1(&JsValue::NULL, &JsValue::from_str(path))
+                .ok()
+                .and_then(|content| content.as_string())
+        };
+        compile_source(input, &options, &resolve)
+    }
+
+    /*
+     * Compiles the program and describes the error, if there is one, together with the span of
+     * the offending text so editors can underline it.
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn diagnose(input: &str, options: CompilerOptions) -> Option<CompilerDiagnostic> {
+        let error = compile_program(input, &options, &|_| None).err()?;
+        Some(CompilerDiagnostic { message: locale::compiler_message(&error), span: error.span(input), suggestion: error.suggestion() })
+    }
+
+    pub fn compile_source(input: &str, options: &CompilerOptions, resolver: &dyn Fn(&str) -> Option<String>) -> Result<CompilerOutput, MimaError> {
+        Ok(compile_program(input, options, resolver)?)
+    }
+
+    // The functions for JS turn errors into a MimaError, Rust callers get the error itself.
+    pub fn compile_program(input: &str, options: &CompilerOptions, resolver: &dyn Fn(&str) -> Option<String>) -> Result<CompilerOutput, CompilerError> {
+        generate_machinecode(&parse_assembly(input, options, resolver)?)
+    }
+
+    /*
+     * Compiles a relocatable object for the linker. References to symbols that aren't defined in
+     * the source become imports, EXPORT makes symbols available to other objects.
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn compile_object(input: &str) -> Result<ObjectFile, MimaError> {
+        Ok(build_object(input, &CompilerOptions::new())?)
+    }
+
+    pub fn build_object(input: &str, options: &CompilerOptions) -> Result<ObjectFile, CompilerError> {
+        let parsed = parse_assembly(input, options, &|_| None)?;
+        let (output, imports) = generate(&parsed, true)?;
+        let exports = parsed.exports.iter().map(|(name, _)| name.to_owned()).collect();
+        Ok(ObjectFile::new(output, imports, exports))
+    }
+
+    pub(crate) fn parse_assembly(input: &str, options: &CompilerOptions, resolver: &dyn Fn(&str) -> Option<String>) -> Result<ParsedProgram, CompilerError> {
+        let lines = preprocess(input, options, resolver)?;
+        assemble(&lines, options, &mut |source| parse_statement(source, options))
+    }
+
+    // Expands includes and macros, the remaining lines each hold a single statement.
+    pub(crate) fn preprocess(input: &str, options: &CompilerOptions, resolver: &dyn Fn(&str) -> Option<String>) -> Result<Vec<SourceLine>, CompilerError> {
+        let mut lines = expand_includes(split_lines(input), resolver)?;
+        if options.dialect == Dialect::Kit {
+            for line in lines.iter_mut() {
+                line.text = convert_kit_line(&line.text);
+            }
+        }
+        expand_macros(lines)
     }
 
-    fn parse_assembly(input: &str) -> Result<ParsedProgram, CompilerError> {
+    /*
+     * Builds the program from the statements of the lines. The statements are provided by the
+     * caller, which allows reusing statements of lines that didn't change.
+     */
+    pub(crate) fn assemble(
+        lines: &[SourceLine],
+        options: &CompilerOptions,
+        statement: &mut dyn FnMut(&SourceLine) -> Result<Statement, CompilerError>,
+    ) -> Result<ParsedProgram, CompilerError> {
         let mut variables: Vec<Variable> = vec![];
         let mut commands: Vec<Cmd> = vec![];
-        let lines: Vec<&str> = input
-            .split("\n")
-            .filter(|line| !line.starts_with(";"))
-            .filter(|line| !line.is_empty())
-            .collect();
-        for line in lines.to_owned() {
-            if VARIABLE_REGEX.is_match(line) {
-                let captures = VARIABLE_REGEX.captures(line).unwrap();
-                let name = captures.get(1).unwrap().as_str();
-                let value = captures
-                    .get(3)
-                    .map(|f| f.as_str().parse::<usize>().ok())
-                    .flatten();
-                variables.push(Variable {
-                    name: name.to_string(),
-                    value,
-                })
-            } else if INSTRUCTION_REGEX.is_match(line) {
-                let captures = INSTRUCTION_REGEX.captures(line).unwrap();
-                let name = captures.get(3).unwrap().as_str();
-                let value = captures.get(5);
-                let label = captures.get(2).map(|f| f.as_str().to_string());
-                let param = match value {
-                    Some(value) => match value.as_str().parse::<usize>() {
-                        Ok(number) => Param::Fixed(number),
-                        Err(_) => Param::Reference(value.as_str().to_string()),
-                    },
+        let mut constants: Vec<Constant> = vec![];
+        // Location counter, only set after an ORG directive
+        let mut location: Option<usize> = None;
+        let mut exports: Vec<(String, usize)> = vec![];
+        let mut title = None;
+        let mut end: Option<(Option<String>, usize)> = None;
+        for source in lines.iter() {
+            let statement = statement(source)?;
+            if end.is_some() {
+                return Err(CompilerError::CodeAfterEnd { line: source.line });
+            }
+            match statement {
+                Statement::Title(text) => title = Some(text),
+                Statement::End(entry) => end = Some((entry, source.line)),
+                Statement::Origin(adress) => location = Some(adress),
+                Statement::Variable { name, parameter, initializer } => {
+                    let initialized = initializer != Initializer::Default;
+                    let scope = Scope { constants: &constants, variables: &variables, commands: &commands, case_sensitive: options.case_sensitive };
+                    let (values, repeat) = initial_values(initializer, &scope, source.line)?;
+                    let variable = Variable {
+                        name,
+                        parameter,
+                        initialized,
+                        values,
+                        repeat,
+                        adress: location,
+                        source: source.to_owned(),
+                    };
+                    location = location.map(|adress| adress + variable.size());
+                    variables.push(variable);
+                }
+                Statement::Constant { name, value } => {
+                    if constants.iter().any(|constant| same_symbol(&constant.name, &name, options.case_sensitive)) {
+                        return Err(CompilerError::ConstantRedefinition { line: source.line, name });
+                    }
+                    constants.push(Constant { name, line: source.line, value });
+                }
+                Statement::Export(names) => exports.extend(names.into_iter().map(|name| (name, source.line))),
+                Statement::Instructions { label, lowered, param } => {
+                    for (index, (instruction, operand)) in lowered.into_iter().enumerate() {
+                        commands.push(Cmd {
+                            instruction,
+                            param: if operand { param.to_owned() } else { Param::None },
+                            label: if index == 0 { label.to_owned() } else { None },
+                            adress: location,
+                            source: source.to_owned(),
+                        });
+                        location = location.map(|adress| adress + 1);
+                    }
+                }
+            }
+        }
+        let parsed = ParsedProgram {
+            variables,
+            commands,
+            constants,
+            exports,
+            title,
+            entry: end.and_then(|(entry, line)| Some((entry?, line))),
+            case_sensitive: options.case_sensitive,
+        };
+        check_duplicate_symbols(&parsed)?;
+        Ok(parsed)
+    }
+
+    // Parses a single line. The result only depends on the text of the line and the options.
+    // Errors report the line of the source, blank lines and comments included.
+    pub(crate) fn parse_statement(source: &SourceLine, options: &CompilerOptions) -> Result<Statement, CompilerError> {
+        let out_of_range = |literal: &Literal| CompilerError::LiteralOutOfRange { line: source.line, value: literal.text.to_owned() };
+        match parse_line(&source.text, source.line)? {
+            Syntax::Origin(literal) => {
+                if !(0..1 << ADRESS_BITS).contains(&literal.value) {
+                    return Err(out_of_range(&literal));
+                }
+                Ok(Statement::Origin(literal.value as usize))
+            }
+            Syntax::Variable { name, parameter, initializer } => Ok(Statement::Variable { name, parameter, initializer }),
+            Syntax::Constant { name, value } => Ok(Statement::Constant { name, value }),
+            Syntax::Export(names) => Ok(Statement::Export(names)),
+            Syntax::Title(title) => Ok(Statement::Title(title)),
+            Syntax::End(entry) => Ok(Statement::End(entry)),
+            Syntax::Instruction { label, name, operand } => {
+                let lowered = match lower_pseudo_instruction(&name) {
+                    Some(lowered) => lowered,
+                    None => {
+                        let instruction = Instruction::from_string(&name).ok_or_else(|| CompilerError::UnknownInstruction {
+                            name: name.to_owned(),
+                            line: source.line,
+                            suggestion: suggest(&name, mnemonics(options.profile).iter().copied()),
+                        })?;
+                        vec![(instruction, true)]
+                    }
+                };
+                if let Some((unsupported, _)) = lowered.iter().find(|(instruction, _)| !options.profile.supports(*instruction)) {
+                    return Err(CompilerError::UnsupportedInstruction {
+                        name: unsupported.mnemonic().to_string(),
+                        line: source.line,
+                        profile: options.profile,
+                    });
+                }
+                // The instruction that receives the operand
+                let instruction = lowered.iter().find(|(_, operand)| *operand).unwrap().0;
+                match (instruction.takes_operand(), operand.is_some()) {
+                    (true, false) => return Err(CompilerError::MissingOperand { line: source.line, name }),
+                    (false, true) => return Err(CompilerError::UnexpectedOperand { line: source.line, name }),
+                    _ => (),
+                }
+                let param = match operand {
                     None => Param::None,
+                    Some(Expression { node: Node::Symbol(name), .. }) => Param::Reference(name),
+                    // Expressions without symbols are computed right away.
+                    Some(expression) if expression.symbols().is_empty() => {
+                        let value = expression.evaluate(source.line, &mut |name| {
+                            Err(CompilerError::UnknownVariable { name: name.to_string(), line: source.line, suggestion: None })
+                        })?;
+                        // Only constants may be negative, adresses are always unsigned.
+                        if value < 0 {
+                            let value = encode_signed(value, CONSTANT_BITS)
+                                .filter(|_| instruction.takes_constant())
+                                .ok_or(CompilerError::LiteralOutOfRange { line: source.line, value: expression.text })?;
+                            Param::Fixed(value)
+                        } else {
+                            Param::Fixed(value as usize)
+                        }
+                    }
+                    Some(expression) => Param::Expression(expression),
                 };
-                commands.push(Cmd {
-                    instruction: Instruction::from_string(name).ok_or(CompilerError::UnknownInstruction { name: name.to_string() })?,
-                    param,
-                    label,
+                Ok(Statement::Instructions { label, lowered, param })
+            }
+        }
+    }
+
+    // Variables, labels and constants share one namespace, mnemonics can't be used as names.
+    fn check_duplicate_symbols(parsed: &ParsedProgram) -> Result<(), CompilerError> {
+        let mut definitions: Vec<(&str, usize)> = parsed
+            .variables
+            .iter()
+            .map(|var| (var.name.as_str(), var.source.line))
+            .chain(
+                parsed
+                    .commands
+                    .iter()
+                    .filter_map(|cmd| cmd.label.as_ref().map(|label| (label.as_str(), cmd.source.line))),
+            )
+            .chain(parsed.constants.iter().map(|constant| (constant.name.as_str(), constant.line)))
+            .collect();
+        definitions.sort_by_key(|(_, line)| *line);
+        for (index, (name, line)) in definitions.iter().enumerate() {
+            if Instruction::from_string(name).is_some() || lower_pseudo_instruction(name).is_some() {
+                return Err(CompilerError::ReservedName { name: name.to_string(), line: *line });
+            }
+            let first = definitions[..index]
+                .iter()
+                .find(|(other, _)| same_symbol(other, name, parsed.case_sensitive));
+            if let Some((_, first_line)) = first {
+                return Err(CompilerError::DuplicateSymbol {
+                    name: name.to_string(),
+                    line: *line,
+                    first_line: *first_line,
                 });
-            } else {
-                return Err(CompilerError::InvalidLine { line: lines.iter().position(|&r| r == line).unwrap() });
             }
         }
-        Ok(ParsedProgram {
-            variables,
-            commands,
-        })
+        Ok(())
+    }
+
+    // Mnemonics of the instructions available on the profile, pseudo instructions included
+    fn mnemonics(profile: MachineProfile) -> Vec<&'static str> {
+        (0..=u8::MAX as usize)
+            .filter_map(Instruction::from_opcode)
+            .filter(|instruction| profile.supports(*instruction))
+            .map(Instruction::mnemonic)
+            .chain(["SUB"])
+            .collect()
+    }
+
+    /*
+     * The candidate a misspelled name was most likely meant to be, like LDV for LVD. Candidates
+     * that need more edits than a third of the name are too different to be a typo.
+     */
+    fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+        let limit = name.chars().count() / 3;
+        candidates
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= limit)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate.to_string())
+    }
+
+    // Edits needed to turn one name into the other ignoring case, swapping two neighbouring characters counts as one.
+    fn edit_distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.to_uppercase().chars().collect();
+        let b: Vec<char> = b.to_uppercase().chars().collect();
+        let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for (i, row) in distances.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, distance) in distances[0].iter_mut().enumerate() {
+            *distance = j;
+        }
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+                let mut distance = substitution.min(distances[i - 1][j] + 1).min(distances[i][j - 1] + 1);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    distance = distance.min(distances[i - 2][j - 2] + 1);
+                }
+                distances[i][j] = distance;
+            }
+        }
+        distances[a.len()][b.len()]
     }
 
-    fn generate_machinecode(parsed: &ParsedProgram) -> Result<CompilerOutput, CompilerError> {
-        let mut compiled = vec![];
-        for var in parsed.variables.to_owned() {
-            compiled.push(var.value.unwrap_or(0));
+    /*
+     * Pseudo instructions are lowered to a sequence of real instructions. The boolean marks the
+     * instruction that receives the operand.
+     * SUB x computes akku - x as NOT(NOT(akku) + x), which doesn't need a constant one.
+     */
+    fn lower_pseudo_instruction(name: &str) -> Option<Vec<(Instruction, bool)>> {
+        match name.to_uppercase().as_str() {
+            "SUB" => Some(vec![
+                (Instruction::NOT, false),
+                (Instruction::ADD, true),
+                (Instruction::NOT, false),
+            ]),
+            _ => None,
         }
-        let first_instruction = parsed.variables.len();
-        for cmd in parsed.commands.to_owned() {
+    }
+
+    /*
+     * Assigns an adress to everything that wasn't placed by an ORG directive. Without ORG the
+     * variables occupy the first adresses and the code follows them.
+     */
+    pub(crate) fn layout(parsed: &ParsedProgram) -> ParsedProgram {
+        let mut parsed = parsed.to_owned();
+        let mut location = 0;
+        for var in parsed.variables.iter_mut().filter(|var| var.adress.is_none()) {
+            var.adress = Some(location);
+            location += var.size();
+        }
+        for cmd in parsed.commands.iter_mut().filter(|cmd| cmd.adress.is_none()) {
+            cmd.adress = Some(location);
+            location += 1;
+        }
+        parsed
+    }
+
+    pub(crate) fn generate_machinecode(parsed: &ParsedProgram) -> Result<CompilerOutput, CompilerError> {
+        generate(parsed, false).map(|(output, _)| output)
+    }
+
+    // Unresolved references are only allowed in objects, where they are left to the linker.
+    fn generate(parsed: &ParsedProgram, linkable: bool) -> Result<(CompilerOutput, Vec<Import>), CompilerError> {
+        let parsed = &layout(parsed);
+        // Only variables and labels have an adress that can be exported.
+        for (name, line) in parsed.exports.iter() {
+            let variable = resolve_variable(&parsed.variables, name, parsed.case_sensitive);
+            if variable.or(resolve_label(&parsed.commands, name, parsed.case_sensitive)).is_none() {
+                return Err(CompilerError::UnknownExport { name: name.to_owned(), line: *line });
+            }
+        }
+        let mut imports = vec![];
+        let mut image: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut source_map = vec![];
+        let mut listing = vec![];
+        let mut relocations = vec![];
+        let mut data_relocations = vec![];
+        let scope = parsed.scope();
+        for var in parsed.variables.iter() {
+            let words = var
+                .values
+                .iter()
+                .map(|value| {
+                    encode_signed(evaluate(&scope, value, var.source.line)?, WORD_BITS)
+                        .ok_or(CompilerError::LiteralOutOfRange { line: var.source.line, value: value.text.to_owned() })
+                })
+                .collect::<Result<Vec<usize>, CompilerError>>()?;
+            for offset in 0..var.size() {
+                let adress = var.adress.unwrap() + offset;
+                let value = &words[offset % words.len()];
+                if adress_weight(parsed, &var.values[offset % words.len()], 0) == Some(1) {
+                    data_relocations.push(adress);
+                }
+                place(&mut image, adress, *value, var.source.line)?;
+                source_map.push(mapping(adress, &var.source));
+                listing.push((adress, listing_line(adress, *value, &format!("DS {value}"), Some(&var.source))));
+            }
+        }
+        let data_length: usize = parsed.variables.iter().map(|var| var.size()).sum();
+        /*
+         * Execution starts at the label given to END, else at a label called START, else at the
+         * first instruction. This way subroutines may be placed before the main program.
+         */
+        let start_adress = match &parsed.entry {
+            Some((name, line)) => resolve_label(&parsed.commands, name, parsed.case_sensitive)
+                .ok_or(CompilerError::UnknownEntry { name: name.to_owned(), line: *line })?,
+            None => resolve_label(&parsed.commands, START_LABEL, parsed.case_sensitive)
+                .unwrap_or(parsed.commands.first().map(|cmd| cmd.adress.unwrap()).unwrap_or(data_length)),
+        };
+        for cmd in parsed.commands.iter() {
             let command = match cmd.to_owned().param {
                 Param::Fixed(value) => Command {
                     instruction: cmd.instruction,
                     value,
                 },
-                // TODO: Checking if this is valid and eventually throw an error.
+                // The operand arity was already checked while parsing.
                 Param::None => Command {
                     instruction: cmd.instruction,
                     value: 0,
                 },
+                Param::Reference(name) if resolve_constant(&parsed.constants, &name, parsed.case_sensitive).is_some() => {
+                    let constant = resolve_constant(&parsed.constants, &name, parsed.case_sensitive).unwrap();
+                    if adress_weight(parsed, &constant.value, 0) == Some(1) {
+                        relocations.push(cmd.adress.unwrap());
+                    }
+                    let constant = evaluate(&scope, &constant.value, constant.line)?;
+                    // Constants follow the same rules as literals: only LDC and ADC accept negative values.
+                    let value = if cmd.instruction.takes_constant() {
+                        encode_signed(constant, CONSTANT_BITS)
+                    } else {
+                        usize::try_from(constant).ok()
+                    };
+                    Command {
+                        instruction: cmd.instruction,
+                        value: value.ok_or(CompilerError::LiteralOutOfRange {
+                            line: cmd.source.line,
+                            value: name.to_string(),
+                        })?,
+                    }
+                }
+                // Expressions may combine any symbols, so the kinds of the symbols aren't checked.
+                Param::Expression(expression) => {
+                    let value = evaluate(&scope, &expression, cmd.source.line)?;
+                    let value = if cmd.instruction.takes_constant() {
+                        encode_signed(value, CONSTANT_BITS)
+                    } else {
+                        usize::try_from(value).ok()
+                    };
+                    if adress_weight(parsed, &expression, 0) == Some(1) {
+                        relocations.push(cmd.adress.unwrap());
+                    }
+                    Command {
+                        instruction: cmd.instruction,
+                        value: value.ok_or(CompilerError::LiteralOutOfRange { line: cmd.source.line, value: expression.text })?,
+                    }
+                }
+                Param::Reference(name) if linkable && !is_defined(parsed, &name) => {
+                    imports.push(Import { adress: cmd.adress.unwrap(), name, line: cmd.source.line });
+                    Command {
+                        instruction: cmd.instruction,
+                        value: 0,
+                    }
+                }
                 Param::Reference(name) => {
-                    let resolved_var = resolve_variable(&parsed.variables, &name);
-                    if resolved_var.is_err()
-                        && (cmd.instruction == Instruction::JMP
-                            || cmd.instruction == Instruction::JMN)
-                    {
-                        // TODO: Forbid variable referencing in jumps
-                        let resolved_label =
-                            resolve_label(&parsed.commands, &name)? + parsed.variables.len();
-                        Command {
-                            instruction: cmd.instruction,
-                            value: resolved_label,
+                    let line = cmd.source.line;
+                    let variable = resolve_variable(&parsed.variables, &name, parsed.case_sensitive);
+                    let label = resolve_label(&parsed.commands, &name, parsed.case_sensitive);
+                    let labels = || parsed.commands.iter().filter_map(|cmd| cmd.label.as_deref());
+                    let variables = || parsed.variables.iter().map(|var| var.name.as_str());
+                    let value = if cmd.instruction.is_jump() {
+                        match (label, variable) {
+                            (Some(adress), _) => adress,
+                            (None, Some(_)) => return Err(CompilerError::VariableAsJumpTarget { name, line }),
+                            (None, None) => {
+                                let suggestion = suggest(&name, labels());
+                                return Err(CompilerError::UnknownLabel { name, line, suggestion });
+                            }
                         }
-                    } else if resolved_var.is_ok() {
-                        Command {
-                            instruction: cmd.instruction,
-                            value: resolved_var?,
+                    } else if cmd.instruction.takes_constant() {
+                        // LDC loads the adress of a symbol, which is how pointers are set up.
+                        match variable.or(label) {
+                            Some(adress) => adress,
+                            None => {
+                                let suggestion = suggest(&name, variables().chain(labels()));
+                                return Err(CompilerError::UnknownVariable { name, line, suggestion });
+                            }
                         }
                     } else {
-                        return Err(CompilerError::InvalidReference { line: parsed.commands.to_owned().iter().position(|r| *r == cmd).unwrap() });
+                        match (variable, label) {
+                            (Some(adress), _) => adress,
+                            (None, Some(_)) => return Err(CompilerError::LabelAsDataOperand { name, line }),
+                            (None, None) => {
+                                let suggestion = suggest(&name, variables());
+                                return Err(CompilerError::UnknownVariable { name, line, suggestion });
+                            }
+                        }
+                    };
+                    relocations.push(cmd.adress.unwrap());
+                    Command {
+                        instruction: cmd.instruction,
+                        value,
                     }
                 }
             };
-            compiled.push(command.to_usize());
+            let command = Command::new(command.instruction, command.value).ok_or(CompilerError::OperandOutOfRange {
+                line: cmd.source.line,
+                value: command.value,
+                bits: command.instruction.operand_bits(),
+            })?;
+            place(&mut image, cmd.adress.unwrap(), command.to_usize(), cmd.source.line)?;
+            source_map.push(mapping(cmd.adress.unwrap(), &cmd.source));
+            // Expanded pseudo instructions show up as their real instructions next to the source.
+            listing.push((cmd.adress.unwrap(), listing_line(cmd.adress.unwrap(), command.to_usize(), &command.to_string(), Some(&cmd.source))));
+        }
+        source_map.sort_by_key(|mapping| mapping.adress);
+        listing.sort_by_key(|(adress, _)| *adress);
+        // Gaps between the segments are filled with zeros.
+        let mut compiled = vec![0; image.keys().next_back().map_or(0, |adress| adress + 1)];
+        for (adress, value) in image {
+            compiled[adress] = value;
         }
-        Ok(CompilerOutput {
+        let output = CompilerOutput {
             mima_code: compiled,
-            start_adress: first_instruction,
+            start_adress,
+            warnings: collect_warnings(parsed),
+            source_map,
+            listing: listing.into_iter().map(|(_, line)| line).collect(),
+            symbols: symbol_table(parsed),
+            relocations,
+            data_relocations,
+            title: parsed.title.to_owned(),
+            entry: parsed.entry.as_ref().map(|(name, _)| name.to_owned()),
+            code_length: parsed.commands.len(),
+            data_length,
+        };
+        Ok((output, imports))
+    }
+
+    /*
+     * Returns the values of a DS directive and how often they are repeated. It is either a single
+     * value, a list of values ("1, 2, 3") or a repetition ("10 * 0"). The size of the variable has
+     * to be known before the layout, so the repetition count may only use constants.
+     */
+    fn initial_values(initializer: Initializer, scope: &Scope, line: usize) -> Result<(Vec<Expression>, usize), CompilerError> {
+        match initializer {
+            Initializer::Default => {
+                let zero = Expression { node: Node::Number(0), text: "0".to_string(), span: Span { start: 0, end: 0 } };
+                Ok((vec![zero], 1))
+            }
+            Initializer::Repeat { count, value } => {
+                let repeat = evaluate(scope, &count, line)?;
+                if !(1..1 << ADRESS_BITS).contains(&repeat) {
+                    return Err(CompilerError::LiteralOutOfRange { line, value: count.text });
+                }
+                Ok((vec![value], repeat as usize))
+            }
+            Initializer::List(values) => Ok((values, 1)),
+        }
+    }
+
+    // Symbols an expression can refer to. Before the layout only constants and ORG placed symbols have a value.
+    struct Scope<'a> {
+        constants: &'a [Constant],
+        variables: &'a [Variable],
+        commands: &'a [Cmd],
+        case_sensitive: bool,
+    }
+
+    // Constants stand for their value, variables and labels for their adress.
+    fn evaluate(scope: &Scope, expression: &Expression, line: usize) -> Result<i64, CompilerError> {
+        evaluate_nested(scope, expression, line, 0)
+    }
+
+    fn evaluate_nested(scope: &Scope, expression: &Expression, line: usize, depth: usize) -> Result<i64, CompilerError> {
+        expression.evaluate(line, &mut |name| {
+            if let Some(constant) = resolve_constant(scope.constants, name, scope.case_sensitive) {
+                if depth >= MAX_CONSTANT_DEPTH {
+                    return Err(CompilerError::CyclicConstant { name: name.to_string(), line });
+                }
+                return evaluate_nested(scope, &constant.value, constant.line, depth + 1);
+            }
+            let adress = scope
+                .variables
+                .iter()
+                .find(|var| same_symbol(&var.name, name, scope.case_sensitive))
+                .map(|var| var.adress)
+                .or_else(|| {
+                    scope
+                        .commands
+                        .iter()
+                        .find(|cmd| cmd.label.as_ref().is_some_and(|label| same_symbol(label, name, scope.case_sensitive)))
+                        .map(|cmd| cmd.adress)
+                });
+            match adress {
+                Some(Some(adress)) => Ok(adress as i64),
+                Some(None) => Err(CompilerError::NotConstant { line, expression: expression.text.to_owned() }),
+                None => {
+                    let names = scope
+                        .constants
+                        .iter()
+                        .map(|constant| constant.name.as_str())
+                        .chain(scope.variables.iter().map(|var| var.name.as_str()))
+                        .chain(scope.commands.iter().filter_map(|cmd| cmd.label.as_deref()));
+                    Err(CompilerError::UnknownVariable { name: name.to_string(), line, suggestion: suggest(name, names) })
+                }
+            }
         })
     }
 
     /*
-     * This function resolves variable references. The variables are placed in the first n adresses, so
-     * the resolving is simply determining the position in the var array.
+     * How often adresses are added up in the expression, None if they are multiplied or divided.
+     * Operands are only relocated if the expression contains exactly one adress, like "LOOP+2".
      */
-    fn resolve_variable(variables: &Vec<Variable>, reference: &str) -> Result<usize, CompilerError> {
-        let mut memory_position = 0 as usize;
-        for var in variables {
-            if var.name == *reference {
-                return Ok(memory_position);
+    fn adress_weight(parsed: &ParsedProgram, expression: &Expression, depth: usize) -> Option<i64> {
+        match &expression.node {
+            Node::Number(_) => Some(0),
+            Node::Symbol(name) => match resolve_constant(&parsed.constants, name, parsed.case_sensitive) {
+                Some(constant) if depth < MAX_CONSTANT_DEPTH => adress_weight(parsed, &constant.value, depth + 1),
+                Some(_) => None,
+                None => Some(1),
+            },
+            Node::Negate(operand) => adress_weight(parsed, operand, depth).map(|weight| -weight),
+            // Not produced by the assembler's parser
+            Node::Memory(_) => None,
+            Node::Binary(operator, left, right) => {
+                let (left, right) = (adress_weight(parsed, left, depth)?, adress_weight(parsed, right, depth)?);
+                match operator {
+                    BinaryOperator::Add => Some(left + right),
+                    BinaryOperator::Subtract => Some(left - right),
+                    _ if left == 0 && right == 0 => Some(0),
+                    _ => None,
+                }
             }
-            memory_position += 1;
         }
-        return Err(CompilerError::UnknownVariable { name: reference.to_string() });
     }
 
-    fn resolve_label(commands: &Vec<Cmd>, label: &str) -> Result<usize, CompilerError> {
-        let mut memory_position = 0 as usize;
-        for cmd in commands {
-            if cmd.label.is_some() && cmd.label.to_owned().unwrap() == label {
-                return Ok(memory_position);
+    fn symbol_table(parsed: &ParsedProgram) -> Vec<Symbol> {
+        let exported = |name: &str| parsed.exports.iter().any(|(export, _)| same_symbol(export, name, parsed.case_sensitive));
+        let variables = parsed.variables.iter().map(|var| Symbol {
+            name: var.name.to_owned(),
+            adress: var.adress.unwrap(),
+            size: var.size(),
+            kind: SymbolKind::Variable,
+            parameter: var.parameter,
+            initialized: var.initialized,
+            exported: exported(&var.name),
+        });
+        let labels = parsed.commands.iter().filter(|cmd| cmd.label.is_some()).map(|cmd| Symbol {
+            name: cmd.label.to_owned().unwrap(),
+            adress: cmd.adress.unwrap(),
+            size: 1,
+            kind: SymbolKind::Label,
+            parameter: None,
+            initialized: true,
+            exported: exported(cmd.label.as_ref().unwrap()),
+        });
+        let mut symbols: Vec<Symbol> = variables.chain(labels).collect();
+        symbols.sort_by_key(|symbol| symbol.adress);
+        symbols
+    }
+
+    fn mapping(adress: usize, source: &SourceLine) -> SourceMapping {
+        SourceMapping {
+            adress,
+            line: source.line,
+            macro_line: source.macro_line,
+        }
+    }
+
+    fn place(image: &mut BTreeMap<usize, usize>, adress: usize, value: usize, line: usize) -> Result<(), CompilerError> {
+        match image.insert(adress, value) {
+            Some(_) => Err(CompilerError::OverlappingAdress { adress, line }),
+            None => Ok(()),
+        }
+    }
+
+    // Encodes the value as a two's complement number, positive values may use the full unsigned range.
+    fn encode_signed(value: i64, bits: u32) -> Option<usize> {
+        let limit = 1i64 << bits;
+        if value < -(limit / 2) || value >= limit {
+            None
+        } else if value < 0 {
+            Some((value + limit) as usize)
+        } else {
+            Some(value as usize)
+        }
+    }
+
+    /*
+     * This function resolves variable references to the adress assigned during the layout.
+     */
+    fn resolve_variable(variables: &[Variable], reference: &str, case_sensitive: bool) -> Option<usize> {
+        variables
+            .iter()
+            .find(|var| same_symbol(&var.name, reference, case_sensitive))
+            .map(|var| var.adress.unwrap())
+    }
+
+    fn resolve_constant<'a>(constants: &'a [Constant], reference: &str, case_sensitive: bool) -> Option<&'a Constant> {
+        constants.iter().find(|constant| same_symbol(&constant.name, reference, case_sensitive))
+    }
+
+    fn resolve_label(commands: &[Cmd], label: &str, case_sensitive: bool) -> Option<usize> {
+        commands
+            .iter()
+            .find(|cmd| cmd.label.as_ref().is_some_and(|name| same_symbol(name, label, case_sensitive)))
+            .map(|cmd| cmd.adress.unwrap())
+    }
+    fn same_symbol(a: &str, b: &str, case_sensitive: bool) -> bool {
+        if case_sensitive {
+            a == b
+        } else {
+            a.eq_ignore_ascii_case(b)
+        }
+    }
+
+    fn collect_warnings(parsed: &ParsedProgram) -> Vec<CompilerWarning> {
+        let mut warnings = case_warnings(parsed);
+        warnings.append(&mut unused_symbol_warnings(parsed));
+        warnings.append(&mut unreachable_code_warnings(parsed));
+        warnings.sort_by_key(|warning| warning.line);
+        warnings
+    }
+
+    fn warning(kind: WarningKind, line: usize, message: String) -> CompilerWarning {
+        CompilerWarning { kind, line, message }
+    }
+
+    // Warns about symbols that only differ in case, as they are easy to mix up.
+    fn case_warnings(parsed: &ParsedProgram) -> Vec<CompilerWarning> {
+        let symbols: Vec<(&String, usize)> = parsed
+            .variables
+            .iter()
+            .map(|var| (&var.name, var.source.line))
+            .chain(parsed.commands.iter().filter_map(|cmd| cmd.label.as_ref().map(|label| (label, cmd.source.line))))
+            .chain(parsed.constants.iter().map(|constant| (&constant.name, constant.line)))
+            .collect();
+        let mut warnings = vec![];
+        for (index, (a, _)) in symbols.iter().enumerate() {
+            for (b, line) in symbols.iter().skip(index + 1) {
+                if a != b && a.eq_ignore_ascii_case(b) {
+                    warnings.push(warning(WarningKind::CaseConflict, *line, format!("Symbols '{a}' and '{b}' only differ in case.")));
+                }
+            }
+        }
+        warnings
+    }
+
+    // Label of the instruction execution starts at, chosen like the start adress
+    fn entry_label(parsed: &ParsedProgram) -> Option<&str> {
+        match &parsed.entry {
+            Some((name, _)) => Some(name),
+            None => parsed
+                .commands
+                .iter()
+                .filter_map(|cmd| cmd.label.as_deref())
+                .find(|label| same_symbol(label, START_LABEL, parsed.case_sensitive))
+                .or_else(|| parsed.commands.first()?.label.as_deref()),
+        }
+    }
+
+    // Exported symbols count as referenced since other objects may use them, the entry label since execution starts there.
+    fn is_referenced(parsed: &ParsedProgram, name: &str) -> bool {
+        let exported = parsed.exports.iter().any(|(export, _)| same_symbol(export, name, parsed.case_sensitive));
+        let entry = entry_label(parsed).is_some_and(|entry| same_symbol(entry, name, parsed.case_sensitive));
+        let mentions = |expression: &Expression| expression.symbols().iter().any(|symbol| same_symbol(symbol, name, parsed.case_sensitive));
+        exported
+            || entry
+            || parsed.commands.iter().any(|cmd| match &cmd.param {
+                Param::Reference(reference) => same_symbol(reference, name, parsed.case_sensitive),
+                Param::Expression(expression) => mentions(expression),
+                _ => false,
+            })
+            || parsed.variables.iter().any(|var| var.values.iter().any(mentions))
+            || parsed.constants.iter().any(|constant| mentions(&constant.value))
+    }
+
+    fn is_defined(parsed: &ParsedProgram, name: &str) -> bool {
+        resolve_variable(&parsed.variables, name, parsed.case_sensitive).is_some()
+            || resolve_label(&parsed.commands, name, parsed.case_sensitive).is_some()
+            || resolve_constant(&parsed.constants, name, parsed.case_sensitive).is_some()
+    }
+
+    fn unused_symbol_warnings(parsed: &ParsedProgram) -> Vec<CompilerWarning> {
+        let variables = parsed
+            .variables
+            .iter()
+            .filter(|var| !is_referenced(parsed, &var.name))
+            .map(|var| warning(WarningKind::UnusedVariable, var.source.line, format!("Variable '{}' is never used.", var.name)));
+        let labels = parsed
+            .commands
+            .iter()
+            .filter_map(|cmd| cmd.label.as_ref().map(|label| (label, cmd.source.line)))
+            .filter(|(label, _)| !is_referenced(parsed, label))
+            .map(|(label, line)| warning(WarningKind::UnusedLabel, line, format!("Label '{label}' is never jumped to.")));
+        variables.chain(labels).collect()
+    }
+
+    /*
+     * Code directly after an unconditional JMP, RET or HALT can only be reached through a jump, so it
+     * is unreachable if it doesn't carry a referenced label. Only the first instruction of such a
+     * block is reported.
+     */
+    fn unreachable_code_warnings(parsed: &ParsedProgram) -> Vec<CompilerWarning> {
+        let mut warnings = vec![];
+        let mut reachable = true;
+        let mut previous_adress: Option<usize> = None;
+        for cmd in parsed.commands.iter() {
+            let targeted = cmd.label.as_ref().is_some_and(|label| is_referenced(parsed, label));
+            // A new segment placed by ORG doesn't follow the previous instruction.
+            let contiguous = previous_adress.is_some_and(|adress| cmd.adress == Some(adress + 1));
+            if targeted || !contiguous {
+                reachable = true;
             }
-            memory_position += 1;
+            if !reachable {
+                warnings.push(warning(WarningKind::UnreachableCode, cmd.source.line, "Instruction is unreachable.".to_string()));
+                // Report the block only once
+                reachable = true;
+            }
+            if matches!(cmd.instruction, Instruction::JMP | Instruction::JIND | Instruction::HALT | Instruction::RET) {
+                reachable = false;
+            }
+            previous_adress = cmd.adress;
         }
-        return Err(CompilerError::UnknownLabel { name: label.to_string() });
+        warnings
     }
+
+    // What a single line of the source defines, before adresses are assigned
+    #[derive(Clone, Debug, PartialEq)]
+    pub(crate) enum Statement {
+        Origin(usize),
+        Variable { name: String, parameter: Option<Parameter>, initializer: Initializer },
+        Constant { name: String, value: Expression },
+        Export(Vec<String>),
+        Title(String),
+        End(Option<String>),
+        // Pseudo instructions are already lowered, the boolean marks the one receiving the operand.
+        Instructions { label: Option<String>, lowered: Vec<(Instruction, bool)>, param: Param },
+    }
+
     // Struct representing the step between parsing and generating assembly code
-    #[derive(Clone, Debug)]
-    struct ParsedProgram {
+    #[derive(Clone, Debug, PartialEq)]
+    pub(crate) struct ParsedProgram {
         pub variables: Vec<Variable>,
         pub commands: Vec<Cmd>,
+        pub constants: Vec<Constant>,
+        // Exported names and the line of their EXPORT directive
+        pub exports: Vec<(String, usize)>,
+        pub title: Option<String>,
+        // Label of the END directive and its line
+        pub entry: Option<(String, usize)>,
+        pub case_sensitive: bool,
     }
-    #[derive(Clone, Debug)]
-    struct Variable {
+    #[derive(Clone, Debug, PartialEq)]
+    pub(crate) struct Variable {
         pub name: String,
-        pub value: Option<usize>,
+        pub parameter: Option<Parameter>,
+        // Whether the DS directive gave a value
+        pub initialized: bool,
+        // One value per occupied memory word, the whole list is repeated 'repeat' times.
+        pub values: Vec<Expression>,
+        pub repeat: usize,
+        // Set by ORG directives or during the layout
+        pub adress: Option<usize>,
+        pub source: SourceLine,
     }
 
+    impl Variable {
+        // Amount of occupied memory words
+        fn size(&self) -> usize {
+            self.values.len() * self.repeat
+        }
+    }
+
+    impl ParsedProgram {
+        fn scope(&self) -> Scope<'_> {
+            Scope {
+                constants: &self.constants,
+                variables: &self.variables,
+                commands: &self.commands,
+                case_sensitive: self.case_sensitive,
+            }
+        }
+    }
+
+    // Symbolic constants defined with EQU, they don't occupy any memory.
     #[derive(Clone, Debug, PartialEq)]
-    struct Cmd {
+    pub(crate) struct Constant {
+        pub name: String,
+        pub line: usize,
+        pub value: Expression,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub(crate) struct Cmd {
         pub instruction: Instruction,
         pub param: Param,
         pub label: Option<String>,
+        pub adress: Option<usize>,
+        pub source: SourceLine,
     }
     #[derive(Clone, Debug, PartialEq)]
-    enum Param {
+    pub(crate) enum Param {
         Fixed(usize),
         Reference(String),
+        // Computed once all adresses are known
+        Expression(Expression),
         None,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{compiler::compiler, mima::Command};
+    use crate::{compiler::{compiler, CompilerError, CompilerOptions, CompilerOutput, Symbol, SymbolKind, WarningKind}, mima::Command};
 
     #[test]
     // Tests a simple addition program
@@ -255,7 +1602,7 @@ c: DS";
             halt.to_usize(),
         ];
         let compiled = compiler::compile(assembly_source);
-        assert_eq!(compiled.is_ok(), true);
+        assert!(compiled.is_ok());
         let compiled = compiled.unwrap();
         assert_eq!(compiled.get_mima_code(), mima_code);
         assert_eq!(compiled.get_start_adress(), 3);
@@ -334,9 +1681,491 @@ FINISH: HALT
             .to_usize(),
         ];
         let compiled = compiler::compile(assembly_source);
-        assert_eq!(compiled.is_ok(), true);
+        assert!(compiled.is_ok());
         let compiled = compiled.unwrap();
         assert_eq!(compiled.get_mima_code(), mima_code);
         assert_eq!(compiled.get_start_adress(), 3);
     }
+    #[test]
+    fn negative_literals() {
+        let assembly_source = "a: DS -1
+b: DS -8388608
+LDC -5
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let ldc = Command {
+            instruction: crate::mima::Instruction::LDC,
+            value: 0xFFFFB,
+        };
+        assert_eq!(compiled.get_mima_code()[0], 0xFFFFFF);
+        assert_eq!(compiled.get_mima_code()[1], 0x800000);
+        assert_eq!(compiled.get_mima_code()[2], ldc.to_usize());
+    }
+    #[test]
+    fn negative_literals_out_of_range() {
+        assert!(compiler::compile("a: DS -8388609\nHALT").is_err());
+        assert!(compiler::compile("a: DS 16777216\nHALT").is_err());
+        assert!(compiler::compile("LDC -524289\nHALT").is_err());
+        // Negative adresses make no sense
+        assert!(compiler::compile("LDV -1\nHALT").is_err());
+    }
+    #[test]
+    fn hex_and_binary_literals() {
+        let assembly_source = "a: DS 0x1F
+b: DS 0b1010
+c: DS $FF
+d: DS -0x1
+LDC 0x10
+LDV $2
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let ldc = Command {
+            instruction: crate::mima::Instruction::LDC,
+            value: 16,
+        };
+        let ldv = Command {
+            instruction: crate::mima::Instruction::LDV,
+            value: 2,
+        };
+        assert_eq!(compiled.get_mima_code()[0..4], [31, 10, 255, 0xFFFFFF]);
+        assert_eq!(compiled.get_mima_code()[4], ldc.to_usize());
+        assert_eq!(compiled.get_mima_code()[5], ldv.to_usize());
+    }
+    #[test]
+    fn equ_constants() {
+        let assembly_source = "SIZE EQU 10
+OFFSET EQU -2
+PORT EQU 0x20
+a: DS 1
+LDC SIZE
+LDC OFFSET
+STV PORT
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let expected = [
+            Command { instruction: crate::mima::Instruction::LDC, value: 10 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::LDC, value: 0xFFFFE }.to_usize(),
+            Command { instruction: crate::mima::Instruction::STV, value: 0x20 }.to_usize(),
+        ];
+        // Constants don't allocate memory, so only the variable precedes the code.
+        assert_eq!(compiled.get_start_adress(), 1);
+        assert_eq!(compiled.get_mima_code()[1..4], expected);
+    }
+    #[test]
+    fn constant_expressions() {
+        let assembly_source = "BUFSIZE EQU 4
+buf: DS BUFSIZE * 0
+tbl: DS 1, 2, 3
+tbl_end: EQU tbl+3
+ptr: DS tbl_end - (BUFSIZE-1)
+LOOP: LDC BUFSIZE-1
+ADD tbl+1
+JMP LOOP+2
+LDC tbl_end
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code[0..8], [0, 0, 0, 0, 1, 2, 3, 4]);
+        let expected = [
+            Command { instruction: crate::mima::Instruction::LDC, value: 3 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::ADD, value: 5 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::JMP, value: 10 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::LDC, value: 7 }.to_usize(),
+        ];
+        assert_eq!(code[8..12], expected);
+        // Operands containing a single adress move with the program.
+        assert_eq!(compiled.get_relocations(), vec![9, 10, 11]);
+        assert_eq!(compiled.get_data_relocations(), vec![7]);
+        // Symbols used in expressions count as referenced.
+        assert!(compiled.get_warnings().iter().all(|warning| !warning.get_message().contains("'tbl'")));
+        let cyclic = compiler::compile("A EQU B+1\nB EQU A\nLDC A");
+        assert!(cyclic.is_err_and(|err| err.to_string().contains("in terms of itself")));
+        let adress_count = compiler::compile("buf: DS later * 0\nlater: HALT");
+        assert!(adress_count.is_err_and(|err| err.to_string().contains("must be constant") || err.to_string().contains("'later'")));
+        assert!(compiler::compile("LDV 1/0").is_err_and(|err| err.to_string().contains("Division by zero")));
+        assert!(compiler::compile("LDC 0xFFFFF+1").is_err());
+        assert!(compiler::compile("LDV 2-3").is_err());
+    }
+    #[test]
+    fn org_directive() {
+        let assembly_source = "ORG 0x10
+a: DS 7
+b: DS
+* = 0x20
+START: LDV a
+STV b
+JMP START";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code.len(), 0x23);
+        assert_eq!(code[0x10], 7);
+        assert_eq!(code[0x20], Command { instruction: crate::mima::Instruction::LDV, value: 0x10 }.to_usize());
+        assert_eq!(code[0x21], Command { instruction: crate::mima::Instruction::STV, value: 0x11 }.to_usize());
+        assert_eq!(code[0x22], Command { instruction: crate::mima::Instruction::JMP, value: 0x20 }.to_usize());
+        assert_eq!(compiled.get_start_adress(), 0x20);
+    }
+    #[test]
+    fn comments_everywhere() {
+        let assembly_source = "; header comment
+    ; indented comment
+a: DS 22 ; first summand
+b: DS 20;second summand
+
+\t
+START: LDV a ; entry point
+ADD b ; add offset
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code[0..2], [22, 20]);
+        assert_eq!(code[2], Command { instruction: crate::mima::Instruction::LDV, value: 0 }.to_usize());
+        assert_eq!(code[3], Command { instruction: crate::mima::Instruction::ADD, value: 1 }.to_usize());
+    }
+    #[test]
+    fn identifiers_with_digits() {
+        let assembly_source = "my_var2: DS 5
+ONE_1 EQU 1
+loop2: LDV my_var2
+ADC ONE_1
+STV my_var2
+JMP loop2";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code[1], Command { instruction: crate::mima::Instruction::LDV, value: 0 }.to_usize());
+        assert_eq!(code[4], Command { instruction: crate::mima::Instruction::JMP, value: 1 }.to_usize());
+        assert_eq!(compiled.get_symbols()[1].get_name(), "loop2");
+        assert!(compiler::compile("2loop: HALT").is_err());
+        assert!(compiler::compile("_x: DS 1\nHALT").is_err());
+        let reserved = compiler::compile("add: DS 1\nHALT").unwrap_err();
+        assert_eq!(reserved.to_string(), "Symbol 'add' in line '1' can't be named like an instruction.");
+        assert!(compiler::compile("sub EQU 1\nHALT").is_err_and(|err| err.to_string().contains("'sub'")));
+    }
+    #[test]
+    fn whitespace_and_trailing_garbage() {
+        let compiled = compiler::compile("a:\tDS\t5   \nSTART :\tLDV\ta  \n\tHALT\t").unwrap();
+        assert_eq!(compiled.get_mima_code()[1], Command { instruction: crate::mima::Instruction::LDV, value: 0 }.to_usize());
+        let err = compiler::compile("START: JMP 10abc").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid literal '10abc' in line '1' at column '12'.");
+        assert!(compiler::compile("LDV a b\na: DS").is_err_and(|err| err.to_string().contains("Unexpected 'b'")));
+    }
+    #[test]
+    fn case_insensitive_mnemonics() {
+        let compiled = compiler::compile("a: ds 1\nldv a\nAdd a\nhalt").unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code[1], Command { instruction: crate::mima::Instruction::LDV, value: 0 }.to_usize());
+        assert_eq!(code[2], Command { instruction: crate::mima::Instruction::ADD, value: 0 }.to_usize());
+        assert_eq!(code[3], Command { instruction: crate::mima::Instruction::HALT, value: 0 }.to_usize());
+    }
+    #[test]
+    fn symbol_case_rules() {
+        let assembly_source = "Counter: DS 1
+LDV counter
+HALT";
+        assert!(compiler::compile(assembly_source).is_err());
+        let mut options = CompilerOptions::new();
+        options.case_sensitive = false;
+        let compiled = compiler::compile_with_options(assembly_source, options).unwrap();
+        assert_eq!(compiled.get_mima_code()[1], Command { instruction: crate::mima::Instruction::LDV, value: 0 }.to_usize());
+
+        let compiled = compiler::compile("max: DS 1\nMAX: DS 2\nHALT").unwrap();
+        let case_warnings: Vec<String> = compiled
+            .get_warnings()
+            .iter()
+            .filter(|warning| warning.get_kind() == WarningKind::CaseConflict)
+            .map(|warning| warning.get_message())
+            .collect();
+        assert_eq!(case_warnings, vec!["Symbols 'max' and 'MAX' only differ in case."]);
+    }
+    #[test]
+    fn machine_profiles() {
+        let mut options = CompilerOptions::new();
+        options.profile = crate::mima::MachineProfile::Basic;
+        let error = compiler::compile_with_options("LDC 1\nCALL END\nEND: HALT", options);
+        assert_eq!(error.err().unwrap().to_string(), "Instruction 'CALL' in line '2' isn't available on the Basic profile.");
+        assert!(compiler::compile_with_options("a: DS\nSUB a\nHALT", options).is_ok());
+        options.profile = crate::mima::MachineProfile::Extended;
+        assert!(compiler::compile_with_options("ADC 1\nHALT", options).is_ok());
+        assert!(compiler::compile_with_options("RET", options).is_err());
+    }
+    #[test]
+    fn sub_lowering() {
+        let compiled = compiler::compile("a: DS 3\nLOOP: SUB a\nJMP LOOP").unwrap();
+        let expected = [
+            3,
+            Command { instruction: crate::mima::Instruction::NOT, value: 0 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::ADD, value: 0 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::NOT, value: 0 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::JMP, value: 1 }.to_usize(),
+        ];
+        assert_eq!(compiled.get_mima_code(), expected);
+        // All words of the expansion map to the SUB line.
+        let lines: Vec<usize> = compiled.get_source_map().iter().map(|mapping| mapping.line).collect();
+        assert_eq!(lines, vec![1, 2, 2, 2, 3]);
+    }
+    #[test]
+    fn listing() {
+        let compiled = compiler::compile("a: DS 22\nb: DS 3\nLDV a ; load\nSUB b\nHALT").unwrap();
+        let listing = compiled.get_listing();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].starts_with("ADDR"));
+        assert_eq!(lines[1], "00000  000016  0000 0000 0000 0000 0001 0110  DS 22            1: a: DS 22");
+        assert_eq!(lines[3], "00002  100000  0001 0000 0000 0000 0000 0000  LDV 0            3: LDV a");
+        assert_eq!(lines[4], "00003  F10000  1111 0001 0000 0000 0000 0000  NOT              4: SUB b");
+        assert_eq!(lines[5], "00004  300001  0011 0000 0000 0000 0000 0001  ADD 1            4: SUB b");
+
+        let raw = CompilerOutput::new(vec![0x100000], 0).get_listing();
+        assert_eq!(raw.lines().nth(1), Some("00000  100000  0001 0000 0000 0000 0000 0000  LDV 0"));
+    }
+    #[test]
+    fn symbol_table() {
+        let compiled = compiler::compile("ORG 4\ntbl: DS 1, 2\ncount: DS\nSTART: LDV count\nLOOP: JMP LOOP").unwrap();
+        let symbols: Vec<(String, usize, usize, SymbolKind)> = compiled
+            .get_symbols()
+            .iter()
+            .map(|symbol| (symbol.get_name(), symbol.get_adress(), symbol.get_size(), symbol.get_kind()))
+            .collect();
+        assert_eq!(
+            symbols,
+            vec![
+                ("tbl".to_string(), 4, 2, SymbolKind::Variable),
+                ("count".to_string(), 6, 1, SymbolKind::Variable),
+                ("START".to_string(), 7, 1, SymbolKind::Label),
+                ("LOOP".to_string(), 8, 1, SymbolKind::Label),
+            ]
+        );
+    }
+    #[test]
+    fn parameters() {
+        let compiled = compiler::compile("n: DS INPUT\nlimit: DS input 10\nresult: DS OUTPUT\ntmp: DS\nSTART: LDV n\nSTV result\nHALT").unwrap();
+        let names = |symbols: Vec<Symbol>| symbols.iter().map(|symbol| symbol.get_name()).collect::<Vec<String>>();
+        assert_eq!(names(compiled.get_inputs()), vec!["n", "limit"]);
+        assert_eq!(names(compiled.get_outputs()), vec!["result"]);
+        assert_eq!(compiled.get_mima_code()[1], 10);
+        let symbols = compiled.get_symbols();
+        assert_eq!(symbols[3].get_parameter(), None);
+        assert_eq!(symbols[4].get_parameter(), None);
+        let initialized: Vec<bool> = symbols.iter().map(|symbol| symbol.is_initialized()).collect();
+        assert_eq!(initialized, vec![false, true, false, false, true]);
+    }
+    #[test]
+    fn program_metadata() {
+        let source = "TITLE \"Double\"\nn: DS 4\nbuffer: DS 0, 0\nHELPER: ADD n\nRET\nMAIN: LDV n\nCALL HELPER\nSTV buffer\nHALT\nEND MAIN";
+        let compiled = compiler::compile(source).unwrap();
+        assert_eq!(compiled.get_title().as_deref(), Some("Double"));
+        assert_eq!(compiled.get_entry().as_deref(), Some("MAIN"));
+        assert_eq!((compiled.get_code_length(), compiled.get_data_length()), (6, 3));
+        assert_eq!(compiled.get_start_adress(), 5);
+        assert!(compiled.get_warnings().is_empty());
+        let mut mima = crate::mima::Mima::new();
+        mima.load(compiled);
+        mima.run();
+        assert_eq!(mima.get_akku(), 8);
+        let plain = compiler::compile("a: DS\nSTART: HALT\nEND").unwrap();
+        assert_eq!((plain.get_title(), plain.get_entry(), plain.get_start_adress()), (None, None, 1));
+        let missing = compiler::compile("HALT\nEND NOPE");
+        assert!(missing.is_err_and(|err| err.to_string() == "Entry label 'NOPE' in line '2' isn't defined."));
+        // Without an entry label, a label called START is the entry.
+        let start = compiler::compile("DOUBLE: ADD n\nRET\nn: DS 3\nSTART: LDV n\nCALL DOUBLE\nHALT").unwrap();
+        assert_eq!(start.get_start_adress(), 3);
+        assert!(start.get_warnings().is_empty());
+        let subroutine = compiler::compile("n: DS 1\nINC: LDV n\nADD n\nRET\nSTART: CALL INC\nHALT").unwrap();
+        assert!(subroutine.get_warnings().is_empty());
+        let mut mima = crate::mima::Mima::new();
+        mima.load(start);
+        mima.run();
+        assert_eq!(mima.get_akku(), 6);
+        let after = compiler::compile("HALT\nEND\nHALT");
+        assert!(after.is_err_and(|err| err.to_string() == "Line '3' follows the END directive."));
+    }
+    #[test]
+    fn original_line_numbers() {
+        // Blank lines and comments count, so errors point at the line shown in the editor.
+        let header = "; Computes nothing\n\n; at all\n";
+        let error = |code: &str| compiler::compile(&format!("{header}{code}")).unwrap_err();
+        assert_eq!(error("LDC 0\nORG 0x200000").to_string(), "Literal '0x200000' in line '5' is out of range.");
+        assert_eq!(error("N EQU 1\n\nN EQU 2").to_string(), "Constant 'N' in line '6' is already defined.");
+        assert_eq!(error("LDV -1").to_string(), "Literal '-1' in line '4' is out of range.");
+        assert_eq!(error("HALT\n  ; done\nFOO 1").to_string(), "Couldn't parse instruction 'FOO' in line '6'.");
+        assert_eq!(error("X EQU -1\nSTV X\nHALT").to_string(), "Literal 'X' in line '5' is out of range.");
+    }
+    #[test]
+    fn error_spans() {
+        let span = |source: &str| {
+            let diagnostic = compiler::diagnose(source, CompilerOptions::new()).unwrap();
+            let span = diagnostic.get_span().unwrap();
+            (span.line, span.start, span.end)
+        };
+        assert_eq!(span("a: DS\nLDV b\nHALT"), (2, 4, 5));
+        assert_eq!(span("  FOO 1"), (1, 2, 5));
+        assert_eq!(span("a: DS 1\nLDC #5"), (2, 4, 5));
+        // Columns count UTF-16 code units.
+        assert_eq!(span("TITLE \"ü\" FOO"), (1, 10, 13));
+        assert_eq!(span("LDC 4 / (2 - 2)\nHALT"), (1, 4, 15));
+        assert_eq!(span("a: DS 0x1000000"), (1, 6, 15));
+        // Errors without a token cover the code of the line.
+        assert_eq!(span("HALT\nEND\n  HALT ; again"), (3, 2, 6));
+        assert_eq!(compiler::diagnose("HALT", CompilerOptions::new()), None);
+        let diagnostic = compiler::diagnose("JMP NOWHERE", CompilerOptions::new()).unwrap();
+        assert_eq!(diagnostic.get_message(), "Couldn't find label 'NOWHERE' in line '1'.");
+    }
+    #[test]
+    fn typo_suggestions() {
+        let error = |source: &str| compiler::compile(source).unwrap_err();
+        assert_eq!(error("a: DS\nLVD a").to_string(), "Couldn't parse instruction 'LVD' in line '2'. Did you mean 'LDV'?");
+        assert_eq!(error("counter: DS\nLDV conter\nHALT").to_string(), "Couldn't find variable 'conter' in line '2'. Did you mean 'counter'?");
+        assert_eq!(error("LOOP: JMP LOPO").to_string(), "Couldn't find label 'LOPO' in line '1'. Did you mean 'LOOP'?");
+        assert_eq!(error("SIZE EQU 4\nLDC SIZ+1").to_string(), "Couldn't find variable 'SIZ' in line '2'. Did you mean 'SIZE'?");
+        // Names that are too different get no suggestion.
+        assert_eq!(error("a: DS\nLDV b\nHALT").to_string(), "Couldn't find variable 'b' in line '2'.");
+        assert_eq!(error("XYZ").to_string(), "Couldn't parse instruction 'XYZ' in line '1'.");
+        let diagnostic = compiler::diagnose("sum: DS\nSTV smu", CompilerOptions::new()).unwrap();
+        assert_eq!(diagnostic.get_suggestion().as_deref(), Some("sum"));
+    }
+    #[test]
+    fn typed_errors() {
+        let resolver = |_: &str| None;
+        let error = crate::compile_program("JMP AWAY", &CompilerOptions::new(), &resolver).unwrap_err();
+        assert_eq!(error, CompilerError::UnknownLabel { name: "AWAY".to_string(), line: 1, suggestion: None });
+        assert_eq!(crate::compile_program("HALT", &CompilerOptions::new(), &resolver).unwrap().get_code_length(), 1);
+        let object = crate::build_object("EXPORT F\nF: RET", &CompilerOptions::new()).unwrap();
+        assert_eq!(object.get_exports(), vec!["F"]);
+    }
+    #[test]
+    fn operand_ranges() {
+        assert!(compiler::compile("LDC 1048575\nHALT").is_ok());
+        let result = compiler::compile("LDC 0\nLDC 1048576\nHALT");
+        assert!(result.is_err_and(|err| err.to_string() == "Operand '1048576' in line '2' doesn't fit into 20 bits."));
+        assert!(compiler::compile("LDV 0x100000").is_err());
+        assert_eq!(crate::mima::Instruction::HALT.operand_bits(), 16);
+        assert!(compiler::compile("BIG EQU 0x200000\nLDC BIG").is_err());
+    }
+    #[test]
+    fn operand_arity() {
+        let missing = compiler::compile("a: DS\nLDV a\nADD\nHALT");
+        assert!(missing.is_err_and(|err| err.to_string() == "Instruction 'ADD' in line '3' needs an operand."));
+        let unexpected = compiler::compile("a: DS\nNOT a\nHALT");
+        assert!(unexpected.is_err_and(|err| err.to_string() == "Instruction 'NOT' in line '2' doesn't take an operand."));
+        assert!(compiler::compile("SUB\nHALT").is_err());
+    }
+    #[test]
+    fn operand_kinds() {
+        let jump = compiler::compile("a: DS\nSTART: LDV a\nJMP a");
+        assert!(jump.is_err_and(|err| err.to_string() == "Variable 'a' in line '3' can't be used as a jump target."));
+        let data = compiler::compile("a: DS\nSTART: LDV a\nADD START\nHALT");
+        assert!(data.is_err_and(|err| err.to_string() == "Label 'START' in line '3' can't be used as a data operand."));
+        assert!(compiler::compile("JMP NOWHERE").is_err_and(|err| err.to_string().contains("label 'NOWHERE'")));
+        assert!(compiler::compile("LDV nothing").is_err_and(|err| err.to_string().contains("variable 'nothing'")));
+        // Explicit adresses and adress constants are fine everywhere.
+        let compiled = compiler::compile("ptr: DS\nLDC ptr\nJMP 0\nHALT").unwrap();
+        assert_eq!(compiled.get_mima_code()[1], Command { instruction: crate::mima::Instruction::LDC, value: 0 }.to_usize());
+    }
+    #[test]
+    fn duplicate_symbols() {
+        let variables = compiler::compile("a: DS 1\nb: DS 2\na: DS 3\nHALT");
+        assert!(variables.is_err_and(|err| err.to_string() == "Symbol 'a' in line '3' is already defined in line '1'."));
+        let mixed = compiler::compile("LOOP: DS 1\nLOOP: JMP LOOP");
+        assert!(mixed.is_err_and(|err| err.to_string().contains("line '2'") && err.to_string().contains("line '1'")));
+        let labels = compiler::compile("X: HALT\nX: HALT");
+        assert!(labels.is_err());
+        // Differing case is only a duplicate if symbols are case-insensitive.
+        assert!(compiler::compile("a: DS\nA: HALT").is_ok());
+        let mut options = CompilerOptions::new();
+        options.case_sensitive = false;
+        assert!(compiler::compile_with_options("a: DS\nA: HALT", options).is_err());
+    }
+    #[test]
+    fn analysis_warnings() {
+        let assembly_source = "used: DS 1
+unused: DS 2
+START: LDV used
+JMP END
+STV used
+DEAD: ADD used
+END: HALT
+LDC 1
+";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let warnings: Vec<(WarningKind, usize)> = compiled
+            .get_warnings()
+            .iter()
+            .map(|warning| (warning.get_kind(), warning.get_line()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (WarningKind::UnusedVariable, 2),
+                (WarningKind::UnreachableCode, 5),
+                (WarningKind::UnusedLabel, 6),
+                (WarningKind::UnreachableCode, 8),
+            ]
+        );
+        // The loop program from above is clean.
+        assert!(compiler::compile("one: DS 1\nLOOP: ADD one\nJMP LOOP").unwrap().get_warnings().is_empty());
+    }
+    #[test]
+    fn call_and_ret() {
+        let compiled = compiler::compile("CALL ROUTINE\nHALT\nROUTINE: RET").unwrap();
+        let expected = [
+            Command { instruction: crate::mima::Instruction::CALL, value: 2 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::HALT, value: 0 }.to_usize(),
+            Command { instruction: crate::mima::Instruction::RET, value: 0 }.to_usize(),
+        ];
+        assert_eq!(compiled.get_mima_code(), expected);
+        assert_eq!(expected[0], 0xC00002);
+        assert_eq!(expected[2], 0xF30000);
+        assert!(compiler::compile("a: DS\nCALL a").is_err());
+    }
+    #[test]
+    fn mima_x_stack_instructions() {
+        let compiled = compiler::compile("ADC -1\nLDSP\nSTSP\nLDFP\nSTFP\nLDRS 2\nSTRS 0xFFFF").unwrap();
+        assert_eq!(
+            compiled.get_mima_code(),
+            vec![0xDFFFFF, 0xF40000, 0xF50000, 0xF60000, 0xF70000, 0xF80002, 0xF9FFFF]
+        );
+        assert!(compiler::compile("LDRS -1").is_err());
+        assert!(compiler::compile("LDRS 0x10000").is_err());
+        assert!(compiler::compile("LDSP 1").is_err());
+    }
+    #[test]
+    fn block_storage() {
+        let assembly_source = "buf: DS 3 * 0
+tbl: DS 1, 2,3,5 ,8
+fill: DS 2*-1
+after: DS 9
+LDV after
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code[0..11], [0, 0, 0, 1, 2, 3, 5, 8, 0xFFFFFF, 0xFFFFFF, 9]);
+        assert_eq!(code[11], Command { instruction: crate::mima::Instruction::LDV, value: 10 }.to_usize());
+        assert_eq!(compiled.get_start_adress(), 11);
+        assert!(compiler::compile("buf: DS 0 * 1\nHALT").is_err());
+    }
+    #[test]
+    fn character_and_string_literals() {
+        let assembly_source = "c: DS 'A'
+msg: DS \"HI; \\\"x\\\"\", 0 ; greeting
+stars: DS 2 * '*'
+LDC 'a'
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code[0..12], [65, 72, 73, 59, 32, 34, 120, 34, 0, 42, 42, 97]);
+        assert_eq!(compiled.get_symbols()[1].get_size(), 8);
+        assert!(compiler::compile("s: DS \"\"\nHALT").is_err());
+    }
+    #[test]
+    fn org_overlap() {
+        // The variable placed without ORG occupies adress 0.
+        let result = compiler::compile("a: DS 1\nORG 0\nHALT");
+        assert!(result.is_err_and(|err| err.to_string().contains("'0'")));
+    }
+    #[test]
+    fn equ_redefinition() {
+        let result = compiler::compile("SIZE EQU 10\nSIZE EQU 11\nHALT");
+        assert!(result.is_err_and(|err| err.to_string().contains("already defined")));
+        // Negative constants can't be used as adresses
+        assert!(compiler::compile("NEG EQU -1\nLDV NEG\nHALT").is_err());
+    }
 }
@@ -0,0 +1,228 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::mima::Instruction;
+use crate::tokenizer::{tokenize, tokenize_line, Token, TokenKind};
+
+// Directives offered at the position of an operation
+const DIRECTIVES: [(&str, &str); 9] = [
+    ("DS", "Reserves memory words, initialized with the given values"),
+    ("EQU", "Defines a named constant"),
+    ("ORG", "Places the following code at the given adress"),
+    ("EXPORT", "Makes symbols visible to other objects when linking"),
+    ("INCLUDE", "Inserts the lines of another file"),
+    ("MACRO", "Starts a macro definition"),
+    ("ENDM", "Ends a macro definition"),
+    ("TITLE", "Names the program"),
+    ("END", "Ends the program, optionally naming the label where execution starts"),
+];
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CompletionKind {
+    Instruction,
+    Directive,
+    Macro,
+    Label,
+    Variable,
+    Constant,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Completion {
+    label: String,
+    kind: CompletionKind,
+    detail: String,
+    // Line of the definition for symbols of the program
+    line: Option<usize>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Completion {
+    pub fn get_label(&self) -> String {
+        self.label.to_owned()
+    }
+    pub fn get_kind(&self) -> CompletionKind {
+        self.kind
+    }
+    pub fn get_detail(&self) -> String {
+        self.detail.to_owned()
+    }
+    pub fn get_line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+fn instructions() -> Vec<Completion> {
+    (0..=u8::MAX as usize)
+        .filter_map(Instruction::from_opcode)
+        .map(|instruction| Completion {
+            label: instruction.mnemonic().to_string(),
+            kind: CompletionKind::Instruction,
+            detail: instruction.description().to_string(),
+            line: None,
+        })
+        .chain(std::iter::once(Completion {
+            label: "SUB".to_string(),
+            kind: CompletionKind::Instruction,
+            detail: "Subtracts the value at the adress from the akku".to_string(),
+            line: None,
+        }))
+        .chain(DIRECTIVES.iter().map(|(name, detail)| Completion {
+            label: name.to_string(),
+            kind: CompletionKind::Directive,
+            detail: detail.to_string(),
+            line: None,
+        }))
+        .collect()
+}
+
+/*
+ * Collects the definitions of the program from its tokens, so it also works on sources that
+ * don't compile while they are being edited.
+ */
+fn symbols(source: &str) -> Vec<Completion> {
+    let tokens = tokenize(source);
+    let mut symbols = vec![];
+    for (index, token) in tokens.iter().enumerate() {
+        let next = tokens.get(index + 1).filter(|next| next.get_line() == token.get_line());
+        let next_text = next.map(|next| next.get_text().to_uppercase());
+        let symbol = |kind: CompletionKind, detail: String| Completion {
+            label: token.get_text(),
+            kind,
+            detail,
+            line: Some(token.get_line()),
+        };
+        match (token.get_kind(), next_text.as_deref()) {
+            (TokenKind::Label, Some("DS")) => symbols.push(symbol(CompletionKind::Variable, "Variable".to_string())),
+            (TokenKind::Label, Some("EQU")) => {
+                let value = tokens
+                    .get(index + 2)
+                    .filter(|value| value.get_line() == token.get_line())
+                    .map(|value| value.get_text())
+                    .unwrap_or_default();
+                symbols.push(symbol(CompletionKind::Constant, format!("Constant = {value}")));
+            }
+            (TokenKind::Label, _) => symbols.push(symbol(CompletionKind::Label, "Label".to_string())),
+            (TokenKind::Directive, _) if token.get_text().eq_ignore_ascii_case("MACRO") => {
+                if let Some(name) = next {
+                    symbols.push(Completion {
+                        label: name.get_text(),
+                        kind: CompletionKind::Macro,
+                        detail: "Macro".to_string(),
+                        line: Some(name.get_line()),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    symbols
+}
+
+/*
+ * Proposals for the word at the cursor. Line and column are given like the spans of tokenize.
+ * Operations are proposed at the start of a statement, symbols matching the operation after it.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn get_completions(source: &str, line: usize, column: usize) -> Vec<Completion> {
+    let text = source.lines().nth(line.saturating_sub(1)).unwrap_or_default();
+    let tokens: Vec<Token> = tokenize_line(text, line);
+    if tokens.iter().any(|token| token.get_kind() == TokenKind::Comment && token.get_start() < column) {
+        return vec![];
+    }
+    let before: Vec<&Token> = tokens.iter().filter(|token| token.get_end() <= column).collect();
+    let prefix = tokens
+        .iter()
+        .find(|token| token.get_start() < column && column <= token.get_end())
+        .map(|token| token.get_text().chars().take(column - token.get_start()).collect::<String>())
+        .unwrap_or_default();
+    let operation = before
+        .iter()
+        .rev()
+        .find(|token| matches!(token.get_kind(), TokenKind::Mnemonic | TokenKind::Directive))
+        .filter(|token| token.get_end() < column || prefix.is_empty());
+    let candidates: Vec<Completion> = match operation {
+        None => {
+            let macros = symbols(source).into_iter().filter(|symbol| symbol.kind == CompletionKind::Macro);
+            instructions().into_iter().chain(macros).collect()
+        }
+        Some(operation) => {
+            let wanted: &[CompletionKind] = match Instruction::from_string(&operation.get_text()) {
+                Some(instruction) if instruction.is_jump() || instruction == Instruction::JIND => &[CompletionKind::Label],
+                Some(instruction) if instruction.takes_constant() => &[CompletionKind::Constant, CompletionKind::Variable, CompletionKind::Label],
+                Some(instruction) if !instruction.takes_operand() => &[],
+                Some(_) => &[CompletionKind::Variable, CompletionKind::Constant],
+                // END names the entry label, only variables and labels have an adress to export.
+                None if operation.get_text().eq_ignore_ascii_case("END") => &[CompletionKind::Label],
+                None if operation.get_text().eq_ignore_ascii_case("EXPORT") => &[CompletionKind::Variable, CompletionKind::Label],
+                None if operation.get_kind() == TokenKind::Directive => &[CompletionKind::Constant],
+                None => &[CompletionKind::Variable, CompletionKind::Constant, CompletionKind::Label],
+            };
+            symbols(source).into_iter().filter(|symbol| wanted.contains(&symbol.kind)).collect()
+        }
+    };
+    let prefix = prefix.to_uppercase();
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.label.to_uppercase().starts_with(&prefix))
+        .collect()
+}
+
+// Documentation of an instruction or directive, or the definition of a symbol of the program.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn get_symbol_info(source: &str, name: &str) -> Option<Completion> {
+    symbols(source)
+        .into_iter()
+        .find(|symbol| symbol.label == name)
+        .or_else(|| symbols(source).into_iter().find(|symbol| symbol.label.eq_ignore_ascii_case(name)))
+        .or_else(|| instructions().into_iter().find(|instruction| instruction.label.eq_ignore_ascii_case(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_completions, get_symbol_info, CompletionKind};
+
+    const SOURCE: &str = "ONE EQU 1
+counter: DS 5
+MACRO TWICE x
+ADD x
+ENDM
+START: LDV counter
+loop: JMN loop
+L";
+
+    fn labels(line: usize, column: usize) -> Vec<String> {
+        get_completions(SOURCE, line, column).iter().map(|completion| completion.get_label()).collect()
+    }
+
+    #[test]
+    fn completions() {
+        // Operations starting with L, including the macro for an empty prefix
+        assert_eq!(labels(8, 1), vec!["LDC", "LDV", "LDIV", "LDSP", "LDFP", "LDRS"]);
+        assert!(labels(8, 0).contains(&"TWICE".to_string()));
+        // Data instructions take variables and constants, jumps take labels.
+        assert_eq!(labels(6, 11), vec!["ONE", "counter"]);
+        assert_eq!(labels(6, 12), vec!["counter"]);
+        assert_eq!(labels(7, 10), vec!["START", "loop"]);
+        assert!(get_completions("HALT ; comment", 1, 10).is_empty());
+        let directives = format!("{SOURCE}\nEND \nEXPORT counter, ");
+        let completions = |line, column| get_completions(&directives, line, column).iter().map(|completion| completion.get_label()).collect::<Vec<String>>();
+        assert_eq!(completions(9, 4), vec!["START", "loop"]);
+        assert_eq!(completions(10, 16), vec!["counter", "START", "loop"]);
+    }
+
+    #[test]
+    fn symbol_info() {
+        let constant = get_symbol_info(SOURCE, "ONE").unwrap();
+        assert_eq!(constant.get_kind(), CompletionKind::Constant);
+        assert_eq!(constant.get_detail(), "Constant = 1");
+        assert_eq!(get_symbol_info(SOURCE, "counter").unwrap().get_line(), Some(2));
+        assert_eq!(get_symbol_info(SOURCE, "TWICE").unwrap().get_kind(), CompletionKind::Macro);
+        let instruction = get_symbol_info(SOURCE, "jmn").unwrap();
+        assert_eq!(instruction.get_kind(), CompletionKind::Instruction);
+        assert!(instruction.get_detail().contains("negative"));
+        assert!(get_symbol_info(SOURCE, "nothing").is_none());
+    }
+}
@@ -0,0 +1,298 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::MimaError;
+use crate::locale;
+
+/*
+ * A device occupying a range of adresses. Reads and writes of the program to that range reach
+ * the device instead of RAM, offsets are relative to the start of the range.
+ */
+pub trait Device {
+    fn read(&mut self, offset: usize) -> usize;
+    fn write(&mut self, offset: usize, value: usize);
+    // Value shown by debugging views, unlike read it must not change the device.
+    fn peek(&self, offset: usize) -> usize;
+    // Called after every instruction, devices request an interrupt by returning the handler adress.
+    fn tick(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+pub(crate) struct MappedDevice {
+    pub start: usize,
+    pub size: usize,
+    pub device: Box<dyn Device>,
+}
+
+impl MappedDevice {
+    pub fn contains(&self, adress: usize) -> bool {
+        (self.start..self.start + self.size).contains(&adress)
+    }
+}
+
+// Writing a word prints the character with that code. Output is collected and passed to the callback.
+pub(crate) struct Console {
+    pub output: Rc<RefCell<String>>,
+    pub callback: Option<Box<dyn FnMut(char)>>,
+}
+
+impl Device for Console {
+    fn read(&mut self, _offset: usize) -> usize {
+        0
+    }
+    fn write(&mut self, _offset: usize, value: usize) {
+        let character = char::from_u32(value as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.output.borrow_mut().push(character);
+        if let Some(callback) = &mut self.callback {
+            callback(character);
+        }
+    }
+    fn peek(&self, _offset: usize) -> usize {
+        0
+    }
+}
+
+/*
+ * Reading the first word takes the next character typed by the user, or 0 if there is none. The
+ * second word holds the number of waiting characters, so programs can poll it.
+ */
+pub(crate) struct Keyboard {
+    pub input: Rc<RefCell<VecDeque<usize>>>,
+}
+
+impl Device for Keyboard {
+    fn read(&mut self, offset: usize) -> usize {
+        match offset {
+            0 => self.input.borrow_mut().pop_front().unwrap_or(0),
+            _ => self.peek(offset),
+        }
+    }
+    fn write(&mut self, _offset: usize, _value: usize) {}
+    fn peek(&self, offset: usize) -> usize {
+        let input = self.input.borrow();
+        match offset {
+            0 => input.front().copied().unwrap_or(0),
+            _ => input.len(),
+        }
+    }
+}
+
+/*
+ * Reading returns the next pseudo-random 24 bit value of a SplitMix64 generator, writing a word
+ * seeds the generator again. The same seed always gives the same sequence.
+ */
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { state: seed }
+    }
+    fn next(state: u64) -> (u64, usize) {
+        let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // The upper bits are the best mixed ones.
+        (state, (z >> 40) as usize)
+    }
+}
+
+impl Device for Random {
+    fn read(&mut self, _offset: usize) -> usize {
+        let (state, value) = Random::next(self.state);
+        self.state = state;
+        value
+    }
+    fn write(&mut self, _offset: usize, value: usize) {
+        self.state = value as u64;
+    }
+    fn peek(&self, _offset: usize) -> usize {
+        Random::next(self.state).1
+    }
+}
+
+/*
+ * The cells of a screen in rows from top to bottom. Every cell holds a character code or a
+ * color, depending on how the frontend draws it.
+ */
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Framebuffer {
+    pub width: usize,
+    pub cells: Vec<usize>,
+    // Smallest rectangle containing all cells changed since the last update as (left, top, right, bottom)
+    pub dirty: Option<(usize, usize, usize, usize)>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer { width, cells: vec![0; width * height], dirty: None }
+    }
+    // Takes the changed rectangle and its cells.
+    pub fn take_update(&mut self) -> Option<ScreenUpdate> {
+        let (left, top, right, bottom) = self.dirty.take()?;
+        let cells = (top..=bottom)
+            .flat_map(|y| self.cells[y * self.width + left..=y * self.width + right].iter().copied())
+            .collect();
+        Some(ScreenUpdate { x: left, y: top, width: right - left + 1, height: bottom - top + 1, cells })
+    }
+}
+
+// Maps the cells of the framebuffer row by row to consecutive adresses.
+pub(crate) struct Screen {
+    pub framebuffer: Rc<RefCell<Framebuffer>>,
+}
+
+impl Device for Screen {
+    fn read(&mut self, offset: usize) -> usize {
+        self.peek(offset)
+    }
+    fn write(&mut self, offset: usize, value: usize) {
+        let mut framebuffer = self.framebuffer.borrow_mut();
+        if framebuffer.cells[offset] == value {
+            return;
+        }
+        framebuffer.cells[offset] = value;
+        let (x, y) = (offset % framebuffer.width, offset / framebuffer.width);
+        framebuffer.dirty = Some(match framebuffer.dirty {
+            Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+            None => (x, y, x, y),
+        });
+    }
+    fn peek(&self, offset: usize) -> usize {
+        self.framebuffer.borrow().cells[offset]
+    }
+}
+
+// A rectangle of the screen that changed, cells are given row by row.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenUpdate {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<usize>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ScreenUpdate {
+    pub fn get_cells(&self) -> Vec<usize> {
+        self.cells.to_owned()
+    }
+}
+
+/*
+ * Requests an interrupt every `interval` instructions. The first word holds the interval, zero
+ * stops the timer, the second one the adress of the interrupt handler. Both can be written.
+ */
+pub(crate) struct Timer {
+    pub interval: usize,
+    pub handler: usize,
+    // Instructions since the last interrupt
+    pub elapsed: usize,
+}
+
+impl Device for Timer {
+    fn read(&mut self, offset: usize) -> usize {
+        self.peek(offset)
+    }
+    fn write(&mut self, offset: usize, value: usize) {
+        match offset {
+            0 => {
+                self.interval = value;
+                self.elapsed = 0;
+            }
+            _ => self.handler = value,
+        }
+    }
+    fn peek(&self, offset: usize) -> usize {
+        match offset {
+            0 => self.interval,
+            _ => self.handler,
+        }
+    }
+    fn tick(&mut self) -> Option<usize> {
+        if self.interval == 0 {
+            return None;
+        }
+        self.elapsed += 1;
+        if self.elapsed < self.interval {
+            return None;
+        }
+        self.elapsed = 0;
+        Some(self.handler)
+    }
+}
+
+// A value a device handed to the program while it executed its `step`-th instruction
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InputEvent {
+    pub step: u64,
+    pub adress: usize,
+    pub value: usize,
+}
+
+/*
+ * All values read from devices during a run. Devices are the only source of nondeterminism, so
+ * replaying the log reproduces the run exactly.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputLog {
+    events: Vec<InputEvent>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl InputLog {
+    pub fn get_events(&self) -> Vec<InputEvent> {
+        self.events.to_owned()
+    }
+    // One event per line as "step adress value", e.g. for links to a run.
+    pub fn to_text(&self) -> String {
+        self.events
+            .iter()
+            .map(|event| format!("{} {} {}", event.step, event.adress, event.value))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    pub fn from_text(text: &str) -> Result<InputLog, MimaError> {
+        let events = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                let invalid = || {
+                    let message = locale::localize(
+                        format!("Invalid input event '{line}' in line {}.", index + 1),
+                        format!("Ungültiges Eingabeereignis '{line}' in Zeile {}.", index + 1),
+                    );
+                    MimaError::new("INVALID_INPUT_LOG", message)
+                };
+                match line.split_whitespace().collect::<Vec<&str>>()[..] {
+                    [step, adress, value] => Ok(InputEvent {
+                        step: step.parse().map_err(|_| invalid())?,
+                        adress: adress.parse().map_err(|_| invalid())?,
+                        value: value.parse().map_err(|_| invalid())?,
+                    }),
+                    _ => Err(invalid()),
+                }
+            })
+            .collect::<Result<Vec<InputEvent>, MimaError>>()?;
+        Ok(InputLog { events })
+    }
+}
+
+impl InputLog {
+    pub(crate) fn new(events: Vec<InputEvent>) -> InputLog {
+        InputLog { events }
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::{identifier_pattern, split_comment};
+
+static KIT_ORIGIN_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\s*)\*\s*=\s*(\S+)\s*$").unwrap());
+static KIT_CONSTANT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(concat!(r"^(\s*)(", identifier_pattern!(), r")\s*=\s*(\S.*?)\s*$")).unwrap());
+static KIT_VARIABLE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(concat!(r"^(\s*)(", identifier_pattern!(), r")(?:\s*:\s*|\s+)(?i:DS)\b\s*(.*?)\s*$")).unwrap());
+
+// Assembly dialects the compiler understands
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Dialect {
+    #[default]
+    Native,
+    // Syntax of the KIT mimasim reference simulator
+    Kit,
+}
+
+/*
+ * Rewrites a line of the KIT syntax: "* = 0x10" becomes "ORG 0x10", "ONE = 1" becomes
+ * "ONE EQU 1" and "a DS 5" or "a:DS 5" becomes "a: DS 5". Everything else is the same in both
+ * dialects.
+ */
+pub fn convert_kit_line(line: &str) -> String {
+    if let Some(captures) = KIT_ORIGIN_REGEX.captures(line) {
+        format!("{}ORG {}", &captures[1], &captures[2])
+    } else if let Some(captures) = KIT_CONSTANT_REGEX.captures(line) {
+        format!("{}{} EQU {}", &captures[1], &captures[2], &captures[3])
+    } else if let Some(captures) = KIT_VARIABLE_REGEX.captures(line) {
+        match &captures[3] {
+            "" => format!("{}{}: DS", &captures[1], &captures[2]),
+            value => format!("{}{}: DS {value}", &captures[1], &captures[2]),
+        }
+    } else {
+        line.to_string()
+    }
+}
+
+// Converts a whole program, comments are kept.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn convert_kit_dialect(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| match split_comment(line) {
+            (code, Some(comment)) => {
+                let trimmed = code.trim_end();
+                format!("{}{}{comment}", convert_kit_line(trimmed), &code[trimmed.len()..])
+            }
+            (_, None) => convert_kit_line(line),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compiler, CompilerOptions};
+    use crate::mima::Mima;
+
+    use super::{convert_kit_dialect, Dialect};
+
+    #[test]
+    fn kit_conversion() {
+        let source = "* = 0 ; start of memory\nONE = 1\nx DS 5\ny:DS\nSTART: LDV x\nADD ONE\nSTV y\nHALT";
+        assert_eq!(
+            convert_kit_dialect(source),
+            "ORG 0 ; start of memory\nONE EQU 1\nx: DS 5\ny: DS\nSTART: LDV x\nADD ONE\nSTV y\nHALT"
+        );
+    }
+
+    #[test]
+    fn kit_dialect_option() {
+        let source = "x DS 5\nSTART: LDC 7\nSTV x\nHALT";
+        assert!(compiler::compile(source).is_err());
+        let mut options = CompilerOptions::new();
+        options.dialect = Dialect::Kit;
+        let compiled = compiler::compile_with_options(source, options).unwrap();
+        let mut mima = Mima::new();
+        mima.load(compiled);
+        mima.run();
+        assert_eq!(mima.read_symbol("x"), Some(7));
+    }
+}
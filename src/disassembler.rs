@@ -0,0 +1,107 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::{Symbol, SymbolKind};
+use crate::mima::Command;
+
+/*
+ * Converts machine words back to assembly text, one line per word. Words that don't decode to an
+ * instruction are shown as data definitions.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn disassemble(code: Vec<usize>) -> Vec<String> {
+    code.into_iter().map(disassemble_word).collect()
+}
+
+pub fn disassemble_word(word: usize) -> String {
+    match Command::from_usize(word) {
+        Some(command) => command.to_string(),
+        None => format!("DS {word}"),
+    }
+}
+
+/*
+ * Disassembles a program with the help of its symbol table. Variables are shown as data
+ * definitions, labels are placed in front of their instructions and operands are replaced by
+ * the name of the symbol at that adress.
+ */
+pub fn disassemble_with_symbols(code: &[usize], symbols: &[Symbol]) -> Vec<String> {
+    let symbol_at = |adress: usize, kind: SymbolKind| {
+        symbols
+            .iter()
+            .find(|symbol| symbol.get_adress() == adress && symbol.get_kind() == kind)
+    };
+    code.iter()
+        .enumerate()
+        .map(|(adress, word)| {
+            let variable = symbols.iter().find(|symbol| {
+                symbol.get_kind() == SymbolKind::Variable
+                    && (symbol.get_adress()..symbol.get_adress() + symbol.get_size()).contains(&adress)
+            });
+            if let Some(variable) = variable {
+                return match variable.get_adress() == adress {
+                    true => format!("{}: DS {word}", variable.get_name()),
+                    false => format!("DS {word}"),
+                };
+            }
+            let text = match Command::from_usize(*word) {
+                Some(command) if command.instruction.takes_operand() => {
+                    let kind = match command.instruction.is_jump() {
+                        true => SymbolKind::Label,
+                        false => SymbolKind::Variable,
+                    };
+                    match symbol_at(command.value, kind) {
+                        Some(symbol) => format!("{} {}", command.get_mnemonic(), symbol.get_name()),
+                        None => command.to_string(),
+                    }
+                }
+                _ => disassemble_word(*word),
+            };
+            match symbol_at(adress, SymbolKind::Label) {
+                Some(label) => format!("{}: {text}", label.get_name()),
+                None => text,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+    use crate::compiler::compiler;
+
+    #[test]
+    fn round_trip() {
+        let assembly_source = "LDC 7
+STV 20
+LOOP: ADD 20
+NOT
+JMN LOOP
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        assert_eq!(
+            disassemble(compiled.get_mima_code()),
+            vec!["LDC 7", "STV 20", "ADD 20", "NOT", "JMN 2", "HALT"]
+        );
+    }
+
+    #[test]
+    fn symbolic_disassembly() {
+        let assembly_source = "a: DS 5
+tbl: DS 1, 2
+START: LDV a
+LOOP: ADD tbl
+JMN LOOP
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        assert_eq!(
+            compiled.disassemble(),
+            vec!["a: DS 5", "tbl: DS 1", "DS 2", "START: LDV a", "LOOP: ADD tbl", "JMN LOOP", "HALT"]
+        );
+    }
+
+    #[test]
+    fn undecodable_words() {
+        assert_eq!(disassemble(vec![0xFFFFFF]), vec!["DS 16777215"]);
+    }
+}
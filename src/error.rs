@@ -0,0 +1,81 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::CompilerError;
+use crate::locale;
+
+/*
+ * The error the functions for JS fail with. The code names the kind of error like
+ * "UNKNOWN_LABEL" and stays the same in every locale and when a message is reworded, so frontends
+ * can handle errors by their code. In JS it becomes an Error with the additional properties code
+ * and line.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct MimaError {
+    code: &'static str,
+    message: String,
+    // Line of the source for compiler errors
+    line: Option<usize>,
+}
+
+impl MimaError {
+    pub fn new(code: &'static str, message: String) -> MimaError {
+        MimaError { code, message, line: None }
+    }
+    pub fn get_code(&self) -> &'static str {
+        self.code
+    }
+    pub fn get_message(&self) -> String {
+        self.message.to_owned()
+    }
+    pub fn get_line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+impl std::fmt::Display for MimaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for MimaError {}
+
+impl From<CompilerError> for MimaError {
+    fn from(error: CompilerError) -> MimaError {
+        MimaError { code: error.code(), message: locale::compiler_message(&error), line: error.line() }
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl From<MimaError> for JsValue {
+    fn from(error: MimaError) -> JsValue {
+        let js_error = js_sys::Error::new(&error.message);
+        let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("code"), &JsValue::from_str(error.code));
+        if let Some(line) = error.line {
+            let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("line"), &JsValue::from(line));
+        }
+        js_error.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compiler;
+    use crate::devices::InputLog;
+    use crate::mima::{HaltReason, Mima};
+
+    #[test]
+    fn error_codes() {
+        let error = compiler::compile("LDC 1\nJMP NOWHERE").unwrap_err();
+        assert_eq!((error.get_code(), error.get_line()), ("UNKNOWN_LABEL", Some(2)));
+        assert_eq!(error.to_string(), "Couldn't find label 'NOWHERE' in line '2'.");
+        assert_eq!(compiler::compile("LDV 0x100000").unwrap_err().get_code(), "OPERAND_OUT_OF_RANGE");
+        assert_eq!(Mima::with_config(100, 24).err().unwrap().get_code(), "INVALID_CONFIG");
+        assert_eq!(InputLog::from_text("1 2").unwrap_err().get_code(), "INVALID_INPUT_LOG");
+        // Runtime errors come from the halt reason.
+        let error = HaltReason::adress_out_of_range(Some(0x100000)).to_error().unwrap();
+        assert_eq!((error.get_code(), error.get_message().as_str()), ("ADDRESS_OOB", "Adress 1048576 is out of range."));
+        assert_eq!(HaltReason::halted().to_error(), None);
+    }
+}
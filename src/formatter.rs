@@ -0,0 +1,126 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::{identifier_pattern, number_pattern, split_comment};
+
+static CONSTANT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(concat!(r"^\s*(", identifier_pattern!(), r"):?\s+(?i:EQU)\s+(.+?)\s*$")).unwrap());
+static ORIGIN_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(concat!(r"^\s*(?:(?i:ORG)\s+|\*\s*=\s*)(", number_pattern!(), r")")).unwrap());
+// An optional label, a mnemonic, directive or macro name and its operands
+static STATEMENT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(concat!(r"^(?:(", identifier_pattern!(), r"):)?\s*(", identifier_pattern!(), r")\s*(.*)$")).unwrap());
+
+// A line split into the columns of the formatted output
+enum Line {
+    Blank,
+    Comment(String),
+    Code { label: String, operation: String, operands: String, comment: Option<String> },
+}
+
+/*
+ * Formats a program: labels, mnemonics and operands are aligned in columns, mnemonics are upper
+ * case and comments are aligned behind the code. Lines the compiler wouldn't understand are kept
+ * as they are.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn format_assembly(source: &str) -> String {
+    let lines: Vec<Line> = source.lines().map(split_line).collect();
+    let code_lines = || {
+        lines.iter().filter_map(|line| match line {
+            Line::Code { label, operation, operands, .. } => Some((label, operation, operands)),
+            _ => None,
+        })
+    };
+    let label_width = code_lines().map(|(label, _, _)| label.len() + 1).max().filter(|width| *width > 1).unwrap_or(0);
+    let operation_width = code_lines().filter(|(_, _, operands)| !operands.is_empty()).map(|(_, operation, _)| operation.len() + 1).max().unwrap_or(0);
+    let code = |label: &str, operation: &str, operands: &str| {
+        let code = match operands {
+            "" => format!("{label:<label_width$}{operation}"),
+            _ => format!("{label:<label_width$}{operation:<operation_width$}{operands}"),
+        };
+        code.trim_end().to_string()
+    };
+    let comment_column = code_lines().map(|(label, operation, operands)| code(label, operation, operands).len()).max().unwrap_or(0) + 1;
+    let mut formatted: Vec<String> = lines
+        .iter()
+        .map(|line| match line {
+            Line::Blank => String::new(),
+            Line::Comment(comment) => comment.to_owned(),
+            Line::Code { label, operation, operands, comment } => match comment {
+                Some(comment) => format!("{:<comment_column$}{comment}", code(label, operation, operands)),
+                None => code(label, operation, operands),
+            },
+        })
+        .collect();
+    if source.ends_with('\n') {
+        formatted.push(String::new());
+    }
+    formatted.join("\n")
+}
+
+fn split_line(line: &str) -> Line {
+    let (code, comment) = match split_comment(line) {
+        (code, Some(comment)) => (code.trim(), Some(comment.trim_end().to_string())),
+        (code, None) => (code.trim(), None),
+    };
+    if code.is_empty() {
+        return comment.map_or(Line::Blank, Line::Comment);
+    }
+    let (label, operation, operands) = if let Some(captures) = ORIGIN_REGEX.captures(code) {
+        (String::new(), "ORG".to_string(), captures[1].to_string())
+    } else if let Some(captures) = CONSTANT_REGEX.captures(code).filter(|captures| captures[0].len() == code.len()) {
+        (captures[1].to_string(), "EQU".to_string(), captures[2].to_string())
+    } else if let Some(captures) = STATEMENT_REGEX.captures(code) {
+        let label = captures.get(1).map_or(String::new(), |label| format!("{}:", label.as_str()));
+        (label, captures[2].to_uppercase(), normalize_operands(&captures[3]))
+    } else {
+        (String::new(), code.to_string(), String::new())
+    };
+    Line::Code { label, operation, operands, comment }
+}
+
+// Operand lists are separated by ", ", string and character literals are left untouched.
+fn normalize_operands(operands: &str) -> String {
+    if operands.contains(['"', '\'']) {
+        return operands.trim().to_string();
+    }
+    operands
+        .split(',')
+        .map(|operand| operand.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compiler;
+
+    use super::format_assembly;
+
+    #[test]
+    fn column_alignment() {
+        let source = "; Adds two numbers\na:   DS 5\ncounter: ds 1,2 ,3\nONE equ 1\nSTART:ldv a ;load\n  add   counter\nhalt\n";
+        let expected = "; Adds two numbers
+a:       DS  5
+counter: DS  1, 2, 3
+ONE      EQU 1
+START:   LDV a       ;load
+         ADD counter
+         HALT
+";
+        assert_eq!(format_assembly(source), expected);
+        // Formatting doesn't change the program
+        assert_eq!(compiler::compile(expected).unwrap().get_mima_code(), compiler::compile(source).unwrap().get_mima_code());
+        assert_eq!(format_assembly(expected), expected);
+    }
+
+    #[test]
+    fn directives() {
+        let source = "*=0x10\nINCLUDE  \"lib.mima\"\nMACRO  twice x\n add x\nendm";
+        assert_eq!(format_assembly(source), "ORG     0x10\nINCLUDE \"lib.mima\"\nMACRO   twice x\nADD     x\nENDM");
+    }
+}
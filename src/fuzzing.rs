@@ -0,0 +1,92 @@
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::compiler::CompilerOutput;
+use crate::mima::{Command, Instruction, Mima, MAX_WORD_BITS};
+
+// Machines are kept small so a fuzzer gets through many inputs per second.
+const MAX_MEMORY_BITS: u32 = 10;
+
+/*
+ * A random machine configuration with a memory image to load. Most words are commands the machine
+ * can decode, their operands are biased towards the end of memory where adress checks matter. The
+ * rest are raw words, which mostly trap as illegal instructions.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryImage {
+    pub memory_words: usize,
+    pub word_bits: u32,
+    pub code: Vec<usize>,
+    pub start_adress: usize,
+    pub akku: usize,
+}
+
+impl<'a> Arbitrary<'a> for MemoryImage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let memory_words = 1 << u.int_in_range(4..=MAX_MEMORY_BITS)?;
+        let word_bits = u.int_in_range(24..=MAX_WORD_BITS)?;
+        let word_mask = (1usize << word_bits) - 1;
+        let length = u.int_in_range(1..=memory_words - 1)?;
+        let mut code = Vec::with_capacity(length);
+        for _ in 0..length {
+            let word = match Instruction::from_opcode(u.arbitrary::<u8>()? as usize) {
+                Some(instruction) if u.ratio(7, 8)? => {
+                    let limit = 1usize << instruction.operand_bits();
+                    let value = match u.int_in_range(0..=2)? {
+                        0 => u.int_in_range(0..=memory_words + 1)?,
+                        1 => memory_words.saturating_sub(u.int_in_range(1..=4)?),
+                        _ => u.int_in_range(0..=limit - 1)?,
+                    };
+                    Command::new(instruction, value.min(limit - 1)).unwrap().to_usize()
+                }
+                _ => u.arbitrary::<u32>()? as usize & word_mask,
+            };
+            code.push(word);
+        }
+        Ok(MemoryImage {
+            memory_words,
+            word_bits,
+            code,
+            start_adress: u.int_in_range(0..=memory_words - 1)?,
+            akku: u.arbitrary::<u32>()? as usize & word_mask,
+        })
+    }
+}
+
+impl MemoryImage {
+    pub fn to_output(&self) -> CompilerOutput {
+        CompilerOutput::new(self.code.to_owned(), self.start_adress)
+    }
+
+    // A machine with the image loaded, ready to step
+    pub fn machine(&self) -> Mima {
+        let mut mima = Mima::with_config(self.memory_words, self.word_bits).unwrap();
+        mima.load(self.to_output());
+        mima.set_akku(self.akku);
+        mima
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use super::MemoryImage;
+
+    #[test]
+    fn images_fit_their_machine() {
+        let bytes: Vec<u8> = (0..4096u32).map(|index| (index.wrapping_mul(2654435761) >> 13) as u8).collect();
+        let mut u = Unstructured::new(&bytes);
+        while let Ok(image) = MemoryImage::arbitrary(&mut u) {
+            assert!(image.code.len() < image.memory_words);
+            assert!(image.code.iter().all(|word| *word < 1 << image.word_bits));
+            let mut mima = image.machine();
+            assert_eq!(mima.get_memory_size(), image.memory_words);
+            for _ in 0..100 {
+                mima.step();
+            }
+            if u.is_empty() {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,286 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::CompilerOutput;
+use crate::mima::{HaltKind, HaltReason, Mima, TraceEntry};
+
+const DEFAULT_MAX_STEPS: usize = 1_000_000;
+// Instructions kept in the report of a failed test case
+const TRACE_LENGTH: usize = 50;
+
+// A memory cell given by its adress or by the name of a variable or label
+#[derive(Clone, Debug, PartialEq)]
+enum Cell {
+    Adress(usize),
+    Symbol(String),
+}
+
+impl Cell {
+    fn resolve(&self, program: &CompilerOutput) -> Option<usize> {
+        match self {
+            Cell::Adress(adress) => Some(*adress),
+            Cell::Symbol(name) => program
+                .get_symbols()
+                .iter()
+                .find(|symbol| symbol.get_name() == *name)
+                .map(|symbol| symbol.get_adress()),
+        }
+    }
+    fn describe(&self) -> String {
+        match self {
+            Cell::Adress(adress) => format!("adress {adress}"),
+            Cell::Symbol(name) => name.to_owned(),
+        }
+    }
+}
+
+/*
+ * One run of an exercise: the memory is set up, the program runs from its start or the entry
+ * label and has to halt within max_steps, then the expected cells and akku are checked.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestCase {
+    name: String,
+    inputs: Vec<(Cell, usize)>,
+    entry: Option<String>,
+    max_steps: usize,
+    expected: Vec<(Cell, usize)>,
+    expected_akku: Option<usize>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TestCase {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(name: &str) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            inputs: vec![],
+            entry: None,
+            max_steps: DEFAULT_MAX_STEPS,
+            expected: vec![],
+            expected_akku: None,
+        }
+    }
+    pub fn get_name(&self) -> String {
+        self.name.to_owned()
+    }
+    // Initializes a variable before the run.
+    pub fn set_input(&mut self, symbol: &str, value: usize) {
+        self.inputs.push((Cell::Symbol(symbol.to_string()), value));
+    }
+    pub fn set_input_adress(&mut self, adress: usize, value: usize) {
+        self.inputs.push((Cell::Adress(adress), value));
+    }
+    // Starts the run at a label instead of the start adress of the program.
+    pub fn set_entry(&mut self, label: &str) {
+        self.entry = Some(label.to_string());
+    }
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+    }
+    pub fn expect(&mut self, symbol: &str, value: usize) {
+        self.expected.push((Cell::Symbol(symbol.to_string()), value));
+    }
+    pub fn expect_adress(&mut self, adress: usize, value: usize) {
+        self.expected.push((Cell::Adress(adress), value));
+    }
+    pub fn expect_akku(&mut self, value: usize) {
+        self.expected_akku = Some(value);
+    }
+}
+
+// A check of a test case that didn't hold. Expected and actual are missing if there was nothing to compare.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestFailure {
+    message: String,
+    pub expected: Option<usize>,
+    pub actual: Option<usize>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TestFailure {
+    pub fn get_message(&self) -> String {
+        self.message.to_owned()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestReport {
+    name: String,
+    pub halt_reason: HaltReason,
+    // Instructions executed by the run
+    pub steps: usize,
+    failures: Vec<TestFailure>,
+    // The last executed instructions, only kept if the test case failed
+    trace: Vec<TraceEntry>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TestReport {
+    pub fn get_name(&self) -> String {
+        self.name.to_owned()
+    }
+    pub fn get_failures(&self) -> Vec<TestFailure> {
+        self.failures.to_owned()
+    }
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+    pub fn get_trace(&self) -> Vec<TraceEntry> {
+        self.trace.to_owned()
+    }
+}
+
+impl TestReport {
+    fn fail(&mut self, message: String, expected: Option<usize>, actual: Option<usize>) {
+        self.failures.push(TestFailure { message, expected, actual });
+    }
+}
+
+// The results of all test cases of a suite in the order of the cases
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuiteReport {
+    reports: Vec<TestReport>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SuiteReport {
+    pub fn get_reports(&self) -> Vec<TestReport> {
+        self.reports.to_owned()
+    }
+    pub fn get_passed_count(&self) -> usize {
+        self.reports.iter().filter(|report| report.passed()).count()
+    }
+    pub fn get_failed_count(&self) -> usize {
+        self.reports.len() - self.get_passed_count()
+    }
+    pub fn passed(&self) -> bool {
+        self.reports.iter().all(TestReport::passed)
+    }
+}
+
+// Runs the program on a fresh machine and checks the expectations of the test case.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn run_test(program: &CompilerOutput, case: &TestCase) -> TestReport {
+    run_case(&mut Mima::new(), program, case)
+}
+
+/*
+ * Runs all test cases on the same machine. Loading the program again before every case resets
+ * memory, registers and the trace, so the cases can't influence each other.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn run_test_suite(program: &CompilerOutput, cases: Vec<TestCase>) -> SuiteReport {
+    let mut mima = Mima::new();
+    SuiteReport { reports: cases.iter().map(|case| run_case(&mut mima, program, case)).collect() }
+}
+
+fn run_case(mima: &mut Mima, program: &CompilerOutput, case: &TestCase) -> TestReport {
+    let mut report = TestReport {
+        name: case.name.to_owned(),
+        halt_reason: HaltReason::not_started(),
+        steps: 0,
+        failures: vec![],
+        trace: vec![],
+    };
+    mima.set_trace_capacity(TRACE_LENGTH);
+    if !mima.load(program.to_owned()) {
+        report.fail("The program doesn't fit into memory.".to_string(), None, None);
+        return report;
+    }
+    for (cell, value) in case.inputs.iter() {
+        if !cell.resolve(program).is_some_and(|adress| mima.write_adress(adress, *value)) {
+            report.fail(format!("Can't set {} to {value}.", cell.describe()), None, None);
+        }
+    }
+    if let Some(entry) = &case.entry {
+        if !Cell::Symbol(entry.to_owned()).resolve(program).is_some_and(|adress| mima.set_iar(adress)) {
+            report.fail(format!("The entry label {entry} doesn't exist."), None, None);
+        }
+    }
+    if !report.passed() {
+        return report;
+    }
+    report.steps = mima.run_chunk(case.max_steps);
+    report.halt_reason = mima.get_debug().halt_reason;
+    match report.halt_reason.kind {
+        HaltKind::Halted => (),
+        HaltKind::NotStarted => report.fail(format!("The program didn't halt within {} steps.", case.max_steps), None, None),
+        kind => report.fail(format!("The program stopped with {kind:?}."), None, report.halt_reason.adress),
+    }
+    for (cell, expected) in case.expected.iter() {
+        let actual = cell.resolve(program).and_then(|adress| mima.read_adress(adress));
+        if actual != Some(*expected) {
+            report.fail(format!("Expected {} to be {expected}.", cell.describe()), Some(*expected), actual);
+        }
+    }
+    if let Some(expected) = case.expected_akku.filter(|expected| *expected != mima.get_akku()) {
+        report.fail(format!("Expected the akku to be {expected}."), Some(expected), Some(mima.get_akku()));
+    }
+    if !report.passed() {
+        report.trace = mima.get_trace();
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compiler;
+
+    use super::{run_test, run_test_suite, TestCase};
+
+    #[test]
+    fn passing_and_failing_cases() {
+        let program = compiler::compile("a: DS 0\nb: DS 0\nsum: DS 0\nSTART: LDV a\nADD b\nSTV sum\nHALT\nLOOP: JMP LOOP").unwrap();
+        let mut case = TestCase::new("add");
+        case.set_input("a", 2);
+        case.set_input("b", 3);
+        case.expect("sum", 5);
+        case.expect_akku(5);
+        let report = run_test(&program, &case);
+        assert!(report.passed());
+        assert_eq!(report.steps, 4);
+        case.expect_adress(0, 3);
+        let failures = run_test(&program, &case).get_failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!((failures[0].expected, failures[0].actual), (Some(3), Some(2)));
+        assert_eq!(failures[0].get_message(), "Expected adress 0 to be 3.");
+        // Entering at the loop never halts.
+        let mut endless = TestCase::new("endless");
+        endless.set_entry("LOOP");
+        endless.set_max_steps(100);
+        let report = run_test(&program, &endless);
+        assert_eq!(report.steps, 100);
+        assert_eq!(report.get_failures()[0].get_message(), "The program didn't halt within 100 steps.");
+        let mut unknown = TestCase::new("unknown");
+        unknown.set_input("c", 1);
+        assert!(!run_test(&program, &unknown).passed());
+    }
+
+    #[test]
+    fn test_suites() {
+        let program = compiler::compile("n: DS 0\nSTART: LDV n\nADD n\nSTV n\nHALT").unwrap();
+        let cases: Vec<TestCase> = [(1, 2), (4, 8), (5, 11)]
+            .iter()
+            .map(|(input, output)| {
+                let mut case = TestCase::new(&format!("double {input}"));
+                case.set_input("n", *input);
+                case.expect("n", *output);
+                case
+            })
+            .collect();
+        let suite = run_test_suite(&program, cases);
+        assert_eq!((suite.get_passed_count(), suite.get_failed_count()), (2, 1));
+        assert!(!suite.passed());
+        // Every case starts from the loaded program, not from the memory of the previous one.
+        let reports = suite.get_reports();
+        assert_eq!(reports[2].get_failures()[0].actual, Some(10));
+        assert!(reports[0].get_trace().is_empty());
+        assert_eq!(reports[2].get_trace().len(), 4);
+        assert_eq!(reports[2].get_trace()[1].get_akku_after(), 10);
+    }
+}
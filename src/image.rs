@@ -0,0 +1,149 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::{CompilerError, CompilerOutput};
+use crate::error::MimaError;
+use crate::mima::{MEMORY_SIZE, VALUE_SIZE};
+
+/*
+ * Memory images for exchanging programs with other MIMA tools. The text format has one memory
+ * cell per line: the adress and the word in hexadecimal, e.g. "00004 123456". Comments start
+ * with ';' or '#' and cells that aren't listed are zero.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CompilerOutput {
+    pub fn to_memory_image(&self) -> String {
+        self.mima_code
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| **word != 0)
+            .map(|(adress, word)| format!("{adress:05X} {word:06X}"))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    pub fn from_memory_image(image: &str, start_adress: usize) -> Result<CompilerOutput, MimaError> {
+        Ok(parse_memory_image(image, start_adress)?)
+    }
+    /*
+     * Intel HEX with three bytes per word in big endian order. Adresses are byte adresses, so
+     * word n starts at byte 3n.
+     */
+    pub fn to_intel_hex(&self) -> String {
+        let bytes: Vec<u8> = self
+            .mima_code
+            .iter()
+            .flat_map(|word| [(word >> 16) as u8, (word >> 8) as u8, *word as u8])
+            .collect();
+        let mut records = vec![];
+        for (index, chunk) in bytes.chunks(16).enumerate() {
+            let adress = index * 16;
+            // Extended linear adress record whenever the upper 16 bits of the adress change
+            if adress % 0x10000 == 0 && adress > 0 {
+                records.push(hex_record(0, 4, &[(adress >> 24) as u8, (adress >> 16) as u8]));
+            }
+            records.push(hex_record(adress as u16, 0, chunk));
+        }
+        records.push(hex_record(0, 1, &[]));
+        records.join("\n")
+    }
+    // Memory image for Logisim RAM and ROM components with a data width of 24 bits
+    pub fn to_logisim_image(&self) -> String {
+        let mut runs: Vec<(usize, usize)> = vec![];
+        for word in self.mima_code.iter() {
+            match runs.last_mut() {
+                Some((value, count)) if value == word => *count += 1,
+                _ => runs.push((*word, 1)),
+            }
+        }
+        let entries: Vec<String> = runs
+            .into_iter()
+            .flat_map(|(value, count)| match count {
+                // Logisim compresses longer runs as "count*value".
+                1..=3 => vec![format!("{value:x}"); count],
+                _ => vec![format!("{count}*{value:x}")],
+            })
+            .collect();
+        let lines = entries.chunks(8).map(|line| line.join(" "));
+        std::iter::once("v2.0 raw".to_string()).chain(lines).collect::<Vec<String>>().join("\n")
+    }
+}
+
+fn hex_record(adress: u16, record_type: u8, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8, (adress >> 8) as u8, adress as u8, record_type];
+    bytes.extend_from_slice(data);
+    let checksum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+    bytes.push(checksum);
+    format!(":{}", bytes.iter().map(|byte| format!("{byte:02X}")).collect::<String>())
+}
+
+fn parse_memory_image(image: &str, start_adress: usize) -> Result<CompilerOutput, CompilerError> {
+    let mut code = vec![];
+    for (index, line) in image.lines().enumerate() {
+        let line_number = index + 1;
+        let content = line.split([';', '#']).next().unwrap().trim();
+        if content.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = content.split(|c: char| c.is_whitespace() || c == ':').filter(|field| !field.is_empty()).collect();
+        let [adress, word] = fields[..] else {
+            return Err(CompilerError::InvalidLine { line: line_number });
+        };
+        let parse = |field: &str, limit: usize| {
+            let digits = field.strip_prefix("0x").or(field.strip_prefix("0X")).unwrap_or(field);
+            usize::from_str_radix(digits, 16)
+                .ok()
+                .filter(|value| *value < limit)
+                .ok_or(CompilerError::LiteralOutOfRange { line: line_number, value: field.to_string() })
+        };
+        let (adress, word) = (parse(adress, MEMORY_SIZE)?, parse(word, VALUE_SIZE)?);
+        if code.len() <= adress {
+            code.resize(adress + 1, 0);
+        }
+        code[adress] = word;
+    }
+    Ok(CompilerOutput::new(code, start_adress))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compiler, CompilerOutput};
+    use crate::mima::Mima;
+
+    #[test]
+    fn memory_image_round_trip() {
+        let compiled = compiler::compile("a: DS 3\nb: DS\nSTART: LDV a\nADD a\nSTV b\nHALT").unwrap();
+        let image = compiled.to_memory_image();
+        assert_eq!(image.lines().next(), Some("00000 000003"));
+        let imported = CompilerOutput::from_memory_image(&image, compiled.get_start_adress()).unwrap();
+        assert_eq!(imported.get_mima_code(), compiled.get_mima_code());
+        let mut mima = Mima::new();
+        mima.load(imported);
+        mima.run();
+        assert_eq!(mima.read_adress(1), Some(6));
+    }
+
+    #[test]
+    fn intel_hex_export() {
+        let output = CompilerOutput::new(vec![0x123456, 0xF00000], 0);
+        assert_eq!(output.to_intel_hex(), ":06000000123456F000006E\n:00000001FF");
+        // 65536 bytes are 21846 words, the next record needs an extended adress.
+        let output = CompilerOutput::new(vec![1; 21846], 0);
+        let hex = output.to_intel_hex();
+        assert!(hex.ends_with(":020000040001F9\n:020000000001FD\n:00000001FF"));
+    }
+
+    #[test]
+    fn logisim_export() {
+        let output = CompilerOutput::new(vec![3, 0, 0, 0, 0, 0x1000, 0x1000, 0xf00000], 0);
+        assert_eq!(output.to_logisim_image(), "v2.0 raw\n3 4*0 1000 1000 f00000");
+    }
+
+    #[test]
+    fn memory_image_errors() {
+        let imported = CompilerOutput::from_memory_image("# comment\n0x10: 0xF00000 ; HALT\n", 0x10).unwrap();
+        assert_eq!(imported.get_mima_code().len(), 0x11);
+        assert!(CompilerOutput::from_memory_image("10", 0).is_err());
+        assert!(CompilerOutput::from_memory_image("100000 1", 0).is_err());
+        assert!(CompilerOutput::from_memory_image("1 1000000", 0).is_err());
+    }
+}
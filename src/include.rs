@@ -0,0 +1,91 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::compiler::{split_lines, CompilerError, SourceLine};
+
+static INCLUDE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"^\s*(?i:INCLUDE)\s+"([^"]+)"\s*$"#).unwrap());
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/*
+ * Replaces every INCLUDE "file" directive with the lines of that file. The content is provided by
+ * the resolver, so the frontend decides where files come from. Included lines are attributed to
+ * the line of the INCLUDE directive.
+ */
+pub fn expand_includes(
+    lines: Vec<SourceLine>,
+    resolver: &dyn Fn(&str) -> Option<String>,
+) -> Result<Vec<SourceLine>, CompilerError> {
+    let mut expanded = vec![];
+    include_lines(lines, resolver, &mut vec![], &mut expanded)?;
+    Ok(expanded)
+}
+
+fn include_lines(
+    lines: Vec<SourceLine>,
+    resolver: &dyn Fn(&str) -> Option<String>,
+    stack: &mut Vec<String>,
+    expanded: &mut Vec<SourceLine>,
+) -> Result<(), CompilerError> {
+    for line in lines {
+        let path = match INCLUDE_REGEX.captures(&line.text) {
+            Some(captures) => captures.get(1).unwrap().as_str().to_string(),
+            None => {
+                expanded.push(line);
+                continue;
+            }
+        };
+        if stack.contains(&path) || stack.len() >= MAX_INCLUDE_DEPTH {
+            return Err(CompilerError::IncludeRecursion { path, line: line.line });
+        }
+        let content = resolver(&path).ok_or(CompilerError::UnresolvedInclude {
+            path: path.to_owned(),
+            line: line.line,
+        })?;
+        let included = split_lines(&content)
+            .into_iter()
+            .map(|included| SourceLine { line: line.line, ..included })
+            .collect();
+        stack.push(path);
+        include_lines(included, resolver, stack, expanded)?;
+        stack.pop();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compiler, CompilerOptions};
+    use crate::mima::{Command, Instruction};
+
+    fn resolve(path: &str) -> Option<String> {
+        match path {
+            "lib/math.mima" => Some("MACRO DOUBLE x\nADD x\nADD x\nENDM".to_string()),
+            "data.mima" => Some("INCLUDE \"lib/math.mima\"\nval: DS 21".to_string()),
+            "loop.mima" => Some("INCLUDE \"loop.mima\"".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn nested_includes() {
+        let assembly_source = "INCLUDE \"data.mima\"
+LDC 0
+DOUBLE val
+HALT";
+        let compiled = compiler::compile_source(assembly_source, &CompilerOptions::new(), &resolve).unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code[0], 21);
+        assert_eq!(code[2], Command { instruction: Instruction::ADD, value: 0 }.to_usize());
+        assert_eq!(compiled.get_source_map()[0].line, 1);
+    }
+
+    #[test]
+    fn include_errors() {
+        let missing = compiler::compile_source("INCLUDE \"nope.mima\"", &CompilerOptions::new(), &resolve);
+        assert!(missing.is_err_and(|err| err.to_string().contains("nope.mima")));
+        let cyclic = compiler::compile_source("INCLUDE \"loop.mima\"", &CompilerOptions::new(), &resolve);
+        assert!(cyclic.is_err_and(|err| err.to_string().contains("includes itself")));
+    }
+}
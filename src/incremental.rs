@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::compiler::{assemble, generate_machinecode, parse_statement, preprocess, ParsedProgram, Statement};
+use crate::compiler::{CompilerOptions, CompilerOutput};
+use crate::error::MimaError;
+
+/*
+ * Compiler for editors that compile on every keystroke. The statements of the lines are cached by
+ * their text, so only edited lines are lexed and parsed again. Symbols are only resolved again if
+ * the parsed program changed, edits of comments or whitespace reuse the last output.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct IncrementalCompiler {
+    options: CompilerOptions,
+    statements: HashMap<String, Statement>,
+    last: Option<(ParsedProgram, CompilerOutput)>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl IncrementalCompiler {
+    pub fn new(options: CompilerOptions) -> IncrementalCompiler {
+        IncrementalCompiler {
+            options,
+            statements: HashMap::new(),
+            last: None,
+        }
+    }
+    pub fn compile(&mut self, input: &str) -> Result<CompilerOutput, MimaError> {
+        let options = self.options;
+        let lines = preprocess(input, &options, &|_| None)?;
+        // Lines that are no longer part of the source are dropped from the cache.
+        let mut cached = std::mem::take(&mut self.statements);
+        let statements = &mut self.statements;
+        let parsed = assemble(&lines, &options, &mut |source| {
+            let statement = match cached.remove(&source.text).or_else(|| statements.get(&source.text).cloned()) {
+                Some(statement) => statement,
+                // Errors aren't cached because they contain the line number.
+                None => parse_statement(source, &options)?,
+            };
+            statements.insert(source.text.to_owned(), statement.to_owned());
+            Ok(statement)
+        })?;
+        if let Some((program, output)) = &self.last {
+            if *program == parsed {
+                return Ok(output.to_owned());
+            }
+        }
+        let output = generate_machinecode(&parsed)?;
+        self.last = Some((parsed, output.to_owned()));
+        Ok(output)
+    }
+    // Number of distinct lines whose statement is cached
+    pub fn get_cached_lines(&self) -> usize {
+        self.statements.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compiler, CompilerOptions};
+
+    use super::IncrementalCompiler;
+
+    #[test]
+    fn incremental_compilation() {
+        let mut incremental = IncrementalCompiler::new(CompilerOptions::new());
+        let source = "a: DS 5\nSTART: LDV a\nADD a\nSTV a\nHALT";
+        let first = incremental.compile(source).unwrap();
+        assert_eq!(first.get_mima_code(), compiler::compile(source).unwrap().get_mima_code());
+        assert_eq!(incremental.get_cached_lines(), 5);
+        // Comments don't change the program.
+        let commented = incremental.compile("a: DS 5\nSTART: LDV a ; load\nADD a\nSTV a\nHALT").unwrap();
+        assert_eq!(commented.get_mima_code(), first.get_mima_code());
+        let edited = "a: DS 5\nb: DS 7\nSTART: LDV b\nADD a\nSTV a\nHALT";
+        assert_eq!(incremental.compile(edited).unwrap().get_mima_code(), compiler::compile(edited).unwrap().get_mima_code());
+        assert_eq!(incremental.get_cached_lines(), 6);
+        // Errors are reported and don't break later compilations.
+        assert!(incremental.compile("a: DS 5\nLDV").is_err());
+        assert_eq!(incremental.compile(source).unwrap().get_mima_code(), first.get_mima_code());
+    }
+}
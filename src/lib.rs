@@ -1,4 +1,37 @@
-#![feature(const_refs_to_static)]
+/*
+ * The simulator, the compiler and the tools around them are plain Rust. The default "wasm"
+ * feature adds the JS bindings on top, build with default-features = false to use the crate
+ * without wasm-bindgen, e.g. in a grading server or a terminal frontend.
+ */
+pub mod mima;
+pub mod compiler;
+pub mod disassembler;
+mod include;
+mod macros;
+pub mod microcode;
+pub mod linker;
+pub mod stdlib;
+pub mod image;
+pub mod dialect;
+pub mod formatter;
+pub mod tokenizer;
+pub mod completion;
+pub mod incremental;
+mod parser;
+pub mod devices;
+pub mod grading;
+pub mod error;
+pub mod report;
+pub mod locale;
+pub mod cfg;
+pub mod lint;
+pub mod termination;
+#[cfg(feature = "arbitrary")]
+pub mod fuzzing;
 
-mod mima;
-mod compiler;
+// The types most users of the Rust API need
+pub use compiler::compiler::{build_object, compile_program};
+pub use compiler::{CompilerError, CompilerOptions, CompilerOutput};
+pub use error::MimaError;
+pub use locale::set_locale;
+pub use mima::{Command, HaltKind, HaltReason, Instruction, Mima};
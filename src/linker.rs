@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::{CompilerError, CompilerOutput, Symbol, SymbolKind};
+use crate::error::MimaError;
+use crate::mima::Command;
+
+// An instruction whose operand refers to a symbol of another object
+#[derive(Clone, Debug, PartialEq)]
+pub struct Import {
+    pub adress: usize,
+    pub name: String,
+    pub line: usize,
+}
+
+// A compiled program that can be combined with other objects by the linker
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug)]
+pub struct ObjectFile {
+    output: CompilerOutput,
+    imports: Vec<Import>,
+    exports: Vec<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ObjectFile {
+    pub fn get_mima_code(&self) -> Vec<usize> {
+        self.output.get_mima_code()
+    }
+    // Names of the symbols used from other objects, sorted
+    pub fn get_imports(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.imports.iter().map(|import| import.name.to_owned()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+    pub fn get_exports(&self) -> Vec<String> {
+        self.exports.to_owned()
+    }
+}
+
+impl ObjectFile {
+    pub fn new(output: CompilerOutput, imports: Vec<Import>, exports: Vec<String>) -> ObjectFile {
+        ObjectFile { output, imports, exports }
+    }
+}
+
+/*
+ * Places the objects one after another and resolves their imports. The first object is the main
+ * program: its start adress is the entry point and its source map and listing are kept.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn link(objects: Vec<ObjectFile>) -> Result<CompilerOutput, MimaError> {
+    Ok(link_objects(&objects)?)
+}
+
+pub fn link_objects(objects: &[ObjectFile]) -> Result<CompilerOutput, CompilerError> {
+    let mut bases = vec![];
+    let mut size = 0;
+    for object in objects {
+        bases.push(size);
+        size += object.output.mima_code.len();
+    }
+    let mut exports: HashMap<&str, Symbol> = HashMap::new();
+    for (object, base) in objects.iter().zip(bases.iter()) {
+        for name in object.exports.iter() {
+            let symbol = object.output.symbols.iter().find(|symbol| symbol.get_name() == *name).unwrap();
+            if exports.insert(name, symbol.relocated(*base)).is_some() {
+                return Err(CompilerError::DuplicateExport { name: name.to_owned() });
+            }
+        }
+    }
+    let mut code = vec![];
+    let mut relocations = vec![];
+    let mut data_relocations = vec![];
+    for (object, base) in objects.iter().zip(bases.iter()) {
+        let mut words = object
+            .output
+            .relocated_code(*base)
+            .map_err(|adress| CompilerError::RelocationOutOfRange { adress: base + adress })?;
+        for import in object.imports.iter() {
+            let symbol = exports
+                .get(import.name.as_str())
+                .ok_or(CompilerError::UnresolvedImport { name: import.name.to_owned(), line: import.line })?;
+            // The objects were compiled without knowing the kind of the imported symbol.
+            let command = Command::from_usize(words[import.adress]).unwrap();
+            let (name, line) = (import.name.to_owned(), import.line);
+            match symbol.get_kind() {
+                SymbolKind::Variable if command.instruction.is_jump() => {
+                    return Err(CompilerError::VariableAsJumpTarget { name, line })
+                }
+                SymbolKind::Label if !command.instruction.is_jump() && !command.instruction.takes_constant() => {
+                    return Err(CompilerError::LabelAsDataOperand { name, line })
+                }
+                _ => (),
+            }
+            let command = command
+                .with_value(symbol.get_adress())
+                .ok_or(CompilerError::RelocationOutOfRange { adress: base + import.adress })?;
+            words[import.adress] = command.to_usize();
+            relocations.push(base + import.adress);
+        }
+        relocations.extend(object.output.relocations.iter().map(|adress| base + adress));
+        data_relocations.extend(object.output.data_relocations.iter().map(|adress| base + adress));
+        code.extend(words);
+    }
+    relocations.sort();
+    let main = objects.first().map(|object| object.output.to_owned());
+    let symbols = objects
+        .iter()
+        .zip(bases.iter())
+        .flat_map(|(object, base)| object.output.symbols.iter().map(|symbol| symbol.relocated(*base)))
+        .collect();
+    Ok(CompilerOutput {
+        mima_code: code,
+        start_adress: main.as_ref().map_or(0, |main| main.start_adress),
+        warnings: objects.iter().flat_map(|object| object.output.warnings.to_owned()).collect(),
+        source_map: main.as_ref().map(|main| main.source_map.to_owned()).unwrap_or_default(),
+        title: main.as_ref().and_then(|main| main.title.to_owned()),
+        entry: main.as_ref().and_then(|main| main.entry.to_owned()),
+        listing: main.map(|main| main.listing).unwrap_or_default(),
+        symbols,
+        relocations,
+        data_relocations,
+        code_length: objects.iter().map(|object| object.output.code_length).sum(),
+        data_length: objects.iter().map(|object| object.output.data_length).sum(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compiler;
+    use crate::mima::Mima;
+
+    use super::link;
+
+    #[test]
+    fn link_main_and_library() {
+        let main = compiler::compile_object("x: DS 5\nresult: DS\nSTART: LDV x\nCALL DOUBLE\nSTV result\nHALT").unwrap();
+        assert_eq!(main.get_imports(), vec!["DOUBLE"]);
+        let repeated = compiler::compile_object("START: JMP A\nJMP B\nJMP A").unwrap();
+        assert_eq!(repeated.get_imports(), vec!["A", "B"]);
+        let library = compiler::compile_object("EXPORT DOUBLE\ntmp: DS\nDOUBLE: STV tmp\nADD tmp\nRET").unwrap();
+        assert_eq!(library.get_exports(), vec!["DOUBLE"]);
+        let linked = link(vec![main, library]).unwrap();
+        assert_eq!(linked.get_mima_code().len(), 10);
+        assert_eq!(linked.get_start_adress(), 2);
+        let mut mima = Mima::new();
+        mima.load(linked);
+        mima.run();
+        assert_eq!(mima.read_symbol("result"), Some(10));
+        // Pointers of the library point into the library after linking.
+        let main = compiler::compile_object("START: CALL FIRST\nHALT").unwrap();
+        let library = compiler::compile_object("EXPORT FIRST\nptr: DS tbl\ntbl: DS 7\nFIRST: LDIV ptr\nRET").unwrap();
+        let linked = link(vec![main, library]).unwrap();
+        assert_eq!(linked.get_data_relocations(), vec![2]);
+        let mut mima = Mima::new();
+        mima.load(linked);
+        mima.run();
+        assert_eq!(mima.get_akku(), 7);
+    }
+
+    #[test]
+    fn link_errors() {
+        let main = compiler::compile_object("START: JMP MISSING").unwrap();
+        assert!(link(vec![main.clone()]).is_err_and(|err| err.to_string().contains("MISSING")));
+        let library = compiler::compile_object("EXPORT MISSING\nMISSING: DS 1").unwrap();
+        assert!(link(vec![main, library.clone()]).is_err_and(|err| err.to_string().contains("can't be used as a jump target")));
+        assert!(link(vec![library.clone(), library]).is_err_and(|err| err.to_string().contains("more than one object")));
+        assert!(compiler::compile_object("EXPORT NOPE\nHALT").is_err());
+        // Without linking, undefined symbols are still an error.
+        assert!(compiler::compile("START: JMP MISSING").is_err());
+    }
+}
@@ -0,0 +1,250 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::cfg::analyze_cfg;
+use crate::compiler::compiler::{generate_machinecode, layout, parse_assembly, Param};
+use crate::compiler::{CompilerOptions, SymbolKind, WarningKind};
+use crate::mima::{Command, Instruction};
+use crate::termination::analyze_termination;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LintRule {
+    MissingHalt,
+    UnusedLabel,
+    UnusedVariable,
+    UnreachableCode,
+    CaseConflict,
+    MagicNumber,
+    JumpIntoData,
+    EndlessLoop,
+}
+
+const RULES: [LintRule; 8] = [
+    LintRule::MissingHalt,
+    LintRule::UnusedLabel,
+    LintRule::UnusedVariable,
+    LintRule::UnreachableCode,
+    LintRule::CaseConflict,
+    LintRule::MagicNumber,
+    LintRule::JumpIntoData,
+    LintRule::EndlessLoop,
+];
+
+impl LintRule {
+    // Stable name of the rule, used to configure it
+    pub fn id(self) -> &'static str {
+        match self {
+            LintRule::MissingHalt => "missing-halt",
+            LintRule::UnusedLabel => "unused-label",
+            LintRule::UnusedVariable => "unused-variable",
+            LintRule::UnreachableCode => "unreachable-code",
+            LintRule::CaseConflict => "case-conflict",
+            LintRule::MagicNumber => "magic-number",
+            LintRule::JumpIntoData => "jump-into-data",
+            LintRule::EndlessLoop => "endless-loop",
+        }
+    }
+    pub fn from_id(id: &str) -> Option<LintRule> {
+        RULES.into_iter().find(|rule| rule.id() == id)
+    }
+    fn default_severity(self) -> LintSeverity {
+        match self {
+            LintRule::MagicNumber => LintSeverity::Info,
+            LintRule::JumpIntoData => LintSeverity::Error,
+            _ => LintSeverity::Warning,
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LintSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintWarning {
+    pub rule: LintRule,
+    pub severity: LintSeverity,
+    pub line: usize,
+    message: String,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl LintWarning {
+    pub fn get_rule_id(&self) -> String {
+        self.rule.id().to_string()
+    }
+    pub fn get_message(&self) -> String {
+        self.message.to_owned()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct RuleSetting {
+    rule: LintRule,
+    severity: LintSeverity,
+    enabled: bool,
+}
+
+/*
+ * Checks programs for things that compile but are likely mistakes or hard to read. Every rule can
+ * be turned off or given another severity by its id. Programs that don't compile aren't linted,
+ * diagnose reports their error instead.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug)]
+pub struct Linter {
+    pub options: CompilerOptions,
+    settings: Vec<RuleSetting>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Linter {
+    pub fn new() -> Linter {
+        Linter {
+            options: CompilerOptions::new(),
+            settings: RULES.into_iter().map(|rule| RuleSetting { rule, severity: rule.default_severity(), enabled: true }).collect(),
+        }
+    }
+    pub fn get_rule_ids(&self) -> Vec<String> {
+        RULES.iter().map(|rule| rule.id().to_string()).collect()
+    }
+    // Returns false if there is no rule with that id.
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) -> bool {
+        self.setting(id).map(|setting| setting.enabled = enabled).is_some()
+    }
+    pub fn set_severity(&mut self, id: &str, severity: LintSeverity) -> bool {
+        self.setting(id).map(|setting| setting.severity = severity).is_some()
+    }
+    // Warnings of the enabled rules ordered by line
+    pub fn lint(&self, source: &str) -> Vec<LintWarning> {
+        let Ok(parsed) = parse_assembly(source, &self.options, &|_| None) else {
+            return vec![];
+        };
+        let Ok(output) = generate_machinecode(&parsed) else {
+            return vec![];
+        };
+        let parsed = layout(&parsed);
+        let mut found: Vec<(LintRule, usize, String)> = output
+            .get_warnings()
+            .iter()
+            .filter_map(|warning| {
+                let rule = match warning.get_kind() {
+                    WarningKind::CaseConflict => LintRule::CaseConflict,
+                    WarningKind::UnusedVariable => LintRule::UnusedVariable,
+                    WarningKind::UnusedLabel => LintRule::UnusedLabel,
+                    // The control flow graph knows better than the compiler, see below.
+                    WarningKind::UnreachableCode => return None,
+                };
+                Some((rule, warning.get_line(), warning.get_message()))
+            })
+            .collect();
+        // Only the first of adjacent unreachable blocks is reported.
+        let blocks = analyze_cfg(&output).get_blocks();
+        for (index, block) in blocks.iter().enumerate() {
+            let follows_unreachable = index > 0 && !blocks[index - 1].reachable && blocks[index - 1].end + 1 == block.start;
+            if let (false, false, Some(line)) = (block.reachable, follows_unreachable, block.line) {
+                found.push((LintRule::UnreachableCode, line, "Instruction is unreachable.".to_string()));
+            }
+        }
+        if let Some(last) = parsed.commands.last() {
+            if !parsed.commands.iter().any(|cmd| cmd.instruction == Instruction::HALT) {
+                found.push((LintRule::MissingHalt, last.source.line, "The program never halts, there is no HALT instruction.".to_string()));
+            }
+        }
+        for warning in analyze_termination(&output) {
+            if let Some(line) = warning.line {
+                found.push((LintRule::EndlessLoop, line, warning.get_message()));
+            }
+        }
+        let code = output.get_mima_code();
+        let variables: Vec<_> = output.get_symbols().into_iter().filter(|symbol| symbol.get_kind() == SymbolKind::Variable).collect();
+        for cmd in parsed.commands.iter() {
+            // Operands computed from symbols or constants have a name, only bare numbers are magic.
+            if let Param::Fixed(value) = cmd.param {
+                let bits = cmd.instruction.operand_bits();
+                let value = if cmd.instruction.takes_constant() && value >> (bits - 1) == 1 { value as i64 - (1 << bits) } else { value as i64 };
+                if !(-1..=1).contains(&value) {
+                    let message = format!("Operand {value} of {} is a magic number, consider naming it with EQU.", cmd.instruction.mnemonic());
+                    found.push((LintRule::MagicNumber, cmd.source.line, message));
+                }
+            }
+            if !cmd.instruction.is_jump() {
+                continue;
+            }
+            let Some(target) = cmd.adress.and_then(|adress| code.get(adress)).and_then(|word| Command::from_usize(*word)).map(|command| command.value) else {
+                continue;
+            };
+            if let Some(variable) = variables.iter().find(|variable| (variable.get_adress()..variable.get_adress() + variable.get_size()).contains(&target)) {
+                let message = format!("{} jumps to adress {target}, which holds the variable '{}'.", cmd.instruction.mnemonic(), variable.get_name());
+                found.push((LintRule::JumpIntoData, cmd.source.line, message));
+            }
+        }
+        found.sort_by_key(|(rule, line, _)| (*line, RULES.iter().position(|other| other == rule)));
+        found
+            .into_iter()
+            .filter_map(|(rule, line, message)| {
+                let setting = self.settings.iter().find(|setting| setting.rule == rule)?;
+                setting.enabled.then_some(LintWarning { rule, severity: setting.severity, line, message })
+            })
+            .collect()
+    }
+}
+
+impl Linter {
+    fn setting(&mut self, id: &str) -> Option<&mut RuleSetting> {
+        let rule = LintRule::from_id(id)?;
+        self.settings.iter_mut().find(|setting| setting.rule == rule)
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Lints the program with all rules at their default severity.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn lint(source: &str) -> Vec<LintWarning> {
+    Linter::new().lint(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, LintRule, LintSeverity, Linter};
+
+    #[test]
+    fn lint_rules() {
+        let source = "N EQU 7\nx: DS 2 * 0\nunused: DS\nSTART: LDC 42\nADD x\nLDC N\nLDC -1\nJMP x+1\nLATER: STV x";
+        let warnings: Vec<(LintRule, LintSeverity, usize)> = lint(source).iter().map(|warning| (warning.rule, warning.severity, warning.line)).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (LintRule::UnusedVariable, LintSeverity::Warning, 3),
+                (LintRule::MagicNumber, LintSeverity::Info, 4),
+                (LintRule::JumpIntoData, LintSeverity::Error, 8),
+                (LintRule::MissingHalt, LintSeverity::Warning, 9),
+                (LintRule::UnusedLabel, LintSeverity::Warning, 9),
+                (LintRule::UnreachableCode, LintSeverity::Warning, 9),
+            ]
+        );
+        assert_eq!(lint(source)[2].get_message(), "JMP jumps to adress 1, which holds the variable 'x'.");
+        assert_eq!(lint(source)[1].get_message(), "Operand 42 of LDC is a magic number, consider naming it with EQU.");
+        let mut linter = Linter::new();
+        assert!(linter.set_enabled("magic-number", false));
+        assert!(linter.set_severity("missing-halt", LintSeverity::Error));
+        assert!(!linter.set_enabled("no-such-rule", false));
+        let warnings = linter.lint(source);
+        assert!(warnings.iter().all(|warning| warning.rule != LintRule::MagicNumber));
+        assert_eq!(warnings.iter().find(|warning| warning.get_rule_id() == "missing-halt").unwrap().severity, LintSeverity::Error);
+        // Programs with errors are left to the compiler.
+        assert!(lint("JMP NOWHERE").is_empty());
+        assert!(lint("START: LDC 1\nHALT").is_empty());
+    }
+}
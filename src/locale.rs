@@ -0,0 +1,154 @@
+use std::cell::Cell;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::CompilerError;
+
+// Languages of the messages for JS, error codes are the same in all of them
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Locale {
+    #[default]
+    English,
+    // The language of the course at KIT
+    German,
+}
+
+thread_local! {
+    static LOCALE: Cell<Locale> = Cell::new(Locale::default());
+}
+
+/*
+ * Selects the language of error messages by its tag like "de" or "en-US". Returns false and
+ * keeps the current language if there are no messages in that language. Display of the Rust
+ * error types stays English.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn set_locale(tag: &str) -> bool {
+    let language = tag.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    let locale = match language.as_str() {
+        "en" => Locale::English,
+        "de" => Locale::German,
+        _ => return false,
+    };
+    LOCALE.set(locale);
+    true
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn get_locale() -> Locale {
+    LOCALE.get()
+}
+
+// Picks the message for the current language.
+pub(crate) fn localize(english: String, german: String) -> String {
+    match get_locale() {
+        Locale::English => english,
+        Locale::German => german,
+    }
+}
+
+// The message of a compiler error in the current language
+pub(crate) fn compiler_message(error: &CompilerError) -> String {
+    match get_locale() {
+        Locale::English => error.to_string(),
+        Locale::German => german(error),
+    }
+}
+
+fn german(error: &CompilerError) -> String {
+    let meant = |suggestion: &Option<String>| suggestion.as_ref().map(|name| format!(" Meinten Sie '{name}'?")).unwrap_or_default();
+    match error {
+        CompilerError::InvalidLine { line } => format!("Ungültige Anweisung in Zeile '{line}'."),
+        CompilerError::UnknownVariable { name, line, suggestion } => {
+            format!("Variable '{name}' in Zeile '{line}' wurde nicht gefunden.{}", meant(suggestion))
+        }
+        CompilerError::UnknownLabel { name, line, suggestion } => {
+            format!("Label '{name}' in Zeile '{line}' wurde nicht gefunden.{}", meant(suggestion))
+        }
+        CompilerError::UnknownInstruction { name, line, suggestion } => {
+            format!("Befehl '{name}' in Zeile '{line}' ist unbekannt.{}", meant(suggestion))
+        }
+        CompilerError::LiteralOutOfRange { line, value } => format!("Literal '{value}' in Zeile '{line}' liegt außerhalb des Wertebereichs."),
+        CompilerError::ConstantRedefinition { line, name } => format!("Konstante '{name}' in Zeile '{line}' ist bereits definiert."),
+        CompilerError::OverlappingAdress { adress, line } => format!("Adresse '{adress}' in Zeile '{line}' ist mehrfach belegt."),
+        CompilerError::UnterminatedMacro { name, line } => format!("Dem Makro '{name}' in Zeile '{line}' fehlt ENDM."),
+        CompilerError::MacroArity { name, line, expected, found } => {
+            format!("Makro '{name}' in Zeile '{line}' erwartet {expected} Argumente, erhielt aber {found}.")
+        }
+        CompilerError::MacroRecursion { name, line } => format!("Makro '{name}' in Zeile '{line}' expandiert rekursiv."),
+        CompilerError::UnresolvedInclude { path, line } => format!("Eingebundene Datei '{path}' in Zeile '{line}' wurde nicht gefunden."),
+        CompilerError::IncludeRecursion { path, line } => format!("Datei '{path}' in Zeile '{line}' bindet sich selbst ein."),
+        CompilerError::OperandOutOfRange { line, value, bits } => format!("Operand '{value}' in Zeile '{line}' passt nicht in {bits} Bits."),
+        CompilerError::MissingOperand { line, name } => format!("Befehl '{name}' in Zeile '{line}' benötigt einen Operanden."),
+        CompilerError::UnexpectedOperand { line, name } => format!("Befehl '{name}' in Zeile '{line}' erwartet keinen Operanden."),
+        CompilerError::VariableAsJumpTarget { name, line } => format!("Variable '{name}' in Zeile '{line}' kann kein Sprungziel sein."),
+        CompilerError::LabelAsDataOperand { name, line } => {
+            format!("Label '{name}' in Zeile '{line}' kann nicht als Datenoperand verwendet werden.")
+        }
+        CompilerError::DuplicateSymbol { name, line, first_line } => {
+            format!("Symbol '{name}' in Zeile '{line}' ist bereits in Zeile '{first_line}' definiert.")
+        }
+        CompilerError::ReservedName { name, line } => format!("Symbol '{name}' in Zeile '{line}' darf nicht wie ein Befehl heißen."),
+        CompilerError::UnsupportedInstruction { name, line, profile } => {
+            format!("Befehl '{name}' in Zeile '{line}' ist im Profil {profile:?} nicht verfügbar.")
+        }
+        CompilerError::UnknownExport { name, line } => format!("Exportiertes Symbol '{name}' in Zeile '{line}' ist nicht definiert."),
+        CompilerError::DuplicateExport { name } => format!("Symbol '{name}' wird von mehr als einem Objekt exportiert."),
+        CompilerError::UnresolvedImport { name, line } => format!("Symbol '{name}' in Zeile '{line}' wird von keinem Objekt exportiert."),
+        CompilerError::UnexpectedCharacter { line, column, character } => {
+            format!("Unerwartetes Zeichen '{character}' in Zeile '{line}' an Spalte '{column}'.")
+        }
+        CompilerError::UnexpectedToken { line, column, found } => {
+            // The parser names the end of the line in English.
+            let found = if found == "end of line" { "Zeilenende" } else { found };
+            format!("Unerwartetes '{found}' in Zeile '{line}' an Spalte '{column}'.")
+        }
+        CompilerError::InvalidLiteral { line, column, value } => format!("Ungültiges Literal '{value}' in Zeile '{line}' an Spalte '{column}'."),
+        CompilerError::InvalidIdentifier { line, column, name } => {
+            format!("Ungültiger Bezeichner '{name}' in Zeile '{line}' an Spalte '{column}'.")
+        }
+        CompilerError::UnterminatedString { line, column } => {
+            format!("Die Zeichenkette in Zeile '{line}' ab Spalte '{column}' ist nicht abgeschlossen.")
+        }
+        CompilerError::ExpressionOverflow { line, expression } => format!("Der Ausdruck '{expression}' in Zeile '{line}' läuft über."),
+        CompilerError::DivisionByZero { line, expression } => format!("Division durch null im Ausdruck '{expression}' in Zeile '{line}'."),
+        CompilerError::NotConstant { line, expression } => format!("Der Ausdruck '{expression}' in Zeile '{line}' muss konstant sein."),
+        CompilerError::CyclicConstant { name, line } => format!("Konstante '{name}' in Zeile '{line}' ist durch sich selbst definiert."),
+        CompilerError::RelocationOutOfRange { adress } => {
+            format!("Der verschobene Operand passt nicht in den Befehl an Adresse '{adress}'.")
+        }
+        CompilerError::CodeAfterEnd { line } => format!("Zeile '{line}' folgt auf die END-Direktive."),
+        CompilerError::UnknownEntry { name, line } => format!("Einsprunglabel '{name}' in Zeile '{line}' ist nicht definiert."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compiler, CompilerOptions};
+    use crate::mima::{HaltReason, Mima};
+
+    use super::{get_locale, set_locale, Locale};
+
+    #[test]
+    fn german_messages() {
+        assert!(!set_locale("fr"));
+        assert_eq!(get_locale(), Locale::English);
+        assert!(set_locale("de-DE"));
+        let error = compiler::compile("LOOP: JMP LOPO").unwrap_err();
+        assert_eq!(error.get_code(), "UNKNOWN_LABEL");
+        assert_eq!(error.to_string(), "Label 'LOPO' in Zeile '1' wurde nicht gefunden. Meinten Sie 'LOOP'?");
+        let error = compiler::compile("LDV (1").unwrap_err();
+        assert_eq!(error.to_string(), "Unerwartetes 'Zeilenende' in Zeile '1' an Spalte '7'.");
+        let diagnostic = compiler::diagnose("a: DS\nLDV b", CompilerOptions::new()).unwrap();
+        assert_eq!(diagnostic.get_message(), "Variable 'b' in Zeile '2' wurde nicht gefunden.");
+        assert_eq!(HaltReason::uninitialized_read(5).get_message(), "Adresse 5 wurde gelesen, bevor sie geschrieben wurde.");
+        assert!(Mima::with_config(3, 24).err().unwrap().to_string().starts_with("Speichergröße 3"));
+        // The Rust error type itself stays English.
+        let error = compiler::compile_program("HALT\nEND NOPE", &CompilerOptions::new(), &|_| None).unwrap_err();
+        assert_eq!(error.to_string(), "Entry label 'NOPE' in line '2' isn't defined.");
+        assert!(set_locale("en"));
+        assert_eq!(compiler::compile("LOOP: JMP LOPO").unwrap_err().to_string(), "Couldn't find label 'LOPO' in line '1'. Did you mean 'LOOP'?");
+    }
+}
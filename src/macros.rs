@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+use crate::compiler::{identifier_pattern, CompilerError, SourceLine};
+
+static MACRO_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(concat!(r"^\s*(?i:MACRO)\s+(", identifier_pattern!(), r")\s*(.*)$")).unwrap());
+static ENDM_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?i:ENDM)\s*$").unwrap());
+static CALL_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(concat!(r"^\s*(?:(", identifier_pattern!(), r"):)?\s*(", identifier_pattern!(), r")\s*(.*)$")).unwrap());
+
+// Guards against macros that (indirectly) invoke themselves.
+const MAX_EXPANSION_DEPTH: usize = 32;
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<SourceLine>,
+}
+
+/*
+ * Collects all MACRO ... ENDM definitions and replaces every invocation with the macro body.
+ * Expanded lines keep the line of the invocation and additionally remember the line of the
+ * definition they were taken from.
+ */
+pub fn expand_macros(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>, CompilerError> {
+    let mut macros: HashMap<String, Macro> = HashMap::new();
+    let mut program = vec![];
+    let mut lines = lines.into_iter();
+    while let Some(line) = lines.next() {
+        match MACRO_REGEX.captures(&line.text) {
+            Some(captures) => {
+                let name = captures.get(1).unwrap().as_str().to_string();
+                let params = split_list(captures.get(2).unwrap().as_str());
+                let mut body = vec![];
+                loop {
+                    match lines.next() {
+                        Some(body_line) if ENDM_REGEX.is_match(&body_line.text) => break,
+                        Some(body_line) => body.push(body_line),
+                        None => return Err(CompilerError::UnterminatedMacro { name, line: line.line }),
+                    }
+                }
+                macros.insert(name.to_uppercase(), Macro { params, body });
+            }
+            None => program.push(line),
+        }
+    }
+    let mut expanded = vec![];
+    for line in program {
+        expand_line(&macros, line, 0, &mut expanded)?;
+    }
+    Ok(expanded)
+}
+
+fn expand_line(
+    macros: &HashMap<String, Macro>,
+    line: SourceLine,
+    depth: usize,
+    expanded: &mut Vec<SourceLine>,
+) -> Result<(), CompilerError> {
+    let captures = match CALL_REGEX.captures(&line.text) {
+        Some(captures) => captures,
+        None => {
+            expanded.push(line);
+            return Ok(());
+        }
+    };
+    let name = captures.get(2).unwrap().as_str();
+    let definition = match macros.get(&name.to_uppercase()) {
+        Some(definition) => definition,
+        None => {
+            expanded.push(line);
+            return Ok(());
+        }
+    };
+    if depth >= MAX_EXPANSION_DEPTH {
+        return Err(CompilerError::MacroRecursion { name: name.to_string(), line: line.line });
+    }
+    let args = split_list(captures.get(3).unwrap().as_str());
+    if args.len() != definition.params.len() {
+        return Err(CompilerError::MacroArity {
+            name: name.to_string(),
+            line: line.line,
+            expected: definition.params.len(),
+            found: args.len(),
+        });
+    }
+    let label = captures.get(1).map(|label| label.as_str());
+    for (index, body_line) in definition.body.iter().enumerate() {
+        let mut text = substitute(&body_line.text, &definition.params, &args);
+        // A label in front of the invocation marks the first expanded line.
+        if let (0, Some(label)) = (index, label) {
+            text = format!("{label}: {}", text.trim_start());
+        }
+        let body_line = SourceLine {
+            text,
+            line: line.line,
+            macro_line: Some(body_line.macro_line.unwrap_or(body_line.line)),
+        };
+        expand_line(macros, body_line, depth + 1, expanded)?;
+    }
+    Ok(())
+}
+
+// Replaces whole-word occurences of the parameters with the arguments of the invocation.
+fn substitute(text: &str, params: &[String], args: &[String]) -> String {
+    if params.is_empty() {
+        return text.to_string();
+    }
+    let alternatives: Vec<String> = params.iter().map(|param| regex::escape(param)).collect();
+    let pattern = Regex::new(&format!(r"\b({})\b", alternatives.join("|"))).unwrap();
+    pattern
+        .replace_all(text, |captures: &Captures| {
+            let index = params.iter().position(|param| param == &captures[1]).unwrap();
+            args[index].to_owned()
+        })
+        .into_owned()
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compiler, SourceMapping};
+    use crate::mima::{Command, Instruction};
+
+    #[test]
+    fn macro_expansion() {
+        let assembly_source = "MACRO SUBTRACT x, one
+NOT
+ADD one
+ADD x
+ENDM
+a: DS 5
+b: DS 3
+one: DS 1
+START: LDV b
+SUBTRACT a, one
+HALT";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let expected = [
+            Command { instruction: Instruction::LDV, value: 1 }.to_usize(),
+            Command { instruction: Instruction::NOT, value: 0 }.to_usize(),
+            Command { instruction: Instruction::ADD, value: 2 }.to_usize(),
+            Command { instruction: Instruction::ADD, value: 0 }.to_usize(),
+            Command { instruction: Instruction::HALT, value: 0 }.to_usize(),
+        ];
+        assert_eq!(compiled.get_mima_code()[3..], expected);
+        // The expanded words point at the invocation and at the definition.
+        let source_map = compiled.get_source_map();
+        assert_eq!(source_map[4], SourceMapping { adress: 4, line: 10, macro_line: Some(2) });
+        assert_eq!(source_map[6], SourceMapping { adress: 6, line: 10, macro_line: Some(4) });
+        assert_eq!(source_map[7], SourceMapping { adress: 7, line: 11, macro_line: None });
+    }
+
+    #[test]
+    fn labeled_nested_invocation() {
+        let assembly_source = "MACRO TWICE x
+ADD x
+ADD x
+ENDM
+MACRO FOUR x
+TWICE x
+TWICE x
+ENDM
+a: DS 1
+LDC 0
+LOOP: FOUR a
+JMP LOOP";
+        let compiled = compiler::compile(assembly_source).unwrap();
+        let code = compiled.get_mima_code();
+        assert_eq!(code.len(), 7);
+        assert_eq!(code[6], Command { instruction: Instruction::JMP, value: 2 }.to_usize());
+    }
+
+    #[test]
+    fn macro_errors() {
+        let arity = compiler::compile("MACRO INC x\nADD x\nENDM\nINC\nHALT");
+        assert!(arity.is_err_and(|err| err.to_string().contains("INC")));
+        assert!(compiler::compile("MACRO INC x\nADD x\nHALT").is_err());
+        assert!(compiler::compile("MACRO LOOPY\nLOOPY\nENDM\nLOOPY").is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::mima::Instruction;
+
+/*
+ * Control word layout of the MIMA microarchitecture as taught in the lecture. The 'r' lines make
+ * a register read from the bus, the 'w' lines make it drive the bus.
+ */
+pub const AR: u32 = 1 << 27;
+pub const AW: u32 = 1 << 26;
+pub const X: u32 = 1 << 25;
+pub const Y: u32 = 1 << 24;
+pub const Z: u32 = 1 << 23;
+pub const E: u32 = 1 << 22;
+pub const PR: u32 = 1 << 21;
+pub const PW: u32 = 1 << 20;
+pub const IR: u32 = 1 << 19;
+pub const IW: u32 = 1 << 18;
+pub const DR: u32 = 1 << 17;
+pub const DW: u32 = 1 << 16;
+pub const S: u32 = 1 << 15;
+pub const R: u32 = 1 << 11;
+pub const W: u32 = 1 << 10;
+
+// The ALU operation is encoded in bits 12 to 14.
+const ALU_SHIFT: u32 = 12;
+pub const ALU_ADD: u32 = 1 << ALU_SHIFT;
+pub const ALU_RAR: u32 = 2 << ALU_SHIFT;
+pub const ALU_AND: u32 = 3 << ALU_SHIFT;
+pub const ALU_OR: u32 = 4 << ALU_SHIFT;
+pub const ALU_XOR: u32 = 5 << ALU_SHIFT;
+pub const ALU_NOT: u32 = 6 << ALU_SHIFT;
+pub const ALU_EQL: u32 = 7 << ALU_SHIFT;
+
+// Reads the instruction at IAR into IR and increments IAR.
+const FETCH: [u32; 5] = [PW | S | X | R, E | Y | R, ALU_ADD | R, Z | PR, DW | IR];
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum MicroPhase {
+    #[default]
+    Fetch,
+    Decode,
+    Execute,
+}
+
+// Internal registers of the datapath that aren't visible on the instruction level
+#[derive(Clone, Debug, Default)]
+pub struct MicroState {
+    pub ir: usize,
+    pub sar: usize,
+    pub sdr: usize,
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
+    pub phase: MicroPhase,
+    // Index of the next control word within the current phase
+    pub position: usize,
+    // Instruction whose microprogram is executed and its adress
+    pub instruction: Option<Instruction>,
+    pub adress: usize,
+    // Control word and bus value of the last cycle
+    pub control: u32,
+    pub bus: Option<usize>,
+    pub trace: Option<MicroTrace>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug)]
+pub struct MicroDebug {
+    pub ir: usize,
+    pub sar: usize,
+    pub sdr: usize,
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
+    pub phase: MicroPhase,
+    pub control: u32,
+    pub bus: Option<usize>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Register {
+    Akku,
+    Iar,
+    Ir,
+    Sar,
+    Sdr,
+    X,
+    Y,
+    Z,
+    One,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AluOperation {
+    None,
+    Add,
+    Rar,
+    And,
+    Or,
+    Xor,
+    Not,
+    Eql,
+}
+
+// Which registers drive the bus and which latch from it, together with the control lines
+const DRIVERS: [(u32, Register); 6] = [
+    (AW, Register::Akku),
+    (Z, Register::Z),
+    (E, Register::One),
+    (PW, Register::Iar),
+    (IW, Register::Ir),
+    (DW, Register::Sdr),
+];
+const LATCHES: [(u32, Register); 7] = [
+    (AR, Register::Akku),
+    (X, Register::X),
+    (Y, Register::Y),
+    (PR, Register::Iar),
+    (IR, Register::Ir),
+    (DR, Register::Sdr),
+    (S, Register::Sar),
+];
+
+// Activity of the datapath during one cycle, meant for animating the datapath diagram
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MicroTrace {
+    phase: MicroPhase,
+    control: u32,
+    bus: Option<usize>,
+    drivers: Vec<Register>,
+    latches: Vec<Register>,
+    alu: AluOperation,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl MicroTrace {
+    pub fn get_phase(&self) -> MicroPhase {
+        self.phase
+    }
+    pub fn get_control(&self) -> u32 {
+        self.control
+    }
+    // Value on the bus, None if no register drove it
+    pub fn get_bus(&self) -> Option<usize> {
+        self.bus
+    }
+    pub fn get_drivers(&self) -> Vec<Register> {
+        self.drivers.to_owned()
+    }
+    // Registers that took the value of the bus, memory reads into SDR are reported by is_read.
+    pub fn get_latches(&self) -> Vec<Register> {
+        self.latches.to_owned()
+    }
+    pub fn get_alu(&self) -> AluOperation {
+        self.alu
+    }
+    pub fn is_read(&self) -> bool {
+        self.control & R != 0
+    }
+    pub fn is_write(&self) -> bool {
+        self.control & W != 0
+    }
+}
+
+impl MicroTrace {
+    pub fn new(phase: MicroPhase, control: u32, bus: Option<usize>) -> MicroTrace {
+        let active = |lines: &[(u32, Register)]| {
+            lines
+                .iter()
+                .filter(|(line, _)| control & line != 0)
+                .map(|(_, register)| *register)
+                .collect::<Vec<Register>>()
+        };
+        let alu = match alu_operation(control) {
+            0 => AluOperation::None,
+            1 => AluOperation::Add,
+            2 => AluOperation::Rar,
+            3 => AluOperation::And,
+            4 => AluOperation::Or,
+            5 => AluOperation::Xor,
+            6 => AluOperation::Not,
+            _ => AluOperation::Eql,
+        };
+        MicroTrace {
+            phase,
+            control,
+            bus,
+            drivers: active(&DRIVERS),
+            latches: if bus.is_some() { active(&LATCHES) } else { vec![] },
+            alu,
+        }
+    }
+}
+
+impl MicroState {
+    pub fn to_debug(&self) -> MicroDebug {
+        MicroDebug {
+            ir: self.ir,
+            sar: self.sar,
+            sdr: self.sdr,
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            phase: self.phase,
+            control: self.control,
+            bus: self.bus,
+        }
+    }
+}
+
+pub fn alu_operation(control: u32) -> u32 {
+    (control >> ALU_SHIFT) & 0b111
+}
+
+/*
+ * Microprograms of the default control store. JMN only runs its microprogram if the akku is
+ * negative. Instructions of the extensions have no microcode and are executed at once.
+ */
+fn default_microprogram(instruction: Instruction) -> Option<Vec<u32>> {
+    let program = match instruction {
+        Instruction::LDC => vec![IW | AR],
+        Instruction::LDV => vec![IW | S | R, R, R, DW | AR],
+        Instruction::STV => vec![AW | DR, IW | S | W, W, W],
+        Instruction::ADD => vec![IW | S | R, AW | X | R, R, DW | Y, ALU_ADD, Z | AR],
+        Instruction::AND => vec![IW | S | R, AW | X | R, R, DW | Y, ALU_AND, Z | AR],
+        Instruction::OR => vec![IW | S | R, AW | X | R, R, DW | Y, ALU_OR, Z | AR],
+        Instruction::XOR => vec![IW | S | R, AW | X | R, R, DW | Y, ALU_XOR, Z | AR],
+        Instruction::EQL => vec![IW | S | R, AW | X | R, R, DW | Y, ALU_EQL, Z | AR],
+        Instruction::JMP | Instruction::JMN => vec![IW | PR],
+        Instruction::LDIV => vec![IW | S | R, R, R, DW | S | R, R, R, DW | AR],
+        Instruction::STIV => vec![IW | S | R, R, R, DW | S, AW | DR, W, W, W],
+        Instruction::NOT => vec![AW | X, ALU_NOT, Z | AR],
+        Instruction::RAR => vec![AW | X, ALU_RAR, Z | AR],
+        _ => return None,
+    };
+    Some(program)
+}
+
+// Names of the control lines in the order of the control word
+const SIGNALS: [(u32, &str); 15] = [
+    (AR, "Ar"),
+    (AW, "Aw"),
+    (X, "X"),
+    (Y, "Y"),
+    (Z, "Z"),
+    (E, "E"),
+    (PR, "Pr"),
+    (PW, "Pw"),
+    (IR, "Ir"),
+    (IW, "Iw"),
+    (DR, "Dr"),
+    (DW, "Dw"),
+    (S, "S"),
+    (R, "R"),
+    (W, "W"),
+];
+
+// Lists the active control lines of a control word, e.g. "Iw S R".
+fn describe(control: u32) -> String {
+    let mut signals: Vec<String> = SIGNALS
+        .iter()
+        .filter(|(line, _)| control & line != 0)
+        .map(|(_, name)| name.to_string())
+        .collect();
+    if alu_operation(control) != 0 {
+        signals.push(format!("ALU {:?}", MicroTrace::new(MicroPhase::Execute, control, None).get_alu()).to_uppercase());
+    }
+    signals.join(" ")
+}
+
+/*
+ * The microprogram of the machine: the fetch sequence shared by all instructions and one
+ * sequence of control words per instruction. Can be modified for microprogramming exercises.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ControlStore {
+    fetch: Vec<u32>,
+    // Microprograms indexed by opcode
+    programs: BTreeMap<usize, Vec<u32>>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ControlStore {
+    pub fn new() -> ControlStore {
+        let programs = (0..=u8::MAX as usize)
+            .filter_map(Instruction::from_opcode)
+            .filter_map(|instruction| Some((instruction.to_opcode(), default_microprogram(instruction)?)))
+            .collect();
+        ControlStore {
+            fetch: FETCH.to_vec(),
+            programs,
+        }
+    }
+    pub fn get_fetch(&self) -> Vec<u32> {
+        self.fetch.to_owned()
+    }
+    // The fetch sequence can't be empty, otherwise no instruction would ever be loaded.
+    pub fn set_fetch(&mut self, program: Vec<u32>) -> bool {
+        if program.is_empty() {
+            return false;
+        }
+        self.fetch = program;
+        true
+    }
+    pub fn get_microprogram(&self, instruction: Instruction) -> Option<Vec<u32>> {
+        self.programs.get(&instruction.to_opcode()).cloned()
+    }
+    pub fn set_microprogram(&mut self, instruction: Instruction, program: Vec<u32>) {
+        self.programs.insert(instruction.to_opcode(), program);
+    }
+    // Without microprogram the instruction is executed in a single cycle.
+    pub fn remove_microprogram(&mut self, instruction: Instruction) {
+        self.programs.remove(&instruction.to_opcode());
+    }
+    // Renders every control word with its active lines, one per row.
+    pub fn to_table(&self) -> String {
+        let programs = self.programs.iter().map(|(opcode, program)| {
+            (Instruction::from_opcode(*opcode).unwrap().mnemonic(), program)
+        });
+        std::iter::once(("FETCH", &self.fetch))
+            .chain(programs)
+            .flat_map(|(name, program)| {
+                program
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, control)| format!("{name:<5} {index:>2}  {control:07X}  {}", describe(*control)))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl ControlStore {
+    pub fn program(&self, instruction: Instruction) -> Option<&[u32]> {
+        self.programs.get(&instruction.to_opcode()).map(|program| program.as_slice())
+    }
+    pub fn fetch(&self) -> &[u32] {
+        &self.fetch
+    }
+}
+
+impl Default for ControlStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
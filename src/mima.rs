@@ -1,159 +1,2502 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-use crate::compiler::CompilerOutput;
+use crate::compiler::{CompilerOutput, SourceMapping, Symbol, SymbolKind};
+use crate::error::MimaError;
+use crate::locale;
+use crate::devices::{
+    Console, Device, Framebuffer, InputEvent, InputLog, Keyboard, MappedDevice, Random, Screen, ScreenUpdate, Timer,
+};
+use crate::microcode::{self, ControlStore, MicroDebug, MicroPhase, MicroState, MicroTrace};
+use crate::parser::{parse_watch, BinaryOperator, Expression, Node};
 
-const MEMORY_SIZE: usize = 1048576;
-const VALUE_SIZE: usize = 16777216;
-const MINUS_ONE: usize = 0b100000000000000000000000;
+pub(crate) const MEMORY_SIZE: usize = 1048576;
+pub(crate) const VALUE_SIZE: usize = 16777216;
+const WORD_BITS: u32 = 24;
+// The sum of two words has to fit into a usize, which only has 32 bits on wasm32.
+pub(crate) const MAX_WORD_BITS: u32 = u32::BITS - 1;
+const WORD_MASK: usize = VALUE_SIZE - 1;
+const SIGN_BIT: usize = 0b100000000000000000000000;
+// Operand field of an instruction with a 4 bit opcode
+const OPERAND_MASK: usize = 0xFFFFF;
+// Instructions between two checks of the cancellation handle
+const CANCELLATION_INTERVAL: usize = 256;
+// Entries of the decode cache, must be a power of two
+const DECODE_CACHE_SIZE: usize = 1024;
+// Longer pauses between ticks, e.g. while the tab was in the background, don't have to be caught up.
+const MAX_TICK_MS: f64 = 1000.0;
 
-#[wasm_bindgen]
+/*
+ * Brent's cycle detection over the machine state: the saved state is compared with every later
+ * one and replaced after `power` steps, with power doubling each time.
+ */
+struct LoopCheck {
+    state: (usize, usize, usize, usize, u64),
+    power: u64,
+    length: u64,
+    // Adresses of the instructions executed since the state was saved
+    low: usize,
+    high: usize,
+}
+
+// Callbacks for machine events, see set_memory_write_hook, set_halt_hook and set_step_hook
+pub type MemoryWriteHook = Box<dyn FnMut(usize, usize)>;
+pub type HaltHook = Box<dyn FnMut(HaltReason)>;
+pub type StepHook = Box<dyn FnMut(&StepResult)>;
+
+/*
+ * Gets notified of machine events, e.g. to collect statistics in an autograder. Steps and writes
+ * are throttled during run like the hooks, see set_run_callback_interval.
+ */
+pub trait Observer {
+    fn on_step(&mut self, _result: &StepResult) {}
+    // Called with the adress and the new value before the write reaches memory or a device.
+    fn on_memory_write(&mut self, _adress: usize, _value: usize) {}
+    fn on_halt(&mut self, _reason: HaltReason) {}
+}
+
+// The hooks set by the frontend, an observer like any other
+#[derive(Default)]
+struct Hooks {
+    memory_write: Option<MemoryWriteHook>,
+    halt: Option<HaltHook>,
+    step: Option<StepHook>,
+}
+
+impl Observer for Hooks {
+    fn on_step(&mut self, result: &StepResult) {
+        if let Some(callback) = &mut self.step {
+            callback(result);
+        }
+    }
+    fn on_memory_write(&mut self, adress: usize, value: usize) {
+        if let Some(callback) = &mut self.memory_write {
+            callback(adress, value);
+        }
+    }
+    fn on_halt(&mut self, reason: HaltReason) {
+        if let Some(callback) = &mut self.halt {
+            callback(reason);
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Mima {
+    // Number of memory words, a power of two
+    memory_size: usize,
+    word_bits: u32,
+    word_mask: usize,
     akku: usize,
     iar: usize,
+    // Stack pointer of the MIMA-X extension, the stack grows downwards from the end of memory
+    sp: usize,
+    // Frame pointer of the MIMA-X extension
+    fp: usize,
     halt: bool,
+    halt_reason: HaltReason,
     memory: Vec<usize>,
+    // XOR of the hashes of all non-zero words, so states can be compared without the whole memory
+    memory_hash: u64,
+    loop_check: Option<LoopCheck>,
+    loop_detection: bool,
+    // Whether a memory word was loaded or written, for the read check
+    initialized: Vec<bool>,
+    read_check: ReadCheck,
+    uninitialized_reads: Vec<UninitializedRead>,
+    // Adresses written since the last call to take_dirty_addresses
+    dirty: BTreeSet<usize>,
+    // Symbol table of the loaded program
+    symbols: Vec<Symbol>,
+    // Source map of the loaded program, used to keep the position in hot_reload
+    source_map: Vec<SourceMapping>,
+    // Instructions of the loaded program, the program can't write to them unless self_modifying is set
+    protected: Vec<Range<usize>>,
+    self_modifying: bool,
+    // Instructions outside of the profile trap instead of executing
+    profile: MachineProfile,
+    // The last executed instructions, only recorded if trace_capacity isn't zero
+    trace: VecDeque<TraceEntry>,
+    trace_capacity: usize,
+    // Executions per adress, only counted while profiling
+    profiling: bool,
+    hits: BTreeMap<usize, u64>,
+    // Adress of the last memory write, reported by step
+    last_write: Option<usize>,
+    // Expressions reported with every step, see add_watch
+    watches: Vec<Watch>,
+    // Datapath state while executing microinstructions
+    micro: MicroState,
+    control_store: ControlStore,
+    // Cycles of every opcode under the control store, looked up for each executed instruction
+    cycles: Vec<u64>,
+    // Memory-mapped devices, accesses to their adresses don't reach RAM
+    devices: Vec<MappedDevice>,
+    // Shared with the console and keyboard devices
+    console_output: Rc<RefCell<String>>,
+    keyboard_input: Rc<RefCell<VecDeque<usize>>>,
+    // Shared with the screen, empty without one
+    framebuffer: Rc<RefCell<Framebuffer>>,
+    // Frontend callbacks for machine events
+    hooks: Hooks,
+    // Indexed by the id returned from add_observer, removed ones leave a gap.
+    observers: Vec<Option<Box<dyn Observer>>>,
+    // During run only every n-th step is reported, zero disables step and write callbacks.
+    run_callback_interval: usize,
+    // Set while run skips the callbacks of the current step
+    quiet: bool,
+    cancellation: Option<CancellationHandle>,
+    // Adress to continue at after the running interrupt handler, None outside of handlers
+    interrupt_return: Option<usize>,
+    // Handler of an interrupt requested while another one was handled
+    pending_interrupt: Option<usize>,
+    // Instructions executed since the program was loaded
+    instruction_count: u64,
+    // Clock cycles these instructions take on the microarchitecture
+    cycle_count: u64,
+    // Instructions left before the machine stops, None for no limit
+    fuel: Option<u64>,
+    // Values read from devices, replaying answers device reads from the log instead
+    input_log: Vec<InputEvent>,
+    replaying: Option<VecDeque<InputEvent>>,
+    // Recently decoded instruction words, so loops don't decode the same words over and over
+    decode_cache: Vec<Option<(usize, Option<Command>)>>,
+    // Instructions per second for tick and run_realtime
+    clock_rate: f64,
+    // Fraction of an instruction left over from the last tick
+    clock_budget: f64,
 }
 
-#[wasm_bindgen]
+/*
+ * Lets the frontend stop run and run_async, e.g. from a stop button. Every call to cancel stops
+ * one run, the machine doesn't halt and can be continued.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, Default)]
+pub struct CancellationHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CancellationHandle {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> CancellationHandle {
+        CancellationHandle::default()
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+    // Withdraws a cancellation that no run has picked up yet.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+}
+
+impl CancellationHandle {
+    // Consumes a pending cancellation.
+    fn take(&self) -> bool {
+        self.cancelled.swap(false, Ordering::Relaxed)
+    }
+}
+
+/*
+ * The instruction sets taught in different courses. Basic is the MIMA from the lecture,
+ * Extended adds ADC, JIND and RTI and MimaX additionally has the stack instructions.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum MachineProfile {
+    Basic,
+    Extended,
+    #[default]
+    MimaX,
+}
+
+impl MachineProfile {
+    pub fn supports(self, instruction: Instruction) -> bool {
+        match instruction {
+            Instruction::ADC | Instruction::JIND | Instruction::RTI => self != MachineProfile::Basic,
+            Instruction::CALL
+            | Instruction::RET
+            | Instruction::LDSP
+            | Instruction::STSP
+            | Instruction::LDFP
+            | Instruction::STFP
+            | Instruction::LDRS
+            | Instruction::STRS => self == MachineProfile::MimaX,
+            _ => true,
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct MimaDebug {
     pub akku: usize,
+    // The akku interpreted as a two's complement number
+    pub akku_signed: i32,
     pub iar: usize,
+    pub sp: usize,
+    pub fp: usize,
     pub halt: bool,
+    pub halt_reason: HaltReason,
+    // The instruction at IAR like "LDV 5", None if the word isn't an instruction
+    instruction: Option<String>,
+    // Adress of the memory cell the instruction at IAR accesses and its current content
+    pub effective_adress: Option<usize>,
+    pub effective_value: Option<usize>,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl MimaDebug {
+    pub fn get_instruction(&self) -> Option<String> {
+        self.instruction.to_owned()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    adress: usize,
+    command: Command,
+    akku_before: usize,
+    akku_after: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TraceEntry {
+    pub fn get_adress(&self) -> usize {
+        self.adress
+    }
+    pub fn get_command(&self) -> Command {
+        self.command.to_owned()
+    }
+    pub fn get_akku_before(&self) -> usize {
+        self.akku_before
+    }
+    pub fn get_akku_after(&self) -> usize {
+        self.akku_after
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileEntry {
+    adress: usize,
+    count: u64,
+    location: Option<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ProfileEntry {
+    pub fn get_adress(&self) -> usize {
+        self.adress
+    }
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+    // Position relative to the closest preceding label
+    pub fn get_location(&self) -> Option<String> {
+        self.location.to_owned()
+    }
+}
+
+// A subroutine call that hasn't returned yet
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackFrame {
+    call_site: usize,
+    target: usize,
+    // Where the return adress is stored on the stack
+    stack_adress: usize,
+    location: Option<String>,
+    function: Option<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl StackFrame {
+    // Adress of the CALL instruction
+    pub fn get_call_site(&self) -> usize {
+        self.call_site
+    }
+    pub fn get_return_adress(&self) -> usize {
+        self.call_site + 1
+    }
+    pub fn get_target(&self) -> usize {
+        self.target
+    }
+    pub fn get_stack_adress(&self) -> usize {
+        self.stack_adress
+    }
+    // The call site relative to the closest preceding label
+    pub fn get_location(&self) -> Option<String> {
+        self.location.to_owned()
+    }
+    // Name of the label that was called
+    pub fn get_function(&self) -> Option<String> {
+        self.function.to_owned()
+    }
+}
+
+// A register with different values in two machines
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegisterDifference {
+    name: String,
+    pub left: usize,
+    pub right: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl RegisterDifference {
+    // "AKKU", "IAR", "SP" or "FP"
+    pub fn get_name(&self) -> String {
+        self.name.to_owned()
+    }
+}
+
+// A memory cell with different values in two machines
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellDifference {
+    pub adress: usize,
+    pub left: usize,
+    pub right: usize,
+    // The adress relative to the closest variable or label of the left machine's program
+    location: Option<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CellDifference {
+    pub fn get_location(&self) -> Option<String> {
+        self.location.to_owned()
+    }
+}
+
+// Everything that differs between two machine states, see Mima::diff
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MachineDiff {
+    registers: Vec<RegisterDifference>,
+    cells: Vec<CellDifference>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl MachineDiff {
+    pub fn get_registers(&self) -> Vec<RegisterDifference> {
+        self.registers.to_owned()
+    }
+    // Differing memory cells in ascending order
+    pub fn get_cells(&self) -> Vec<CellDifference> {
+        self.cells.to_owned()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.cells.is_empty()
+    }
+}
+
+// What happened during a call to step
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepResult {
+    command: Option<Command>,
+    adress: usize,
+    written: Option<usize>,
+    halt: bool,
+    halt_reason: HaltReason,
+    watches: Vec<WatchValue>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl StepResult {
+    // The executed instruction, None if nothing could be executed
+    pub fn get_command(&self) -> Option<Command> {
+        self.command.to_owned()
+    }
+    pub fn get_adress(&self) -> usize {
+        self.adress
+    }
+    // Adress of the memory cell the instruction wrote to
+    pub fn get_written_adress(&self) -> Option<usize> {
+        self.written
+    }
+    pub fn is_halt(&self) -> bool {
+        self.halt
+    }
+    pub fn get_halt_reason(&self) -> HaltReason {
+        self.halt_reason
+    }
+    // The watch expressions after the step, in the order they were added
+    pub fn get_watches(&self) -> Vec<WatchValue> {
+        self.watches.to_owned()
+    }
+}
+
+// An expression added with add_watch
+#[derive(Clone, Debug)]
+struct Watch {
+    text: String,
+    expression: Expression,
+}
+
+// The current value of a watch expression, or why it couldn't be evaluated
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchValue {
+    expression: String,
+    pub value: Option<i64>,
+    error: Option<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl WatchValue {
+    pub fn get_expression(&self) -> String {
+        self.expression.to_owned()
+    }
+    pub fn get_error(&self) -> Option<String> {
+        self.error.to_owned()
+    }
+}
+
+// A memory cell written during step_n and the value it holds afterwards
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MemoryWrite {
+    pub adress: usize,
+    pub value: usize,
+}
+
+/*
+ * What happened during a call to step_n: the registers after the last instruction and every cell
+ * that was written, each listed once in ascending order.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchResult {
+    // Instructions executed by this batch
+    pub steps: usize,
+    pub akku: usize,
+    pub iar: usize,
+    pub sp: usize,
+    pub fp: usize,
+    writes: Vec<MemoryWrite>,
+    pub halt: bool,
+    pub halt_reason: HaltReason,
+    watches: Vec<WatchValue>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl BatchResult {
+    pub fn get_writes(&self) -> Vec<MemoryWrite> {
+        self.writes.to_owned()
+    }
+    // The watch expressions after the last instruction of the batch
+    pub fn get_watches(&self) -> Vec<WatchValue> {
+        self.watches.to_owned()
+    }
+}
+
+// Outcome of run_async and the debugger operations
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RunResult {
+    pub halt_reason: HaltReason,
+    // Instructions executed by this run
+    pub steps: usize,
+    // The run was cancelled before the machine halted or reached its target
+    pub cancelled: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HaltKind {
+    // The machine is still running or hasn't been started
+    NotStarted,
+    // A HALT instruction was executed
+    Halted,
+    IllegalInstruction,
+    AdressOutOfRange,
+    // The fuel set with set_fuel is used up
+    OutOfFuel,
+    // The program wrote to its own instructions
+    WriteProtected,
+    // The program read memory that was never written, only with ReadCheck::Trap
+    UninitializedRead,
+    // The machine reached the same state twice, see set_loop_detection
+    InfiniteLoop,
+}
+
+/*
+ * Why the machine stopped. For illegal instructions adress and word are the location and content
+ * of the instruction, for infinite loops adress and end are the first and last adress of the
+ * loop, for the other errors adress is the adress that was accessed (if any).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HaltReason {
+    pub kind: HaltKind,
+    pub adress: Option<usize>,
+    pub word: Option<usize>,
+    pub end: Option<usize>,
+}
+
+impl HaltReason {
+    pub fn not_started() -> HaltReason {
+        HaltReason { kind: HaltKind::NotStarted, adress: None, word: None, end: None }
+    }
+    pub fn halted() -> HaltReason {
+        HaltReason { kind: HaltKind::Halted, adress: None, word: None, end: None }
+    }
+    pub fn illegal_instruction(adress: usize, word: usize) -> HaltReason {
+        HaltReason { kind: HaltKind::IllegalInstruction, adress: Some(adress), word: Some(word), end: None }
+    }
+    pub fn adress_out_of_range(adress: Option<usize>) -> HaltReason {
+        HaltReason { kind: HaltKind::AdressOutOfRange, adress, word: None, end: None }
+    }
+    pub fn out_of_fuel() -> HaltReason {
+        HaltReason { kind: HaltKind::OutOfFuel, adress: None, word: None, end: None }
+    }
+    pub fn write_protected(adress: usize) -> HaltReason {
+        HaltReason { kind: HaltKind::WriteProtected, adress: Some(adress), word: None, end: None }
+    }
+    pub fn uninitialized_read(adress: usize) -> HaltReason {
+        HaltReason { kind: HaltKind::UninitializedRead, adress: Some(adress), word: None, end: None }
+    }
+    pub fn infinite_loop(start: usize, end: usize) -> HaltReason {
+        HaltReason { kind: HaltKind::InfiniteLoop, adress: Some(start), word: None, end: Some(end) }
+    }
+    // The reason as an error, None if the program halted normally or is still running
+    pub fn to_error(&self) -> Option<MimaError> {
+        match self.kind {
+            HaltKind::NotStarted | HaltKind::Halted => None,
+            _ => Some(MimaError::new(self.kind.code(), self.get_message())),
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl HaltReason {
+    // Stable name of the kind for frontends, like the codes of MimaError
+    pub fn get_code(&self) -> String {
+        self.kind.code().to_string()
+    }
+    pub fn get_message(&self) -> String {
+        let (adress, word, end) = (self.adress.unwrap_or_default(), self.word.unwrap_or_default(), self.end.unwrap_or_default());
+        let (english, german) = match self.kind {
+            HaltKind::NotStarted => ("The machine hasn't stopped.".to_string(), "Die Maschine hat nicht angehalten.".to_string()),
+            HaltKind::Halted => ("The program halted.".to_string(), "Das Programm hat angehalten.".to_string()),
+            HaltKind::IllegalInstruction => {
+                (format!("Illegal instruction {word:06X} at adress {adress}."), format!("Ungültiger Befehl {word:06X} an Adresse {adress}."))
+            }
+            HaltKind::AdressOutOfRange if self.adress.is_none() => {
+                ("An adress is out of range.".to_string(), "Eine Adresse liegt außerhalb des Speichers.".to_string())
+            }
+            HaltKind::AdressOutOfRange => (format!("Adress {adress} is out of range."), format!("Adresse {adress} liegt außerhalb des Speichers.")),
+            HaltKind::OutOfFuel => ("The machine ran out of fuel.".to_string(), "Die Maschine hat ihr Schrittlimit aufgebraucht.".to_string()),
+            HaltKind::WriteProtected => {
+                (format!("The program wrote to its code at adress {adress}."), format!("Das Programm hat an Adresse {adress} in seinen Code geschrieben."))
+            }
+            HaltKind::UninitializedRead => {
+                (format!("Adress {adress} was read before it was written."), format!("Adresse {adress} wurde gelesen, bevor sie geschrieben wurde."))
+            }
+            HaltKind::InfiniteLoop => (
+                format!("The program loops forever between adress {adress} and {end}."),
+                format!("Das Programm läuft zwischen Adresse {adress} und {end} endlos im Kreis."),
+            ),
+        };
+        locale::localize(english, german)
+    }
+}
+
+impl HaltKind {
+    fn code(self) -> &'static str {
+        match self {
+            HaltKind::NotStarted => "NOT_STARTED",
+            HaltKind::Halted => "HALTED",
+            HaltKind::IllegalInstruction => "ILLEGAL_INSTRUCTION",
+            HaltKind::AdressOutOfRange => "ADDRESS_OOB",
+            HaltKind::OutOfFuel => "OUT_OF_FUEL",
+            HaltKind::WriteProtected => "WRITE_PROTECTED",
+            HaltKind::UninitializedRead => "UNINITIALIZED_READ",
+            HaltKind::InfiniteLoop => "INFINITE_LOOP",
+        }
+    }
+}
+
+/*
+ * What happens when the program reads memory that was neither loaded nor written before.
+ * Variables declared without a value like "counter: DS" count as not written.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum ReadCheck {
+    #[default]
+    Off,
+    // The read is recorded, see take_uninitialized_reads
+    Warn,
+    // The machine stops with UninitializedRead
+    Trap,
+}
+
+// A read of memory that was never written
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UninitializedRead {
+    pub adress: usize,
+    // Adress of the reading instruction
+    pub instruction: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Mima {
     pub fn reset(&mut self) {
+        self.reset_registers();
+        // Every cell that gets cleared counts as modified for the frontend.
+        for (adress, value) in self.memory.iter().enumerate() {
+            if *value != 0 {
+                self.dirty.insert(adress);
+            }
+        }
+        self.memory = vec![0; self.memory_size];
+        self.memory_hash = 0;
+        self.initialized = vec![false; self.memory_size];
+    }
+    // Everything reset does except clearing memory
+    fn reset_registers(&mut self) {
         self.akku = 0;
         self.iar = 0;
+        self.sp = self.memory_size;
+        self.fp = self.memory_size;
         self.halt = false;
-        self.memory = vec![0; MEMORY_SIZE];
+        self.halt_reason = HaltReason::not_started();
+        self.loop_check = None;
+        self.uninitialized_reads.clear();
+        self.symbols = vec![];
+        self.source_map = vec![];
+        self.protected = vec![];
+        self.micro = MicroState::default();
+        self.clock_budget = 0.0;
+        self.interrupt_return = None;
+        self.pending_interrupt = None;
+        self.instruction_count = 0;
+        self.cycle_count = 0;
+        self.input_log.clear();
+        self.trace.clear();
+        self.hits.clear();
     }
     pub fn write_adress(&mut self, adress: usize, value: usize) -> bool {
-        if adress >= MEMORY_SIZE || value >= VALUE_SIZE {
+        if adress >= self.memory_size || value > self.word_mask {
             false
         } else {
-            self.memory[adress] = value;
+            self.write_word(adress, value);
             true
         }
     }
 
-    pub fn read_adress(&mut self, adress: usize) -> Option<usize> {
-        if adress >= MEMORY_SIZE {
-            None
-        } else {
-            Some(self.memory[adress])
+    // Reads the value of a variable or label of the loaded program.
+    pub fn read_symbol(&mut self, name: &str) -> Option<usize> {
+        let adress = self.resolve_symbol(name)?;
+        self.read_adress(adress)
+    }
+
+    pub fn write_symbol(&mut self, name: &str, value: usize) -> bool {
+        match self.resolve_symbol(name) {
+            Some(adress) => self.write_adress(adress, value),
+            None => false,
         }
     }
 
+    // Returns all adresses modified since the last call in ascending order.
+    pub fn take_dirty_addresses(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.dirty).into_iter().collect()
+    }
+
+    pub fn read_adress(&mut self, adress: usize) -> Option<usize> {
+        self.peek(adress)
+    }
+
     pub fn memdump(&mut self) -> Vec<usize> {
         self.memory.to_owned()
     }
 
-    pub fn step(&mut self) {
-        if self.halt {
-            return;
+    /*
+     * Adds an expression that is evaluated after every step and returned with its result, e.g.
+     * "counter", "mem[tbl+3]" or "akku - max". Variables stand for their value and labels for
+     * their adress, inside "mem[...]" variables stand for their adress like in operands. AKKU,
+     * IAR, SP and FP are the registers. Words are read as two's complement numbers. Returns the
+     * index of the watch.
+     */
+    pub fn add_watch(&mut self, expression: &str) -> Result<usize, MimaError> {
+        let parsed = parse_watch(expression)?;
+        self.watches.push(Watch { text: expression.trim().to_string(), expression: parsed });
+        Ok(self.watches.len() - 1)
+    }
+    // The following watches move down by one.
+    pub fn remove_watch(&mut self, index: usize) -> bool {
+        if index >= self.watches.len() {
+            return false;
         }
-        let command = Command::from_usize(self.memory[self.iar]);
-        if command.is_none() || command.as_ref().unwrap().value >= MEMORY_SIZE {
-            self.halt = true;
-            return;
+        self.watches.remove(index);
+        true
+    }
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
+    // Evaluates the watch expressions now.
+    pub fn get_watches(&self) -> Vec<WatchValue> {
+        self.watches
+            .iter()
+            .map(|watch| {
+                let result = self.evaluate_watch(&watch.expression, false);
+                WatchValue { expression: watch.text.to_owned(), value: result.as_ref().ok().copied(), error: result.err() }
+            })
+            .collect()
+    }
+
+    pub fn step(&mut self) -> StepResult {
+        let result = self.step_instruction();
+        if !self.quiet {
+            self.notify(&mut |observer| observer.on_step(&result));
         }
-        let command = command.unwrap();
-        let mut next_instruction = self.iar + 1;
-        match command.instruction {
-            Instruction::LDC => self.akku = command.value,
-            Instruction::LDV => self.akku = self.memory[command.value],
-            Instruction::STV => self.memory[command.value] = self.akku,
-            // TODO: Overflow checking
-            Instruction::ADD => self.akku += self.memory[command.value],
-            Instruction::AND => self.akku &= self.memory[command.value],
-            Instruction::OR => self.akku |= self.memory[command.value],
-            Instruction::XOR => self.akku ^= self.memory[command.value],
-            Instruction::EQL => {
-                self.akku = if self.akku == self.memory[command.value] {
-                    MINUS_ONE
-                } else {
-                    0
+        result
+    }
+
+    /*
+     * Advances the machine by a single clock cycle of the microarchitecture and returns true once
+     * the current instruction is completed. Like in hardware the IAR is incremented during fetch.
+     */
+    pub fn micro_step(&mut self) -> bool {
+        if self.halt {
+            return false;
+        }
+        match self.micro.phase {
+            MicroPhase::Fetch => {
+                // Remembered to start the fetch over if the control store is replaced.
+                if self.micro.position == 0 {
+                    self.micro.adress = self.iar;
                 }
+                let fetch = self.control_store.fetch();
+                let (control, length) = (fetch[self.micro.position], fetch.len());
+                self.cycle(control);
+                self.micro.position += 1;
+                if self.micro.position == length {
+                    self.micro.phase = MicroPhase::Decode;
+                    self.micro.position = 0;
+                }
+                false
             }
-            Instruction::JMP => next_instruction = command.value,
-            Instruction::JMN => {
-                if self.akku >= MINUS_ONE {
-                    next_instruction = command.value
+            MicroPhase::Decode => {
+                self.cycle(0);
+                // SAR still holds the adress of the instruction from the fetch phase.
+                let adress = self.micro.sar;
+                self.micro.adress = adress;
+                let command = match self.decode(self.micro.ir) {
+                    Some(command) => command,
+                    None => {
+                        self.iar = adress;
+                        self.stop(HaltReason::illegal_instruction(adress, self.micro.ir));
+                        self.micro.phase = MicroPhase::Fetch;
+                        return true;
+                    }
+                };
+                let program = match command.instruction {
+                    Instruction::JMN if self.akku & self.sign_bit() == 0 => Some(&[][..]),
+                    instruction => self.control_store.program(instruction),
+                };
+                match program {
+                    Some([]) => self.micro.phase = MicroPhase::Fetch,
+                    Some(_) => {
+                        self.micro.phase = MicroPhase::Execute;
+                        self.micro.instruction = Some(command.instruction);
+                    }
+                    // Instructions without microcode are executed in a single cycle.
+                    None => {
+                        self.iar = adress;
+                        self.execute(command);
+                        self.micro.phase = MicroPhase::Fetch;
+                    }
                 }
+                self.micro.phase == MicroPhase::Fetch
+            }
+            MicroPhase::Execute => {
+                // The microprogram may have been replaced in the middle of the instruction.
+                let program = self.micro.instruction.and_then(|instruction| self.control_store.program(instruction)).unwrap_or_default();
+                let (control, length) = (program.get(self.micro.position).copied(), program.len());
+                if let Some(control) = control {
+                    self.cycle(control);
+                    self.micro.position += 1;
+                }
+                if self.micro.position < length {
+                    return false;
+                }
+                self.micro.phase = MicroPhase::Fetch;
+                self.micro.position = 0;
+                self.micro.instruction = None;
+                true
             }
-            Instruction::LDIV => self.akku = self.memory[self.memory[command.value]],
-            Instruction::STIV => {
-                let adress = self.memory[command.value];
-                self.memory[adress] = self.akku;
-            },
-            Instruction::HALT => self.halt = true,
-            Instruction::NOT => self.akku = !self.akku,
-            Instruction::RAR => self.akku = self.akku.rotate_right(1),
-        }
-        if !self.halt {
-            self.iar = next_instruction;
         }
     }
-    pub fn run(&mut self) {
-        while !self.halt {
-            self.step();
+    /*
+     * Records the last `capacity` instructions executed by step and run. A capacity of zero
+     * disables tracing and clears the trace.
+     */
+    pub fn set_trace_capacity(&mut self, capacity: usize) {
+        self.trace_capacity = capacity;
+        while self.trace.len() > capacity {
+            self.trace.pop_front();
         }
     }
-    pub fn new() -> Mima {
-        Mima {
-            akku: 0,
-            iar: 0,
-            halt: false,
-            memory: vec![0; MEMORY_SIZE],
-        }
+    // The recorded instructions, oldest first
+    pub fn get_trace(&self) -> Vec<TraceEntry> {
+        self.trace.iter().cloned().collect()
     }
-    pub fn load(&mut self, program: CompilerOutput) -> bool {
-        self.reset();
-        let code = program.get_mima_code();
-        if code.len() >= MEMORY_SIZE {
-            return false;
-        }
-        for i in 0..code.len() {
-            self.memory[i] = code[i];
+    // Enabling or disabling profiling keeps the counts, they are cleared by reset.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+    }
+    // Execution counts per adress, the most executed adresses first
+    pub fn get_profile(&self) -> Vec<ProfileEntry> {
+        let mut profile: Vec<ProfileEntry> = self
+            .hits
+            .iter()
+            .map(|(adress, count)| ProfileEntry {
+                adress: *adress,
+                count: *count,
+                location: self.location(*adress),
+            })
+            .collect();
+        profile.sort_by(|a, b| b.count.cmp(&a.count).then(a.adress.cmp(&b.adress)));
+        profile
+    }
+    pub fn get_profile_report(&self) -> String {
+        let header = format!("{:<5}  {:>10}  LOCATION", "ADDR", "COUNT");
+        let lines = self
+            .get_profile()
+            .into_iter()
+            .map(|entry| format!("{:05X}  {:>10}  {}", entry.adress, entry.count, entry.location.unwrap_or_default()));
+        std::iter::once(header).chain(lines).collect::<Vec<String>>().join("\n")
+    }
+    pub fn get_micro_debug(&self) -> MicroDebug {
+        self.micro.to_debug()
+    }
+    pub fn get_control_store(&self) -> ControlStore {
+        self.control_store.to_owned()
+    }
+    /*
+     * Programs keep running under the new microprogram, memory and registers are not touched. A
+     * fetch in progress starts over, the IAR may already have been incremented by the old one.
+     */
+    pub fn set_control_store(&mut self, control_store: ControlStore) {
+        if self.micro.phase == MicroPhase::Fetch && self.micro.position > 0 {
+            self.iar = self.micro.adress;
+            self.micro.position = 0;
         }
-        self.iar = program.get_start_adress();
-        true
+        self.cycles = cycle_table(&control_store);
+        self.control_store = control_store;
     }
-    pub fn get_debug(&self) -> MimaDebug {
-        MimaDebug {
+    // Bus and register activity of the last micro step
+    pub fn get_micro_trace(&self) -> Option<MicroTrace> {
+        self.micro.trace.to_owned()
+    }
+    /*
+     * Executes up to n instructions like n calls to step, but reports them in a single result so
+     * animations in JS don't have to cross into wasm for every instruction.
+     */
+    pub fn step_n(&mut self, n: usize) -> BatchResult {
+        let mut steps = 0;
+        let mut written = BTreeSet::new();
+        while steps < n && !self.halt {
+            let result = self.step();
+            if result.get_command().is_none() {
+                break;
+            }
+            steps += 1;
+            written.extend(result.get_written_adress());
+        }
+        let writes = written
+            .into_iter()
+            .filter_map(|adress| self.peek(adress).map(|value| MemoryWrite { adress, value }))
+            .collect();
+        BatchResult {
+            steps,
             akku: self.akku,
             iar: self.iar,
+            sp: self.sp,
+            fp: self.fp,
+            writes,
             halt: self.halt,
+            halt_reason: self.halt_reason,
+            watches: self.get_watches(),
         }
     }
-    pub fn get_next_instruction(&self) -> Option<Command> {
-        Command::from_usize(self.memory[self.iar])
+    // Runs until the machine halts or the cancellation handle is triggered.
+    pub fn run(&mut self) -> HaltReason {
+        self.run_chunk(usize::MAX);
+        self.halt_reason
+    }
+    /*
+     * Executes the next instruction. A CALL is executed up to the point where the subroutine
+     * returns, recursive calls included.
+     */
+    pub fn step_over(&mut self) -> RunResult {
+        match self.get_next_instruction() {
+            Some(Command { instruction: Instruction::CALL, .. }) if !self.halt => {
+                let (return_adress, sp) = (self.iar + 1, self.sp);
+                self.step();
+                let (steps, cancelled) =
+                    self.run_while(usize::MAX, &|mima: &Mima| mima.iar != return_adress || mima.sp < sp);
+                RunResult { halt_reason: self.halt_reason, steps: steps + 1, cancelled }
+            }
+            _ => {
+                let steps = usize::from(self.step().get_command().is_some());
+                RunResult { halt_reason: self.halt_reason, steps, cancelled: false }
+            }
+        }
+    }
+    /*
+     * Runs until the instruction at `adress` is about to be executed, like a breakpoint that is
+     * removed once it's hit. At least one instruction is executed.
+     */
+    pub fn run_to(&mut self, adress: usize) -> RunResult {
+        let first = self.step().get_command().is_some();
+        let (steps, cancelled) = self.run_while(usize::MAX, &|mima: &Mima| mima.iar != adress);
+        RunResult { halt_reason: self.halt_reason, steps: steps + usize::from(first), cancelled }
+    }
+    /*
+     * Runs a single routine of the loaded program, e.g. to test it on its own. Execution starts at
+     * the label and stops before the RET that leaves the routine, when the machine halts or after
+     * max_steps instructions. Memory and registers are kept, so arguments can be set up before
+     * and results read afterwards. Returns None if the program has no such label.
+     */
+    pub fn call(&mut self, symbol: &str, max_steps: usize) -> Option<RunResult> {
+        let adress = self
+            .symbols
+            .iter()
+            .find(|label| label.get_kind() == SymbolKind::Label && label.get_name() == symbol)?
+            .get_adress();
+        self.clear_halt();
+        self.set_iar(adress);
+        let sp = self.sp;
+        let (steps, cancelled) = self.run_while(max_steps, &|mima: &Mima| {
+            mima.sp < sp || !matches!(mima.get_next_instruction(), Some(Command { instruction: Instruction::RET, .. }))
+        });
+        Some(RunResult { halt_reason: self.halt_reason, steps, cancelled })
+    }
+    /*
+     * The calls that haven't returned yet, innermost first. Every word on the stack that points
+     * behind a CALL instruction is taken as a return adress, so arguments that happen to look
+     * like one show up as well.
+     */
+    pub fn get_call_stack(&self) -> Vec<StackFrame> {
+        (self.sp..self.memory_size)
+            .filter_map(|stack_adress| {
+                let call_site = self.memory[stack_adress].checked_sub(1)?;
+                let command = self.memory.get(call_site).and_then(|word| Command::from_usize(*word))?;
+                (command.instruction == Instruction::CALL).then(|| StackFrame {
+                    call_site,
+                    target: command.value,
+                    stack_adress,
+                    location: self.location(call_site),
+                    function: self
+                        .symbols
+                        .iter()
+                        .find(|symbol| symbol.get_kind() == SymbolKind::Label && symbol.get_adress() == command.value)
+                        .map(|symbol| symbol.get_name()),
+                })
+            })
+            .collect()
+    }
+    /*
+     * Compares the registers and the RAM of two machines, e.g. after running a reference solution
+     * and a submission on the same input. Cells missing in the smaller memory count as zero,
+     * devices aren't compared.
+     */
+    pub fn diff(&self, other: &Mima) -> MachineDiff {
+        let registers = [
+            ("AKKU", self.akku, other.akku),
+            ("IAR", self.iar, other.iar),
+            ("SP", self.sp, other.sp),
+            ("FP", self.fp, other.fp),
+        ];
+        let registers = registers
+            .into_iter()
+            .filter(|(_, left, right)| left != right)
+            .map(|(name, left, right)| RegisterDifference { name: name.to_string(), left, right })
+            .collect();
+        let cells = (0..self.memory_size.max(other.memory_size))
+            .filter_map(|adress| {
+                let left = self.memory.get(adress).copied().unwrap_or(0);
+                let right = other.memory.get(adress).copied().unwrap_or(0);
+                (left != right).then(|| CellDifference { adress, left, right, location: self.location_of(adress, &[SymbolKind::Variable, SymbolKind::Label]) })
+            })
+            .collect();
+        MachineDiff { registers, cells }
+    }
+    /*
+     * Limits the instructions the machine executes from now on, over all calls to step, run and
+     * run_async and across loading programs. Once the fuel is used up the machine stops with
+     * OutOfFuel. None removes the limit.
+     */
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+    pub fn get_remaining_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+    /*
+     * Programs can't overwrite their own instructions, writing to them stops the machine with
+     * WriteProtected. Exercises on self-modifying code have to allow it explicitly.
+     */
+    pub fn set_self_modifying(&mut self, allowed: bool) {
+        self.self_modifying = allowed;
+    }
+    pub fn is_self_modifying(&self) -> bool {
+        self.self_modifying
+    }
+    /*
+     * Stops the machine with InfiniteLoop when it reaches a state it was in before, i.e. the same
+     * registers and memory. Only works without devices and for instructions executed with step or run.
+     */
+    pub fn set_loop_detection(&mut self, enabled: bool) {
+        self.loop_detection = enabled;
+        self.loop_check = None;
+    }
+    // Checks reads of memory that was never written, set it before loading the program.
+    pub fn set_read_check(&mut self, check: ReadCheck) {
+        self.read_check = check;
+    }
+    // Returns the uninitialized reads recorded with ReadCheck::Warn since the last call.
+    pub fn take_uninitialized_reads(&mut self) -> Vec<UninitializedRead> {
+        std::mem::take(&mut self.uninitialized_reads)
+    }
+    // Instructions executed since the program was loaded
+    pub fn get_instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+    /*
+     * Clock cycles the executed instructions take on the datapath of the lecture, following the
+     * microprograms of the control store.
+     */
+    pub fn get_cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+    // Seconds the executed instructions take at a clock of `clock_hz` cycles per second
+    pub fn get_simulated_time(&self, clock_hz: f64) -> f64 {
+        self.cycle_count as f64 / clock_hz
+    }
+    /*
+     * Cycles of an instruction: the fetch sequence, the decode cycle and its microprogram. JMN only
+     * runs its microprogram if it jumps, instructions without microcode execute while decoding.
+     */
+    pub fn get_instruction_cycles(&self, instruction: Instruction) -> usize {
+        self.cycles[instruction.to_opcode()] as usize
+    }
+    // Everything the program read from devices since it was loaded
+    pub fn get_input_log(&self) -> InputLog {
+        InputLog::new(self.input_log.to_owned())
+    }
+    /*
+     * Runs the loaded program again with the device input of an earlier run. Load the program
+     * first, the devices have to be attached at the same adresses but aren't read.
+     */
+    pub fn replay(&mut self, log: &InputLog) -> HaltReason {
+        self.replaying = Some(log.get_events().into());
+        let reason = self.run();
+        self.replaying = None;
+        reason
+    }
+    // The handle is shared with the frontend, cancelling it stops the current run.
+    pub fn set_cancellation_handle(&mut self, handle: Option<CancellationHandle>) {
+        self.cancellation = handle;
+    }
+    pub fn get_clock_rate(&self) -> f64 {
+        self.clock_rate
+    }
+    // Instructions per second, e.g. 10 for demonstrations. Rates that aren't positive are rejected.
+    pub fn set_clock_rate(&mut self, hz: f64) -> bool {
+        if !(hz > 0.0 && hz.is_finite()) {
+            return false;
+        }
+        self.clock_rate = hz;
+        true
+    }
+    /*
+     * Executes as many instructions as fit into the elapsed time at the clock rate and returns
+     * their number. Meant to be called from requestAnimationFrame, left over fractions of an
+     * instruction are carried over to the next tick.
+     */
+    pub fn tick(&mut self, elapsed_ms: f64) -> usize {
+        if self.halt || elapsed_ms.is_nan() || elapsed_ms <= 0.0 {
+            return 0;
+        }
+        self.clock_budget += elapsed_ms.min(MAX_TICK_MS) * self.clock_rate / 1000.0;
+        let steps = self.clock_budget.floor();
+        self.clock_budget -= steps;
+        let executed = self.run_chunk(steps as usize);
+        if self.halt {
+            self.clock_budget = 0.0;
+        }
+        executed
+    }
+    /*
+     * Throttles the step and memory write callbacks during run to every n-th step, zero disables
+     * them. The halt callback is always called.
+     */
+    pub fn set_run_callback_interval(&mut self, interval: usize) {
+        self.run_callback_interval = interval;
+    }
+    pub fn new() -> Mima {
+        Mima::with_profile(MachineProfile::default())
+    }
+    pub fn with_profile(profile: MachineProfile) -> Mima {
+        Mima::build(profile, MEMORY_SIZE, WORD_BITS)
+    }
+    /*
+     * A machine with `memory_words` words of `word_bits` bits each, e.g. 16 words that fit on a
+     * slide. Memory has to be a power of two up to 2^20 words, because that's what an operand can
+     * adress, and instructions need words of at least 24 bits.
+     */
+    pub fn with_config(memory_words: usize, word_bits: u32) -> Result<Mima, MimaError> {
+        if !memory_words.is_power_of_two() || memory_words > MEMORY_SIZE {
+            let message = locale::localize(
+                format!("Memory size {memory_words} isn't a power of two up to {MEMORY_SIZE}."),
+                format!("Speichergröße {memory_words} ist keine Zweierpotenz bis {MEMORY_SIZE}."),
+            );
+            return Err(MimaError::new("INVALID_CONFIG", message));
+        }
+        if !(WORD_BITS..=MAX_WORD_BITS).contains(&word_bits) {
+            let message = locale::localize(
+                format!("Word size {word_bits} isn't between {WORD_BITS} and {MAX_WORD_BITS} bits."),
+                format!("Wortbreite {word_bits} liegt nicht zwischen {WORD_BITS} und {MAX_WORD_BITS} Bits."),
+            );
+            return Err(MimaError::new("INVALID_CONFIG", message));
+        }
+        Ok(Mima::build(MachineProfile::default(), memory_words, word_bits))
+    }
+    pub fn get_memory_size(&self) -> usize {
+        self.memory_size
+    }
+    pub fn get_word_bits(&self) -> u32 {
+        self.word_bits
+    }
+    pub fn get_machine_profile(&self) -> MachineProfile {
+        self.profile
+    }
+    pub fn load(&mut self, program: CompilerOutput) -> bool {
+        self.reset();
+        let code = program.get_mima_code();
+        if code.len() >= self.memory_size {
+            return false;
+        }
+        for (adress, value) in code.into_iter().enumerate() {
+            self.store(adress, value);
+        }
+        self.iar = program.get_start_adress();
+        self.forget_uninitialized(&program, 0);
+        self.protected = code_ranges(&program, 0);
+        self.symbols = program.get_symbols();
+        self.source_map = program.get_source_map();
+        true
+    }
+    /*
+     * Like load, but memory isn't cleared first. Values written before, e.g. test inputs, stay
+     * in the variables the program reserves without initializing them ("x: DS"). All other words
+     * of the program are written as usual.
+     */
+    pub fn load_preserving_memory(&mut self, program: CompilerOutput) -> bool {
+        let code = program.get_mima_code();
+        if code.len() >= self.memory_size {
+            return false;
+        }
+        self.reset_registers();
+        let reserved: Vec<Range<usize>> = program
+            .get_symbols()
+            .iter()
+            .filter(|symbol| !symbol.is_initialized())
+            .map(|symbol| symbol.get_adress()..symbol.get_adress() + symbol.get_size())
+            .collect();
+        for (adress, value) in code.into_iter().enumerate() {
+            if !reserved.iter().any(|range| range.contains(&adress)) {
+                self.store(adress, value);
+            }
+        }
+        self.iar = program.get_start_adress();
+        self.protected = code_ranges(&program, 0);
+        self.symbols = program.get_symbols();
+        self.source_map = program.get_source_map();
+        true
+    }
+    /*
+     * Replaces the loaded program by a new version of it without restarting, e.g. after a line
+     * was edited while debugging. Only words that differ are written. Variables keep their
+     * current values if the new program still has a variable of the same name and size, wherever
+     * it is placed now. Registers and memory behind the program are kept.
+     *
+     * With keep_position the IAR moves to the word generated from the same source line it is on
+     * now, otherwise or if that line doesn't produce code anymore to the start of the new program.
+     * Returns the patched adresses, None if the program doesn't fit into memory.
+     */
+    pub fn hot_reload(&mut self, program: CompilerOutput, keep_position: bool) -> Option<Vec<usize>> {
+        let mut words: Vec<(usize, bool)> = program.get_mima_code().into_iter().map(|word| (word, true)).collect();
+        if words.len() >= self.memory_size {
+            return None;
+        }
+        let symbols = program.get_symbols();
+        for symbol in symbols.iter().filter(|symbol| symbol.get_kind() == SymbolKind::Variable) {
+            let range = symbol.get_adress()..symbol.get_adress() + symbol.get_size();
+            let previous = self.symbols.iter().find(|previous| {
+                previous.get_kind() == SymbolKind::Variable
+                    && previous.get_name() == symbol.get_name()
+                    && previous.get_size() == symbol.get_size()
+            });
+            match previous {
+                Some(previous) => {
+                    for (offset, adress) in range.enumerate() {
+                        let old = previous.get_adress() + offset;
+                        words[adress] = (self.memory[old], self.initialized[old]);
+                    }
+                }
+                None if !symbol.is_initialized() => range.for_each(|adress| words[adress].1 = false),
+                None => (),
+            }
+        }
+        let mut patched = vec![];
+        for (adress, (word, initialized)) in words.into_iter().enumerate() {
+            if self.memory[adress] != word {
+                self.store(adress, word);
+                patched.push(adress);
+            }
+            self.initialized[adress] = initialized;
+        }
+        let source_map = program.get_source_map();
+        let position = self
+            .source_map
+            .iter()
+            .position(|mapping| mapping.adress == self.iar)
+            .filter(|_| keep_position)
+            .and_then(|index| {
+                let mapping = self.source_map[index];
+                let same_line = |other: &&SourceMapping| other.line == mapping.line && other.macro_line == mapping.macro_line;
+                let offset = self.source_map[..index].iter().filter(same_line).count();
+                let mut matching = source_map.iter().filter(same_line);
+                let first = matching.clone().next()?.adress;
+                Some(matching.nth(offset).map_or(first, |mapping| mapping.adress))
+            });
+        self.protected = code_ranges(&program, 0);
+        self.symbols = symbols;
+        self.source_map = source_map;
+        self.clear_halt();
+        self.set_iar(position.unwrap_or(program.get_start_adress()));
+        Some(patched)
+    }
+    // Loads a memory image that starts at adress 0, e.g. one produced by another simulator.
+    pub fn load_raw(&mut self, words: Vec<usize>, start: usize) -> bool {
+        if words.len() > self.memory_size || start >= self.memory_size || words.iter().any(|word| *word > self.word_mask) {
+            return false;
+        }
+        self.reset();
+        for (adress, value) in words.into_iter().enumerate() {
+            self.store(adress, value);
+        }
+        self.iar = start;
+        true
+    }
+    /*
+     * Loads a program at the given base adress without clearing memory, so several programs can
+     * coexist. Operands referencing symbols, data words holding adresses, the symbols and the start
+     * adress are relocated.
+     */
+    pub fn load_at(&mut self, program: CompilerOutput, base: usize) -> bool {
+        let code = match program.relocated_code(base) {
+            Ok(code) if base + code.len() <= self.memory_size => code,
+            _ => return false,
+        };
+        for (offset, value) in code.into_iter().enumerate() {
+            self.store(base + offset, value);
+        }
+        self.iar = program.get_start_adress() + base;
+        self.forget_uninitialized(&program, base);
+        self.protected.extend(code_ranges(&program, base));
+        self.symbols.extend(program.get_symbols().iter().map(|symbol| symbol.relocated(base)));
+        self.source_map.extend(program.get_source_map().iter().map(|mapping| SourceMapping { adress: mapping.adress + base, ..*mapping }));
+        self.clear_halt();
+        true
+    }
+    pub fn get_debug(&self) -> MimaDebug {
+        let command = self.get_next_instruction();
+        let effective_adress = command.as_ref().and_then(|command| self.effective_adress(command));
+        MimaDebug {
+            akku: self.akku,
+            akku_signed: self.signed(self.akku),
+            iar: self.iar,
+            sp: self.sp,
+            fp: self.fp,
+            halt: self.halt,
+            halt_reason: self.halt_reason,
+            instruction: command.map(|command| command.to_string()),
+            effective_adress,
+            effective_value: effective_adress.and_then(|adress| self.peek(adress)),
+        }
+    }
+    pub fn get_akku(&self) -> usize {
+        self.akku
+    }
+    pub fn set_akku(&mut self, value: usize) -> bool {
+        if value > self.word_mask {
+            return false;
+        }
+        self.akku = value;
+        true
+    }
+    pub fn get_iar(&self) -> usize {
+        self.iar
+    }
+    // Moving the IAR abandons an instruction started with micro_step and restarts loop detection.
+    pub fn set_iar(&mut self, adress: usize) -> bool {
+        if adress >= self.memory_size {
+            return false;
+        }
+        self.iar = adress;
+        self.loop_check = None;
+        self.micro.phase = MicroPhase::Fetch;
+        self.micro.position = 0;
+        self.micro.instruction = None;
+        true
+    }
+    // Lets a halted machine continue, e.g. after fixing the state that made it stop.
+    pub fn clear_halt(&mut self) {
+        self.halt = false;
+        self.halt_reason = HaltReason::not_started();
+    }
+    pub fn get_next_instruction(&self) -> Option<Command> {
+        self.memory.get(self.iar).and_then(|word| Command::from_usize(*word))
+    }
+    // Maps a console to the given adress, every word the program writes there is printed as a character.
+    pub fn attach_console(&mut self, adress: usize) -> bool {
+        self.attach_console_with(adress, None)
+    }
+    /*
+     * Maps a keyboard to the given adress and the one after it. The first returns the next
+     * character of the input or 0, the second the number of characters waiting.
+     */
+    pub fn attach_keyboard(&mut self, adress: usize) -> bool {
+        let keyboard = Keyboard { input: Rc::clone(&self.keyboard_input) };
+        self.attach_device(adress, 2, Box::new(keyboard))
+    }
+    /*
+     * Maps a random number generator to the given adress. Every read returns a new 24 bit value,
+     * the sequence only depends on the seed or the last value the program wrote to the adress.
+     */
+    pub fn attach_random(&mut self, adress: usize, seed: u32) -> bool {
+        self.attach_device(adress, 1, Box::new(Random::new(seed as u64)))
+    }
+    /*
+     * Maps a timer to the given adress and the one after it, holding the interval and the handler
+     * adress. Every `interval` instructions the machine jumps to the handler, RTI returns from it.
+     */
+    pub fn attach_timer(&mut self, adress: usize, interval: usize, handler: usize) -> bool {
+        self.attach_device(adress, 2, Box::new(Timer { interval, handler, elapsed: 0 }))
+    }
+    // Whether an interrupt handler is running, further interrupts wait until it returns.
+    pub fn is_in_interrupt(&self) -> bool {
+        self.interrupt_return.is_some()
+    }
+    /*
+     * Maps a screen of `width` times `height` cells to the memory starting at the given adress,
+     * row by row. A machine has a single screen, attaching another one fails.
+     */
+    pub fn attach_screen(&mut self, adress: usize, width: usize, height: usize) -> bool {
+        // Sizes coming from JS may be anything, nothing is allocated before they fit into memory.
+        let size = match width.checked_mul(height) {
+            Some(size) if size > 0 && size <= self.memory_size => size,
+            _ => return false,
+        };
+        if !self.framebuffer.borrow().cells.is_empty() {
+            return false;
+        }
+        let framebuffer = Rc::new(RefCell::new(Framebuffer::new(width, height)));
+        if !self.attach_device(adress, size, Box::new(Screen { framebuffer: Rc::clone(&framebuffer) })) {
+            return false;
+        }
+        self.framebuffer = framebuffer;
+        true
+    }
+    // All cells of the screen row by row
+    pub fn get_framebuffer(&self) -> Vec<usize> {
+        self.framebuffer.borrow().cells.to_owned()
+    }
+    // Returns the rectangle of the screen changed since the last call, so only it has to be redrawn.
+    pub fn take_screen_update(&mut self) -> Option<ScreenUpdate> {
+        self.framebuffer.borrow_mut().take_update()
+    }
+    // The screen as text with one line per row, cells that aren't printable characters are spaces.
+    pub fn get_screen_text(&self) -> String {
+        let framebuffer = self.framebuffer.borrow();
+        framebuffer
+            .cells
+            .chunks(framebuffer.width.max(1))
+            .map(|row| {
+                row.iter()
+                    .map(|cell| char::from_u32(*cell as u32).filter(|character| !character.is_control()).unwrap_or(' '))
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    // Queues text typed by the user for the keyboard.
+    pub fn push_input(&mut self, text: &str) {
+        self.keyboard_input.borrow_mut().extend(text.chars().map(|character| character as usize));
+    }
+    // Returns everything printed to the console since the last call.
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.console_output.borrow_mut())
+    }
+    // Removes all devices, the memory behind them becomes visible again.
+    pub fn detach_devices(&mut self) {
+        self.devices.clear();
+        self.console_output.borrow_mut().clear();
+        *self.framebuffer.borrow_mut() = Framebuffer::default();
+        self.keyboard_input.borrow_mut().clear();
+    }
+}
+
+impl Default for Mima {
+    fn default() -> Mima {
+        Mima::new()
     }
 }
 
-#[wasm_bindgen]
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum Instruction {
-    LDC,
-    LDV,
-    STV,
-    ADD,
-    AND,
-    OR,
-    XOR,
-    EQL,
-    JMP,
-    JMN,
-    LDIV,
-    STIV,
-    NOT,
-    RAR,
-    HALT,
-}
-
+impl Mima {
+    fn build(profile: MachineProfile, memory_size: usize, word_bits: u32) -> Mima {
+        Mima {
+            memory_size,
+            word_bits,
+            word_mask: (1 << word_bits) - 1,
+            akku: 0,
+            iar: 0,
+            sp: memory_size,
+            fp: memory_size,
+            halt: false,
+            halt_reason: HaltReason::not_started(),
+            memory: vec![0; memory_size],
+            memory_hash: 0,
+            loop_check: None,
+            loop_detection: false,
+            initialized: vec![false; memory_size],
+            read_check: ReadCheck::Off,
+            uninitialized_reads: vec![],
+            dirty: BTreeSet::new(),
+            symbols: vec![],
+            source_map: vec![],
+            protected: vec![],
+            self_modifying: false,
+            profile,
+            trace: VecDeque::new(),
+            trace_capacity: 0,
+            profiling: false,
+            hits: BTreeMap::new(),
+            watches: vec![],
+            last_write: None,
+            micro: MicroState::default(),
+            cycles: cycle_table(&ControlStore::new()),
+            control_store: ControlStore::new(),
+            devices: vec![],
+            console_output: Rc::new(RefCell::new(String::new())),
+            framebuffer: Rc::new(RefCell::new(Framebuffer::default())),
+            keyboard_input: Rc::new(RefCell::new(VecDeque::new())),
+            hooks: Hooks::default(),
+            observers: vec![],
+            run_callback_interval: 1,
+            quiet: false,
+            cancellation: None,
+            interrupt_return: None,
+            pending_interrupt: None,
+            instruction_count: 0,
+            cycle_count: 0,
+            fuel: None,
+            input_log: vec![],
+            replaying: None,
+            decode_cache: vec![None; DECODE_CACHE_SIZE],
+            clock_rate: 10.0,
+            clock_budget: 0.0,
+        }
+    }
+
+    // Reads memory like the debugger sees it, devices aren't affected.
+    fn peek(&self, adress: usize) -> Option<usize> {
+        if adress >= self.memory_size {
+            None
+        } else if let Some(mapped) = self.devices.iter().find(|mapped| mapped.contains(adress)) {
+            Some(mapped.device.peek(adress - mapped.start))
+        } else {
+            Some(self.memory[adress])
+        }
+    }
+
+    // The memory cell an instruction reads or writes when executed now, through pointers and the stack
+    fn effective_adress(&self, command: &Command) -> Option<usize> {
+        match command.instruction {
+            Instruction::LDV
+            | Instruction::STV
+            | Instruction::ADD
+            | Instruction::AND
+            | Instruction::OR
+            | Instruction::XOR
+            | Instruction::EQL
+            | Instruction::JIND => Some(command.value),
+            Instruction::LDIV | Instruction::STIV => self.peek(command.value),
+            Instruction::LDRS | Instruction::STRS => Some(self.sp + command.value),
+            Instruction::RET => Some(self.sp),
+            Instruction::CALL => self.sp.checked_sub(1),
+            _ => None,
+        }
+    }
+
+    // The highest bit of a word, set for negative numbers
+    fn sign_bit(&self) -> usize {
+        1 << (self.word_bits - 1)
+    }
+
+    // Interprets a word as a two's complement number.
+    fn signed(&self, value: usize) -> i32 {
+        let value = (value & self.word_mask) as i64;
+        if value as usize & self.sign_bit() != 0 {
+            (value - (1i64 << self.word_bits)) as i32
+        } else {
+            value as i32
+        }
+    }
+
+    // Extends the 20 bit constant of ADC to a whole word.
+    fn sign_extend(&self, constant: usize) -> usize {
+        if constant & (1 << 19) != 0 {
+            constant | (self.word_mask & !OPERAND_MASK)
+        } else {
+            constant
+        }
+    }
+
+    // Like attach_console, the callback additionally receives every printed character.
+    pub fn attach_console_with(&mut self, adress: usize, callback: Option<Box<dyn FnMut(char)>>) -> bool {
+        let console = Console { output: Rc::clone(&self.console_output), callback };
+        self.attach_device(adress, 1, Box::new(console))
+    }
+    // Called with the adress and the new value before every write reaches memory or a device, writes through write_adress included.
+    pub fn set_memory_write_hook(&mut self, hook: Option<MemoryWriteHook>) {
+        self.hooks.memory_write = hook;
+    }
+    // Called whenever the machine stops.
+    pub fn set_halt_hook(&mut self, hook: Option<HaltHook>) {
+        self.hooks.halt = hook;
+    }
+    // Called after every step.
+    pub fn set_step_hook(&mut self, hook: Option<StepHook>) {
+        self.hooks.step = hook;
+    }
+    // Observers are notified after the hooks in the order they were added. Returns an id for remove_observer.
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) -> usize {
+        self.observers.push(Some(observer));
+        self.observers.len() - 1
+    }
+    pub fn remove_observer(&mut self, id: usize) -> Option<Box<dyn Observer>> {
+        self.observers.get_mut(id)?.take()
+    }
+
+    // Executes one instruction, step additionally notifies the frontend.
+    fn step_instruction(&mut self) -> StepResult {
+        let (command, adress) = self.execute_next();
+        self.step_result(command, adress)
+    }
+
+    // Executes one instruction and returns it together with its adress.
+    fn execute_next(&mut self) -> (Option<Command>, usize) {
+        let adress = self.iar;
+        self.last_write = None;
+        let akku = self.akku;
+        if self.halt {
+            return (None, adress);
+        }
+        if self.fuel == Some(0) {
+            self.stop(HaltReason::out_of_fuel());
+            return (None, adress);
+        }
+        // An instruction started with micro_step is completed first.
+        if self.micro.phase != MicroPhase::Fetch || self.micro.position != 0 {
+            while !self.halt && !self.micro_step() {}
+            let command = self.decode(self.micro.ir);
+            self.record(command.to_owned(), self.micro.adress, akku);
+            self.poll_interrupts();
+            return (command, self.micro.adress);
+        }
+        // Running off the end of memory
+        if self.iar >= self.memory_size {
+            self.stop(HaltReason::adress_out_of_range(Some(self.iar)));
+            return (None, adress);
+        }
+        let word = self.memory[self.iar];
+        let command = self.decode(word);
+        match command.to_owned() {
+            Some(command) => self.execute(command),
+            None => self.stop(HaltReason::illegal_instruction(self.iar, word)),
+        }
+        self.record(command.to_owned(), adress, akku);
+        self.poll_interrupts();
+        if self.loop_detection {
+            self.detect_loop(adress);
+        }
+        (command, adress)
+    }
+
+    /*
+     * Stops the machine once it is in a state it was in before. Programs can't leave such a loop,
+     * unless a device changes their input, so machines with devices aren't checked.
+     */
+    fn detect_loop(&mut self, adress: usize) {
+        if self.halt || !self.devices.is_empty() {
+            self.loop_check = None;
+            return;
+        }
+        let state = (self.iar, self.akku, self.sp, self.fp, self.memory_hash);
+        let Some(check) = &mut self.loop_check else {
+            self.loop_check = Some(LoopCheck { state, power: 1, length: 0, low: usize::MAX, high: 0 });
+            return;
+        };
+        check.low = check.low.min(adress);
+        check.high = check.high.max(adress);
+        check.length += 1;
+        if check.state == state {
+            let (low, high) = (check.low, check.high);
+            self.loop_check = None;
+            self.stop(HaltReason::infinite_loop(low, high));
+        } else if check.length == check.power {
+            *check = LoopCheck { state, power: check.power * 2, length: 0, low: usize::MAX, high: 0 };
+        }
+    }
+
+    /*
+     * Lets the devices count the executed instruction and enters the handler of a requested
+     * interrupt. Like a CALL the return adress is saved, but in a register instead of the stack,
+     * so handlers also work without the MIMA-X extension. Handlers have to save the akku themselves.
+     */
+    fn poll_interrupts(&mut self) {
+        if self.halt {
+            return;
+        }
+        for mapped in self.devices.iter_mut() {
+            if let Some(handler) = mapped.device.tick() {
+                self.pending_interrupt = self.pending_interrupt.or(Some(handler));
+            }
+        }
+        if self.interrupt_return.is_none() {
+            if let Some(handler) = self.pending_interrupt.take() {
+                self.interrupt_return = Some(self.iar);
+                self.iar = handler;
+            }
+        }
+    }
+
+    /*
+     * Executes up to `steps` instructions and returns how many were executed. Stops early if the
+     * machine halts or the run is cancelled.
+     */
+    pub(crate) fn run_chunk(&mut self, steps: usize) -> usize {
+        self.run_while(steps, &|_| true).0
+    }
+
+    /*
+     * Executes instructions as long as `condition` holds, at most `steps` of them. Returns how
+     * many were executed and whether the run was cancelled.
+     */
+    fn run_while(&mut self, steps: usize, condition: &dyn Fn(&Mima) -> bool) -> (usize, bool) {
+        let mut executed = 0;
+        let mut cancelled = false;
+        // Without hooks or observers nothing has to be reported, which saves building step results.
+        let observed = self.hooks.step.is_some() || self.hooks.memory_write.is_some() || self.observers.iter().any(Option::is_some);
+        while !self.halt && executed < steps && condition(self) {
+            if executed % CANCELLATION_INTERVAL == 0 && self.cancellation.as_ref().is_some_and(CancellationHandle::take) {
+                cancelled = true;
+                break;
+            }
+            self.quiet = !observed || self.run_callback_interval == 0 || !executed.is_multiple_of(self.run_callback_interval);
+            if self.quiet {
+                self.execute_next();
+            } else {
+                self.step();
+            }
+            executed += 1;
+        }
+        self.quiet = false;
+        (executed, cancelled)
+    }
+
+    // Maps a device to `size` adresses starting at `start`. Ranges may not overlap.
+    pub fn attach_device(&mut self, start: usize, size: usize, device: Box<dyn Device>) -> bool {
+        let Some(end) = start.checked_add(size) else {
+            return false;
+        };
+        if size == 0 || end > self.memory_size || self.devices.iter().any(|mapped| start < mapped.start + mapped.size && mapped.start < end) {
+            return false;
+        }
+        self.devices.push(MappedDevice { start, size, device });
+        true
+    }
+
+    // Variables declared without a value are loaded as zero, but don't count as written.
+    fn forget_uninitialized(&mut self, program: &CompilerOutput, base: usize) {
+        for symbol in program.get_symbols().iter().filter(|symbol| !symbol.is_initialized()) {
+            let start = base + symbol.get_adress();
+            self.initialized[start..start + symbol.get_size()].fill(false);
+        }
+    }
+
+    // Inside "mem[...]" variables stand for their adress, see add_watch.
+    fn evaluate_watch(&self, expression: &Expression, adress: bool) -> Result<i64, String> {
+        let overflow = || format!("Expression '{}' overflows.", expression.text);
+        match &expression.node {
+            Node::Number(value) => Ok(*value),
+            Node::Symbol(name) => match self.symbols.iter().find(|symbol| symbol.get_name() == *name) {
+                Some(symbol) if adress || symbol.get_kind() == SymbolKind::Label => Ok(symbol.get_adress() as i64),
+                Some(symbol) => Ok(self.signed(self.peek(symbol.get_adress()).unwrap_or(0)) as i64),
+                None => match name.to_uppercase().as_str() {
+                    "AKKU" => Ok(self.signed(self.akku) as i64),
+                    "IAR" => Ok(self.iar as i64),
+                    "SP" => Ok(self.sp as i64),
+                    "FP" => Ok(self.fp as i64),
+                    _ => Err(format!("Couldn't find symbol '{name}'.")),
+                },
+            },
+            Node::Memory(operand) => {
+                let adress = self.evaluate_watch(operand, true)?;
+                usize::try_from(adress)
+                    .ok()
+                    .and_then(|adress| self.peek(adress))
+                    .map(|word| self.signed(word) as i64)
+                    .ok_or_else(|| format!("Adress '{adress}' is out of range."))
+            }
+            Node::Negate(operand) => self.evaluate_watch(operand, adress)?.checked_neg().ok_or_else(overflow),
+            Node::Binary(operator, left, right) => {
+                let (left, right) = (self.evaluate_watch(left, adress)?, self.evaluate_watch(right, adress)?);
+                match operator {
+                    BinaryOperator::Add => left.checked_add(right),
+                    BinaryOperator::Subtract => left.checked_sub(right),
+                    BinaryOperator::Multiply => left.checked_mul(right),
+                    BinaryOperator::Divide | BinaryOperator::Remainder if right == 0 => {
+                        return Err(format!("Division by zero in expression '{}'.", expression.text))
+                    }
+                    BinaryOperator::Divide => left.checked_div(right),
+                    BinaryOperator::Remainder => left.checked_rem(right),
+                }
+                .ok_or_else(overflow)
+            }
+        }
+    }
+
+    fn resolve_symbol(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.get_name() == name)
+            .map(|symbol| symbol.get_adress())
+    }
+
+    fn stop(&mut self, reason: HaltReason) {
+        self.halt = true;
+        self.halt_reason = reason;
+        self.notify(&mut |observer| observer.on_halt(reason));
+    }
+
+    fn notify(&mut self, event: &mut dyn FnMut(&mut dyn Observer)) {
+        event(&mut self.hooks);
+        for observer in self.observers.iter_mut().flatten() {
+            event(observer.as_mut());
+        }
+    }
+
+    // Describes an adress relative to the closest label in front of it, e.g. "LOOP+2".
+    fn location(&self, adress: usize) -> Option<String> {
+        self.location_of(adress, &[SymbolKind::Label])
+    }
+
+    // Like location, but relative to the closest symbol of one of the given kinds
+    fn location_of(&self, adress: usize, kinds: &[SymbolKind]) -> Option<String> {
+        let label = self
+            .symbols
+            .iter()
+            .filter(|symbol| kinds.contains(&symbol.get_kind()) && symbol.get_adress() <= adress)
+            .max_by_key(|symbol| symbol.get_adress())?;
+        Some(match adress - label.get_adress() {
+            0 => label.get_name(),
+            offset => format!("{}+{offset}", label.get_name()),
+        })
+    }
+
+    // Decodes an instruction word, invalid words and instructions outside the profile are rejected.
+    fn decode(&mut self, word: usize) -> Option<Command> {
+        // Direct-mapped, the operand bits vary the most between words.
+        let slot = (word ^ (word >> 16)) & (DECODE_CACHE_SIZE - 1);
+        if let Some((cached, command)) = &self.decode_cache[slot] {
+            if *cached == word {
+                return command.to_owned();
+            }
+        }
+        let command = Command::from_usize(word).filter(|command| self.profile.supports(command.instruction));
+        self.decode_cache[slot] = Some((word, command.to_owned()));
+        command
+    }
+
+    // Executes the instruction at IAR at once.
+    fn execute(&mut self, command: Command) {
+        let mut next_instruction = self.iar + 1;
+        let akku = self.akku;
+        match command.instruction {
+            Instruction::LDC => self.akku = command.value,
+            Instruction::LDV => self.akku = self.read_memory(command.value),
+            Instruction::STV => self.write_memory(command.value, self.akku),
+            // Arithmetic is done modulo 2^24, so overflows wrap around like in hardware.
+            Instruction::ADD => self.akku = (self.akku + self.read_memory(command.value)) & self.word_mask,
+            Instruction::AND => self.akku &= self.read_memory(command.value),
+            Instruction::OR => self.akku |= self.read_memory(command.value),
+            Instruction::XOR => self.akku ^= self.read_memory(command.value),
+            Instruction::EQL => {
+                self.akku = if self.akku == self.read_memory(command.value) {
+                    self.word_mask
+                } else {
+                    0
+                }
+            }
+            Instruction::JMP => next_instruction = command.value,
+            // Only the lower 20 bits of the stored word form the target adress.
+            Instruction::JIND => next_instruction = self.read_memory(command.value) & OPERAND_MASK,
+            Instruction::JMN => {
+                if self.akku & self.sign_bit() != 0 {
+                    next_instruction = command.value
+                }
+            }
+            // Both the pointer and the adress it holds are checked, a failed first read ends the instruction.
+            Instruction::LDIV => {
+                let adress = self.read_memory(command.value);
+                if !self.halt {
+                    self.akku = self.read_memory(adress);
+                }
+            }
+            Instruction::STIV => {
+                let adress = self.read_memory(command.value);
+                if !self.halt {
+                    self.write_memory(adress, self.akku);
+                }
+            },
+            // A stack overflow or returning from an empty stack halts the machine.
+            Instruction::CALL => {
+                if self.sp == 0 {
+                    self.stop(HaltReason::adress_out_of_range(None));
+                    return;
+                }
+                self.sp -= 1;
+                self.write_memory(self.sp, self.iar + 1);
+                next_instruction = command.value;
+            }
+            Instruction::RET => {
+                if self.sp >= self.memory_size {
+                    self.stop(HaltReason::adress_out_of_range(Some(self.sp)));
+                    return;
+                }
+                next_instruction = self.read_memory(self.sp);
+                self.sp += 1;
+            }
+            // Returning without an interrupt would jump anywhere, so it traps like an unknown instruction.
+            Instruction::RTI => match self.interrupt_return.take() {
+                Some(adress) => next_instruction = adress,
+                None => {
+                    self.stop(HaltReason::illegal_instruction(self.iar, command.to_usize()));
+                    return;
+                }
+            },
+            Instruction::ADC => self.akku = (self.akku + self.sign_extend(command.value)) & self.word_mask,
+            Instruction::LDSP => self.akku = self.sp,
+            Instruction::LDFP => self.akku = self.fp,
+            // The stack and frame pointer may point one past the end of memory (empty stack).
+            Instruction::STSP | Instruction::STFP if self.akku > self.memory_size => {
+                self.stop(HaltReason::adress_out_of_range(Some(self.akku)));
+                return;
+            }
+            Instruction::STSP => self.sp = self.akku,
+            Instruction::STFP => self.fp = self.akku,
+            Instruction::LDRS | Instruction::STRS if self.sp + command.value >= self.memory_size => {
+                self.stop(HaltReason::adress_out_of_range(Some(self.sp + command.value)));
+                return;
+            }
+            Instruction::LDRS => self.akku = self.read_memory(self.sp + command.value),
+            Instruction::STRS => self.write_memory(self.sp + command.value, self.akku),
+            Instruction::HALT => self.stop(HaltReason::halted()),
+            Instruction::NOT => self.akku = !self.akku & self.word_mask,
+            Instruction::RAR => self.akku = ((self.akku >> 1) | ((self.akku & 1) << (self.word_bits - 1))) & self.word_mask,
+        }
+        if !self.halt {
+            self.iar = next_instruction;
+        } else if matches!(self.halt_reason.kind, HaltKind::AdressOutOfRange | HaltKind::UninitializedRead) {
+            // A failed memory access doesn't change the akku.
+            self.akku = akku;
+        }
+    }
+
+    // Performs one clock cycle: transfers over the bus, then the ALU and the memory.
+    fn cycle(&mut self, control: u32) {
+        let drivers = [
+            (microcode::AW, self.akku),
+            (microcode::Z, self.micro.z),
+            (microcode::E, 1),
+            (microcode::PW, self.iar),
+            // Only the operand of the instruction register is connected to the bus.
+            (microcode::IW, self.micro.ir & OPERAND_MASK),
+            (microcode::DW, self.micro.sdr),
+        ];
+        let bus = drivers
+            .iter()
+            .filter(|(line, _)| control & line != 0)
+            .map(|(_, value)| *value)
+            .reduce(|a, b| a | b);
+        if let Some(value) = bus {
+            if control & microcode::AR != 0 {
+                self.akku = value;
+            }
+            if control & microcode::X != 0 {
+                self.micro.x = value;
+            }
+            if control & microcode::Y != 0 {
+                self.micro.y = value;
+            }
+            if control & microcode::PR != 0 {
+                self.iar = value & (self.memory_size - 1);
+            }
+            if control & microcode::IR != 0 {
+                self.micro.ir = value;
+            }
+            if control & microcode::DR != 0 {
+                self.micro.sdr = value;
+            }
+            if control & microcode::S != 0 {
+                self.micro.sar = value & (self.memory_size - 1);
+            }
+        }
+        let (x, y) = (self.micro.x, self.micro.y);
+        match microcode::alu_operation(control) {
+            0 => (),
+            1 => self.micro.z = (x + y) & self.word_mask,
+            2 => self.micro.z = ((x >> 1) | ((x & 1) << (self.word_bits - 1))) & self.word_mask,
+            3 => self.micro.z = x & y,
+            4 => self.micro.z = x | y,
+            5 => self.micro.z = x ^ y,
+            6 => self.micro.z = !x & self.word_mask,
+            _ => self.micro.z = if x == y { self.word_mask } else { 0 },
+        }
+        if control & microcode::R != 0 {
+            self.micro.sdr = self.read_memory(self.micro.sar);
+        }
+        if control & microcode::W != 0 {
+            self.write_memory(self.micro.sar, self.micro.sdr);
+        }
+        self.micro.control = control;
+        self.micro.bus = bus;
+        self.micro.trace = Some(MicroTrace::new(self.micro.phase, control, bus));
+    }
+
+    // Data reads of the program go through here so devices can answer them.
+    fn read_memory(&mut self, adress: usize) -> usize {
+        if adress >= self.memory_size {
+            self.stop(HaltReason::adress_out_of_range(Some(adress)));
+            return 0;
+        }
+        let Some(mapped) = self.devices.iter_mut().find(|mapped| mapped.contains(adress)) else {
+            if !self.initialized[adress] {
+                self.check_read(adress);
+            }
+            return self.memory[adress];
+        };
+        let value = match &mut self.replaying {
+            // A program that reads different devices than in the log gets zeros.
+            Some(events) => events.pop_front().filter(|event| event.adress == adress).map_or(0, |event| event.value),
+            None => mapped.device.read(adress - mapped.start),
+        };
+        self.input_log.push(InputEvent { step: self.instruction_count, adress, value });
+        value
+    }
+
+    // Memory writes of the program, which may not overwrite its instructions
+    fn write_memory(&mut self, adress: usize, value: usize) {
+        if !self.self_modifying && self.protected.iter().any(|range| range.contains(&adress)) {
+            self.stop(HaltReason::write_protected(adress));
+            return;
+        }
+        self.write_word(adress, value);
+    }
+
+    // All memory writes go through here so they can be tracked. Devices and observers get the value as it's stored.
+    fn write_word(&mut self, adress: usize, value: usize) {
+        if adress >= self.memory_size {
+            self.stop(HaltReason::adress_out_of_range(Some(adress)));
+            return;
+        }
+        let value = value & self.word_mask;
+        self.last_write = Some(adress);
+        if !self.quiet {
+            self.notify(&mut |observer| observer.on_memory_write(adress, value));
+        }
+        if let Some(mapped) = self.devices.iter_mut().find(|mapped| mapped.contains(adress)) {
+            mapped.device.write(adress - mapped.start, value);
+            return;
+        }
+        self.store(adress, value);
+    }
+
+    fn check_read(&mut self, adress: usize) {
+        // Microprograms read after the IAR was already incremented.
+        let instruction = if self.micro.phase == MicroPhase::Execute { self.micro.adress } else { self.iar };
+        match self.read_check {
+            ReadCheck::Off => (),
+            ReadCheck::Warn => self.uninitialized_reads.push(UninitializedRead { adress, instruction }),
+            ReadCheck::Trap => self.stop(HaltReason::uninitialized_read(adress)),
+        }
+    }
+
+    // Writes to RAM even if a device is mapped there, used for loading programs.
+    // Memory only ever holds words of the configured width, wider values are cut off.
+    fn store(&mut self, adress: usize, value: usize) {
+        let value = value & self.word_mask;
+        self.memory_hash ^= word_hash(adress, self.memory[adress]) ^ word_hash(adress, value);
+        self.memory[adress] = value;
+        self.initialized[adress] = true;
+        self.dirty.insert(adress);
+    }
+
+    // Feeds an executed instruction into the trace and the profile.
+    fn record(&mut self, command: Option<Command>, adress: usize, akku_before: usize) {
+        if let Some(command) = &command {
+            self.instruction_count += 1;
+            if let Some(fuel) = &mut self.fuel {
+                *fuel = fuel.saturating_sub(1);
+            }
+            self.cycle_count += match command.instruction {
+                Instruction::JMN if self.akku & self.sign_bit() == 0 => self.control_store.fetch().len() as u64 + 1,
+                instruction => self.cycles[instruction.to_opcode()],
+            };
+        }
+        if self.profiling && command.is_some() {
+            *self.hits.entry(adress).or_default() += 1;
+        }
+        if self.trace_capacity == 0 {
+            return;
+        }
+        if let Some(command) = command {
+            if self.trace.len() == self.trace_capacity {
+                self.trace.pop_front();
+            }
+            self.trace.push_back(TraceEntry { adress, command, akku_before, akku_after: self.akku });
+        }
+    }
+
+    fn step_result(&self, command: Option<Command>, adress: usize) -> StepResult {
+        StepResult {
+            command,
+            adress,
+            written: self.last_write,
+            halt: self.halt,
+            halt_reason: self.halt_reason,
+            watches: self.get_watches(),
+        }
+    }
+}
+
+// Bindings that only exist in JS. Callbacks are wrapped into hooks, errors they throw are ignored.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl Mima {
+    pub fn set_on_memory_write(&mut self, callback: Option<js_sys::Function>) {
+        self.hooks.memory_write = callback.map(|callback| {
+            Box::new(move |adress: usize, value: usize| {
+                let _ = callback.call2(&JsValue::NULL, &JsValue::from(adress), &JsValue::from(value));
+            }) as MemoryWriteHook
+        });
+    }
+    pub fn set_on_halt(&mut self, callback: Option<js_sys::Function>) {
+        self.hooks.halt = callback.map(|callback| {
+            Box::new(move |reason: HaltReason| {
+                let _ = callback.call1(&JsValue::NULL, &JsValue::from(reason));
+            }) as HaltHook
+        });
+    }
+    pub fn set_on_step(&mut self, callback: Option<js_sys::Function>) {
+        self.hooks.step = callback.map(|callback| {
+            Box::new(move |result: &StepResult| {
+                let _ = callback.call1(&JsValue::NULL, &JsValue::from(result.to_owned()));
+            }) as StepHook
+        });
+    }
+    // Like attach_console, the callback is called with every printed character.
+    pub fn attach_console_with_callback(&mut self, adress: usize, callback: js_sys::Function) -> bool {
+        let forward = move |character: char| {
+            let _ = callback.call1(&JsValue::NULL, &JsValue::from_str(&character.to_string()));
+        };
+        self.attach_console_with(adress, Some(Box::new(forward)))
+    }
+    /*
+     * Runs the program in chunks of `steps_per_chunk` instructions and gives control back to the
+     * browser between them, so the page stays responsive. Aborting the optional AbortSignal stops
+     * the run after the current chunk, the cancellation handle within it.
+     */
+    pub async fn run_async(&mut self, steps_per_chunk: usize, signal: Option<js_sys::Object>) -> RunResult {
+        let mut steps = 0;
+        loop {
+            let executed = self.run_chunk(steps_per_chunk.max(1));
+            steps += executed;
+            let cancelled = executed < steps_per_chunk.max(1) || signal.as_ref().is_some_and(is_aborted);
+            if self.halt || cancelled {
+                return RunResult { halt_reason: self.halt_reason, steps, cancelled: !self.halt };
+            }
+            // Errors only occur without a JS event loop, running on is the best we can do then.
+            let _ = wasm_bindgen_futures::JsFuture::from(sleep(0.0)).await;
+        }
+    }
+    /*
+     * Runs the program at `hz` instructions per second driven by an internal timer, until it
+     * halts or is cancelled like run_async.
+     */
+    pub async fn run_realtime(&mut self, hz: f64, signal: Option<js_sys::Object>) -> RunResult {
+        if !self.set_clock_rate(hz) {
+            return RunResult { halt_reason: self.halt_reason, steps: 0, cancelled: true };
+        }
+        // Slow clocks wake up once per instruction, fast ones at most every 4 ms.
+        let interval = (1000.0 / hz).clamp(4.0, MAX_TICK_MS);
+        let mut steps = 0;
+        let mut last = js_sys::Date::now();
+        loop {
+            let _ = wasm_bindgen_futures::JsFuture::from(sleep(interval)).await;
+            let now = js_sys::Date::now();
+            let expected = ((now - last).min(MAX_TICK_MS) * self.clock_rate / 1000.0 + self.clock_budget).floor() as usize;
+            let executed = self.tick(now - last);
+            last = now;
+            steps += executed;
+            let cancelled = executed < expected || signal.as_ref().is_some_and(is_aborted);
+            if self.halt || cancelled {
+                return RunResult { halt_reason: self.halt_reason, steps, cancelled: !self.halt };
+            }
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+fn is_aborted(signal: &js_sys::Object) -> bool {
+    js_sys::Reflect::get(signal, &JsValue::from_str("aborted")).is_ok_and(|aborted| aborted.is_truthy())
+}
+
+// A promise resolved by setTimeout, so rendering and input events get processed before it resolves.
+#[cfg(feature = "wasm")]
+fn sleep(ms: f64) -> js_sys::Promise {
+    js_sys::Promise::new(&mut |resolve, reject| {
+        let set_timeout = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|function| function.dyn_into::<js_sys::Function>().ok());
+        match set_timeout {
+            Some(set_timeout) => {
+                let _ = set_timeout.call2(&JsValue::NULL, &resolve, &JsValue::from(ms));
+            }
+            None => {
+                let _ = reject.call0(&JsValue::NULL);
+            }
+        }
+    })
+}
+
+// Cycles of every opcode: the fetch, the decode and the microprogram of the instruction
+fn cycle_table(control_store: &ControlStore) -> Vec<u64> {
+    let fetch = control_store.fetch().len() + 1;
+    (0..=u8::MAX as usize)
+        .map(|opcode| {
+            let program = Instruction::from_opcode(opcode).and_then(|instruction| control_store.program(instruction));
+            (fetch + program.map_or(0, |program| program.len())) as u64
+        })
+        .collect()
+}
+
+// Hash of a memory word for Mima::memory_hash, zero for empty words so cleared memory hashes to zero
+fn word_hash(adress: usize, value: usize) -> u64 {
+    if value == 0 {
+        return 0;
+    }
+    let mut hash = ((adress as u64) << 32 | value as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    hash ^= hash >> 29;
+    hash.wrapping_mul(0xBF58_476D_1CE4_E5B9)
+}
+
+/*
+ * The adresses of a compiled program that don't belong to variables, moved to `base`. Programs
+ * without symbols, e.g. memory images, can't be told apart from data and aren't protected.
+ */
+fn code_ranges(program: &CompilerOutput, base: usize) -> Vec<Range<usize>> {
+    let symbols = program.get_symbols();
+    if symbols.is_empty() {
+        return vec![];
+    }
+    let variables: Vec<Range<usize>> = symbols
+        .iter()
+        .filter(|symbol| symbol.get_kind() == SymbolKind::Variable)
+        .map(|symbol| symbol.get_adress()..symbol.get_adress() + symbol.get_size())
+        .collect();
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for adress in 0..program.get_mima_code().len() {
+        if variables.iter().any(|variable| variable.contains(&adress)) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if range.end == base + adress => range.end += 1,
+            _ => ranges.push(base + adress..base + adress + 1),
+        }
+    }
+    ranges
+}
+
+// Interprets a 24 bit memory word as a two's complement number.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn to_signed(value: usize) -> i32 {
+    let value = (value & WORD_MASK) as i32;
+    if value as usize & SIGN_BIT != 0 {
+        value - VALUE_SIZE as i32
+    } else {
+        value
+    }
+}
+
+// Encodes a number as a 24 bit two's complement word, values outside of the word wrap around.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn from_signed(value: i32) -> usize {
+    value as usize & WORD_MASK
+}
+
+// How format_word and Command::format render numbers
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WordStyle {
+    Decimal,
+    // Padded to the width of the word or operand, like 0x00003
+    Hex,
+    // Groups of four bits, the opcode of an instruction separated by a bar
+    Binary,
+}
+
+// Renders a 24 bit data word, e.g. "0x00002A" or "0000 0000 0000 0000 0010 1010".
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn format_word(value: usize, style: WordStyle) -> String {
+    let value = value & WORD_MASK;
+    match style {
+        WordStyle::Decimal => value.to_string(),
+        WordStyle::Hex => format!("0x{value:06X}"),
+        WordStyle::Binary => bit_groups(value, WORD_BITS),
+    }
+}
+
+// The lowest `bits` bits of the value in groups of four, most significant first
+fn bit_groups(value: usize, bits: u32) -> String {
+    (0..bits.div_ceil(4))
+        .rev()
+        .map(|group| format!("{:04b}", (value >> (group * 4)) & 0xF))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// What the operand of an instruction stands for
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OperandKind {
+    Adress,
+    Constant,
+    // The operand is ignored and may be left out
+    None,
+}
+
+// An entry of get_instruction_set
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstructionInfo {
+    mnemonic: String,
+    pub opcode: usize,
+    pub operand: OperandKind,
+    // Bits of the instruction word left for the operand, 0 if it takes none
+    pub operand_bits: u32,
+    description: String,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl InstructionInfo {
+    pub fn get_mnemonic(&self) -> String {
+        self.mnemonic.to_owned()
+    }
+    pub fn get_description(&self) -> String {
+        self.description.to_owned()
+    }
+}
+
+// Every instruction the machine understands ordered by opcode, for help texts in the frontend.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn get_instruction_set() -> Vec<InstructionInfo> {
+    (0..=u8::MAX as usize)
+        .filter_map(Instruction::from_opcode)
+        .map(|instruction| {
+            let operand = if !instruction.takes_operand() {
+                OperandKind::None
+            } else if instruction.takes_constant() {
+                OperandKind::Constant
+            } else {
+                OperandKind::Adress
+            };
+            InstructionInfo {
+                mnemonic: instruction.mnemonic().to_string(),
+                opcode: instruction.to_opcode(),
+                operand,
+                operand_bits: if operand == OperandKind::None { 0 } else { instruction.operand_bits() },
+                description: instruction.description().to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Instruction {
+    LDC,
+    LDV,
+    STV,
+    ADD,
+    AND,
+    OR,
+    XOR,
+    EQL,
+    JMP,
+    JMN,
+    LDIV,
+    STIV,
+    NOT,
+    RAR,
+    HALT,
+    // MIMA-X extension
+    CALL,
+    RET,
+    ADC,
+    LDSP,
+    STSP,
+    LDFP,
+    STFP,
+    LDRS,
+    STRS,
+    JIND,
+    // Interrupts
+    RTI,
+}
+
 impl Instruction {
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Instruction::LDC => "LDC",
+            Instruction::LDV => "LDV",
+            Instruction::STV => "STV",
+            Instruction::ADD => "ADD",
+            Instruction::AND => "AND",
+            Instruction::OR => "OR",
+            Instruction::XOR => "XOR",
+            Instruction::EQL => "EQL",
+            Instruction::JMP => "JMP",
+            Instruction::JMN => "JMN",
+            Instruction::LDIV => "LDIV",
+            Instruction::STIV => "STIV",
+            Instruction::HALT => "HALT",
+            Instruction::NOT => "NOT",
+            Instruction::RAR => "RAR",
+            Instruction::CALL => "CALL",
+            Instruction::RET => "RET",
+            Instruction::ADC => "ADC",
+            Instruction::LDSP => "LDSP",
+            Instruction::STSP => "STSP",
+            Instruction::LDFP => "LDFP",
+            Instruction::STFP => "STFP",
+            Instruction::LDRS => "LDRS",
+            Instruction::STRS => "STRS",
+            Instruction::JIND => "JIND",
+            Instruction::RTI => "RTI",
+        }
+    }
+    // One line of documentation for help texts and completions
+    pub fn description(self) -> &'static str {
+        match self {
+            Instruction::LDC => "Loads the constant into the akku",
+            Instruction::LDV => "Loads the value at the adress into the akku",
+            Instruction::STV => "Stores the akku at the adress",
+            Instruction::ADD => "Adds the value at the adress to the akku",
+            Instruction::AND => "Bitwise and of the akku and the value at the adress",
+            Instruction::OR => "Bitwise or of the akku and the value at the adress",
+            Instruction::XOR => "Bitwise xor of the akku and the value at the adress",
+            Instruction::EQL => "Sets the akku to -1 if it equals the value at the adress, otherwise to 0",
+            Instruction::JMP => "Jumps to the adress",
+            Instruction::JMN => "Jumps to the adress if the akku is negative",
+            Instruction::LDIV => "Loads the value at the adress stored at the adress",
+            Instruction::STIV => "Stores the akku at the adress stored at the adress",
+            Instruction::NOT => "Inverts all bits of the akku",
+            Instruction::RAR => "Rotates the akku one bit to the right",
+            Instruction::HALT => "Stops the machine",
+            Instruction::CALL => "Calls the subroutine at the adress",
+            Instruction::RET => "Returns from a subroutine",
+            Instruction::ADC => "Adds the constant to the akku",
+            Instruction::LDSP => "Loads the stack pointer into the akku",
+            Instruction::STSP => "Sets the stack pointer to the akku",
+            Instruction::LDFP => "Loads the frame pointer into the akku",
+            Instruction::STFP => "Sets the frame pointer to the akku",
+            Instruction::LDRS => "Loads the value at the stack pointer plus the offset",
+            Instruction::STRS => "Stores the akku at the stack pointer plus the offset",
+            Instruction::JIND => "Jumps to the adress stored at the adress",
+            Instruction::RTI => "Returns from an interrupt handler",
+        }
+    }
+    // The extended instructions work on the akku only and ignore their operand.
+    pub fn takes_operand(self) -> bool {
+        !matches!(
+            self,
+            Instruction::HALT
+                | Instruction::NOT
+                | Instruction::RAR
+                | Instruction::RET
+                | Instruction::LDSP
+                | Instruction::STSP
+                | Instruction::LDFP
+                | Instruction::STFP
+                | Instruction::RTI
+        )
+    }
+    // Instructions whose operand is a (possibly negative) constant instead of an adress
+    pub fn takes_constant(self) -> bool {
+        matches!(self, Instruction::LDC | Instruction::ADC)
+    }
+    // Jumps take labels as operands, all other instructions work on data.
+    pub fn is_jump(self) -> bool {
+        matches!(self, Instruction::JMP | Instruction::JMN | Instruction::CALL)
+    }
+    // Extended instructions use 8 bit opcodes and only leave 16 bits for the operand.
+    pub fn operand_bits(self) -> u32 {
+        if self.to_opcode() >= 240 {
+            16
+        } else {
+            20
+        }
+    }
+    // The largest operand the instruction word has room for
+    pub fn max_operand(self) -> usize {
+        (1 << self.operand_bits()) - 1
+    }
     pub fn from_opcode(opcode: usize) -> Option<Instruction> {
         match opcode {
             0 => Some(Instruction::LDC),
@@ -168,14 +2511,26 @@ impl Instruction {
             9 => Some(Instruction::JMN),
             10 => Some(Instruction::LDIV),
             11 => Some(Instruction::STIV),
+            12 => Some(Instruction::CALL),
+            13 => Some(Instruction::ADC),
+            14 => Some(Instruction::JIND),
             240 => Some(Instruction::HALT),
             241 => Some(Instruction::NOT),
             242 => Some(Instruction::RAR),
+            243 => Some(Instruction::RET),
+            244 => Some(Instruction::LDSP),
+            245 => Some(Instruction::STSP),
+            246 => Some(Instruction::LDFP),
+            247 => Some(Instruction::STFP),
+            248 => Some(Instruction::LDRS),
+            249 => Some(Instruction::STRS),
+            250 => Some(Instruction::RTI),
             _ => None,
         }
     }
+    // Mnemonics are case-insensitive.
     pub fn from_string(string: &str) -> Option<Instruction> {
-        match string {
+        match string.to_uppercase().as_str() {
             "LDC" => Some(Self::LDC),
             "LDV" => Some(Self::LDV),
             "STV" => Some(Self::STV),
@@ -191,177 +2546,1525 @@ impl Instruction {
             "HALT" => Some(Self::HALT),
             "NOT" => Some(Self::NOT),
             "RAR" => Some(Self::RAR),
+            "CALL" => Some(Self::CALL),
+            "RET" => Some(Self::RET),
+            "ADC" => Some(Self::ADC),
+            "LDSP" => Some(Self::LDSP),
+            "STSP" => Some(Self::STSP),
+            "LDFP" => Some(Self::LDFP),
+            "STFP" => Some(Self::STFP),
+            "LDRS" => Some(Self::LDRS),
+            "STRS" => Some(Self::STRS),
+            "JIND" => Some(Self::JIND),
+            "RTI" => Some(Self::RTI),
             _ => None
         }
     }
-    pub fn to_opcode(&self) -> usize {
-        match self {
-            Instruction::LDC => 0,
-            Instruction::LDV => 1,
-            Instruction::STV => 2,
-            Instruction::ADD => 3,
-            Instruction::AND => 4,
-            Instruction::OR => 5,
-            Instruction::XOR => 6,
-            Instruction::EQL => 7,
-            Instruction::JMP => 8,
-            Instruction::JMN => 9,
-            Instruction::LDIV => 10,
-            Instruction::STIV => 11,
-            Instruction::HALT => 240,
-            Instruction::NOT => 241,
-            Instruction::RAR => 242,
+    pub fn to_opcode(self) -> usize {
+        match self {
+            Instruction::LDC => 0,
+            Instruction::LDV => 1,
+            Instruction::STV => 2,
+            Instruction::ADD => 3,
+            Instruction::AND => 4,
+            Instruction::OR => 5,
+            Instruction::XOR => 6,
+            Instruction::EQL => 7,
+            Instruction::JMP => 8,
+            Instruction::JMN => 9,
+            Instruction::LDIV => 10,
+            Instruction::STIV => 11,
+            Instruction::HALT => 240,
+            Instruction::NOT => 241,
+            Instruction::RAR => 242,
+            Instruction::CALL => 12,
+            Instruction::RET => 243,
+            Instruction::ADC => 13,
+            Instruction::LDSP => 244,
+            Instruction::STSP => 245,
+            Instruction::LDFP => 246,
+            Instruction::STFP => 247,
+            Instruction::LDRS => 248,
+            Instruction::STRS => 249,
+            Instruction::JIND => 14,
+            Instruction::RTI => 250,
+        }
+    }
+}
+
+/*
+ * The operand of an instruction word. Instructions with 4 bit opcodes have 20 bit operands, the
+ * extended ones only 16 bits, so the width depends on the instruction.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Operand {
+    value: usize,
+    bits: u32,
+}
+
+impl Operand {
+    // Fails if the value doesn't fit into the operand of the instruction.
+    pub fn new(instruction: Instruction, value: usize) -> Option<Operand> {
+        (value <= instruction.max_operand()).then_some(Operand { value, bits: instruction.operand_bits() })
+    }
+    pub fn value(self) -> usize {
+        self.value
+    }
+    pub fn bits(self) -> u32 {
+        self.bits
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    // Only new and with_value build commands outside of the crate, so the operand always fits.
+    pub(crate) instruction: Instruction,
+    pub(crate) value: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Command {
+    pub fn get_instruction(&self) -> Instruction {
+        self.instruction
+    }
+    pub fn get_value(&self) -> usize {
+        self.value
+    }
+    pub fn get_mnemonic(&self) -> String {
+        self.instruction.mnemonic().to_string()
+    }
+    // Assembly representation like "LDV 5"
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = toString))]
+    pub fn to_assembly(&self) -> String {
+        self.to_string()
+    }
+    pub fn from_usize(v: usize) -> Option<Command> {
+        if v >= VALUE_SIZE {
+            return None;
+        }
+        // The 4 most significant bits are the opcode, 0b1111 extends it to 8 bits.
+        let (opcode, value) = match v >> 20 {
+            0b1111 => (v >> 16, v & 0xFFFF),
+            opcode => (opcode, v & 0xFFFFF),
+        };
+        Instruction::from_opcode(opcode).map(|instruction| Command { instruction, value })
+    }
+    /*
+     * Renders the command for display: "LDV 3", "LDV 0x00003" or the encoding like
+     * "0001 | 0000 0000 0000 0000 0011". Hex operands are padded to the width of the operand.
+     */
+    pub fn format(&self, style: WordStyle) -> String {
+        let bits = self.instruction.operand_bits();
+        match style {
+            WordStyle::Decimal => self.to_string(),
+            WordStyle::Hex if self.instruction.takes_operand() => {
+                format!("{} 0x{:0width$X}", self.instruction.mnemonic(), self.value, width = bits.div_ceil(4) as usize)
+            }
+            WordStyle::Hex => self.instruction.mnemonic().to_string(),
+            WordStyle::Binary => format!(
+                "{} | {}",
+                bit_groups(self.instruction.to_opcode(), WORD_BITS - bits),
+                bit_groups(self.value, bits)
+            ),
+        }
+    }
+    pub fn to_usize(&self) -> usize {
+        // The check guards commands built by hand inside of the crate.
+        debug_assert!(self.operand().is_some(), "operand of {self} doesn't fit into the instruction");
+        let bits = self.instruction.operand_bits();
+        (self.instruction.to_opcode() << bits) | (self.value & self.instruction.max_operand())
+    }
+}
+
+impl Command {
+    // Fails if the value doesn't fit into the operand of the instruction.
+    pub fn new(instruction: Instruction, value: usize) -> Option<Command> {
+        Operand::new(instruction, value).map(|operand| Command { instruction, value: operand.value() })
+    }
+    // The same instruction with another operand, e.g. after relocating it
+    pub fn with_value(&self, value: usize) -> Option<Command> {
+        Command::new(self.instruction, value)
+    }
+    pub fn operand(&self) -> Option<Operand> {
+        Operand::new(self.instruction, self.value)
+    }
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.instruction.takes_operand() {
+            write!(f, "{} {}", self.instruction.mnemonic(), self.value)
+        } else {
+            write!(f, "{}", self.instruction.mnemonic())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compiler::{compiler, CompilerOutput}, mima::{from_signed, to_signed, Command, Instruction, MachineProfile, Operand}};
+
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use proptest::prelude::*;
+
+    use super::{
+        format_word, get_instruction_set, CancellationHandle, HaltKind, HaltReason, MemoryWrite, Mima, Observer, OperandKind, ReadCheck,
+        StepResult, WordStyle,
+    };
+    use crate::devices::InputLog;
+    use crate::microcode::{self, AluOperation, ControlStore, MicroPhase, Register};
+
+    #[test]
+    fn command_loading() {
+        //TODO: Expand tests
+        let testcode = 0b000100000000000000000001;
+        let cmd = Command::from_usize(testcode);
+
+        assert_eq!(
+            cmd,
+            Some(Command {
+                instruction: Instruction::LDV,
+                value: 1
+            })
+        );
+        let cmd = cmd.unwrap();
+        assert_eq!((cmd.get_instruction(), cmd.get_value()), (Instruction::LDV, 1));
+    }
+
+    #[test]
+    fn command_dumping() {
+        let testcode = 0b000100000000000000000001;
+        let cmd = Command {
+            instruction: Instruction::LDV,
+            value: 1,
+        };
+
+        assert_eq!(cmd.to_usize(), testcode);
+    }
+    #[test]
+    fn long_opcodes() {
+        let halt_code = 0b111100000000000000000001;
+        let cmd = Command {
+            instruction: Instruction::HALT,
+            value: 1,
+        };
+        assert_eq!(Command::from_usize(halt_code).unwrap(), cmd);
+        assert_eq!(cmd.to_usize(), halt_code);
+    }
+    #[test]
+    fn words_beyond_memory_size() {
+        assert_eq!(Command::from_usize(super::VALUE_SIZE), None);
+        assert_eq!(Command::from_usize(super::VALUE_SIZE + 1), None);
+    }
+    fn commands() -> impl Strategy<Value = Command> {
+        (0usize..256).prop_filter_map("unknown opcode", Instruction::from_opcode).prop_flat_map(|instruction| {
+            (0..1usize << instruction.operand_bits()).prop_map(move |value| Command { instruction, value })
+        })
+    }
+    proptest! {
+        #[test]
+        fn command_round_trip(command in commands()) {
+            let word = command.to_usize();
+            prop_assert!(word < super::VALUE_SIZE);
+            prop_assert_eq!(Command::from_usize(word), Some(command));
+        }
+        #[test]
+        fn word_round_trip(word in 0..super::VALUE_SIZE) {
+            if let Some(command) = Command::from_usize(word) {
+                prop_assert_eq!(command.to_usize(), word);
+            }
+        }
+        #[test]
+        fn words_out_of_range(word in super::VALUE_SIZE..usize::MAX) {
+            prop_assert_eq!(Command::from_usize(word), None);
+        }
+    }
+    #[test]
+    fn operand_widths() {
+        assert_eq!(Operand::new(Instruction::LDV, 0xFFFFF).map(Operand::bits), Some(20));
+        assert_eq!(Operand::new(Instruction::LDRS, 0xFFFF).map(Operand::value), Some(0xFFFF));
+        assert_eq!(Operand::new(Instruction::LDRS, 0x10000), None);
+        assert_eq!(Command::new(Instruction::JMP, super::MEMORY_SIZE), None);
+        let command = Command::new(Instruction::STRS, 2).unwrap();
+        assert_eq!(command.with_value(0x10000), None);
+        // The compiler rejects operands that only fit into 20 bits for extended instructions.
+        assert!(compiler::compile("LDRS 0xFFFF\nHALT").is_ok());
+        assert!(compiler::compile("LDRS 0x10000\nHALT").is_err());
+    }
+    #[test]
+    fn decode_cache_collisions() {
+        // Both words share a slot of the cache and replace each other.
+        let output = compiler::compile("LDC 1\nLDC 0x401\nLDC 1\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        for expected in [1, 0x401, 1] {
+            mima.step();
+            assert_eq!(mima.akku, expected);
+        }
+    }
+    #[test]
+    fn rar_rotates_within_24_bits() {
+        let output = compiler::compile("LDC 1\nRAR\nRAR\nNOT\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.step();
+        mima.step();
+        // The low bit moves into the sign bit of the word.
+        assert_eq!(mima.akku, 0x800000);
+        mima.step();
+        assert_eq!(mima.akku, 0x400000);
+        mima.step();
+        assert_eq!(mima.akku, 0xBFFFFF);
+        // A full rotation through the micro architecture gives back the original value.
+        let source = std::iter::repeat_n("RAR", 24).collect::<Vec<&str>>().join("\n") + "\nHALT";
+        let mut mima = Mima::new();
+        mima.load(compiler::compile(&source).unwrap());
+        mima.set_akku(0x123457);
+        while !mima.get_debug().halt {
+            mima.micro_step();
+        }
+        assert_eq!(mima.akku, 0x123457);
+    }
+    proptest! {
+        // Every case allocates a whole machine, so fewer cases are run.
+        #![proptest_config(ProptestConfig::with_cases(32))]
+        #[test]
+        fn rar_and_not_stay_within_word(akku in 0..super::VALUE_SIZE) {
+            let output = compiler::compile("RAR\nNOT\nHALT").unwrap();
+            let mut mima = Mima::new();
+            mima.load(output);
+            mima.set_akku(akku);
+            mima.step();
+            prop_assert!(mima.akku < super::VALUE_SIZE);
+            prop_assert_eq!(mima.akku, (akku >> 1) | ((akku & 1) << 23));
+            mima.step();
+            prop_assert!(mima.akku < super::VALUE_SIZE);
+        }
+    }
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+        // Whatever a small memory holds, executing it may halt the machine but never panics.
+        #[test]
+        fn random_images_never_panic(
+            words in proptest::collection::vec(prop_oneof![commands().prop_map(|command| command.to_usize()), 0..super::VALUE_SIZE], 1..63),
+            start in 0usize..64,
+            micro in any::<bool>(),
+        ) {
+            let mut mima = Mima::with_config(64, 24).unwrap();
+            mima.load(CompilerOutput::new(words, start));
+            for _ in 0..500 {
+                if micro {
+                    mima.micro_step();
+                } else {
+                    mima.step();
+                }
+            }
+        }
+    }
+    #[test]
+    fn mima_add_program() {
+        let ldv = Command {instruction: crate::mima::Instruction::LDV, value: 0};
+        let add = Command {instruction: crate::mima::Instruction::ADD, value: 1};
+        let stv = Command {instruction: crate::mima::Instruction::STV, value: 2};
+        let halt = Command {instruction: crate::mima::Instruction::HALT, value: 0};
+        let mima_code = vec![22, 20, 0, ldv.to_usize(), add.to_usize(), stv.to_usize(), halt.to_usize()];
+        let compiler_output = CompilerOutput::new(mima_code, 3);
+
+        let mut mima = Mima::new();
+        mima.load(compiler_output);
+        mima.run();
+        assert!(mima.halt);
+        assert_eq!(mima.akku, 42);
+        assert_eq!(mima.iar, 6);
+        assert_eq!(mima.read_adress(2), Some(42));
+    }
+    #[test]
+    fn mima_loop_program() {
+        // A simple loop program that counts to 100
+        let assembly_source = "one: DS 1
+max: DS 100
+counter: DS
+START: LDV one
+STV counter
+LOOP: LDV counter
+ADD one
+STV counter
+LDV max
+EQL counter
+JMN FINISH
+JMP LOOP
+FINISH: HALT
+";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert!(mima.halt);
+        // Check if the program counted to 100 at the given adress.
+        assert_eq!(mima.read_adress(2), Some(100));
+    }
+    #[test]
+    fn signed_interpretation() {
+        assert_eq!(to_signed(0), 0);
+        assert_eq!(to_signed(0x7FFFFF), 8388607);
+        assert_eq!(to_signed(0x800000), -8388608);
+        assert_eq!(to_signed(0xFFFFFF), -1);
+        assert_eq!(from_signed(-1), 0xFFFFFF);
+        assert_eq!(from_signed(-8388608), 0x800000);
+        assert_eq!(from_signed(42), 42);
+        for value in [-8388608, -3, 0, 8388607] {
+            assert_eq!(to_signed(from_signed(value)), value);
+        }
+    }
+    #[test]
+    fn add_overflow_wraps() {
+        let output = compiler::compile("max: DS 16777215\ntwo: DS 2\nLDV max\nADD two\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(mima.akku, 1);
+    }
+    #[test]
+    fn negative_results() {
+        // 3 - 5 computed as 3 + NOT(5) + 1
+        let assembly_source = "a: DS 3
+b: DS 5
+one: DS 1
+LDV b
+NOT
+ADD one
+ADD a
+HALT";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(to_signed(mima.akku), -2);
+        assert!(mima.akku < super::VALUE_SIZE);
+    }
+    #[test]
+    fn jmn_after_wraparound() {
+        // 0x7FFFFF + 1 overflows into the sign bit and must be taken as negative.
+        let assembly_source = "big: DS 8388607
+one: DS 1
+result: DS
+LDV big
+ADD one
+JMN NEG
+HALT
+NEG: STV result
+HALT";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(mima.read_adress(2), Some(0x800000));
+        assert_eq!(to_signed(0x800000), -8388608);
+    }
+    #[test]
+    fn eql_yields_minus_one() {
+        let output = compiler::compile("a: DS 4\nLDV a\nEQL a\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(to_signed(mima.akku), -1);
+        assert_eq!(mima.get_debug().akku_signed, -1);
+    }
+    #[test]
+    fn table_indexing() {
+        // Copies tbl[2] to tbl[0] through pointers
+        let assembly_source = "tbl: DS 4, 8, 15, 16
+src: DS 2
+dst: DS 0
+LDIV src
+STIV dst
+HALT";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(mima.memdump()[0..4], [15, 8, 15, 16]);
+    }
+    #[test]
+    fn sub_pseudo_instruction() {
+        let output = compiler::compile("a: DS 10\nb: DS 13\nLDV a\nSUB b\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(to_signed(mima.akku), -3);
+    }
+    #[test]
+    fn symbolic_memory_access() {
+        let output = compiler::compile("counter: DS 3\nLDV counter\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert_eq!(mima.read_symbol("counter"), Some(3));
+        assert!(mima.write_symbol("counter", 5));
+        assert_eq!(mima.read_adress(0), Some(5));
+        mima.run();
+        assert_eq!(mima.akku, 5);
+        // Symbols are case-sensitive and unknown ones are rejected.
+        assert_eq!(mima.read_symbol("Counter"), None);
+        assert!(!mima.write_symbol("missing", 1));
+        // Values still have to fit into a memory word.
+        assert!(!mima.write_symbol("counter", super::VALUE_SIZE));
+
+        // Loading a new program replaces the old symbols.
+        let output = compiler::compile("renamed: DS 7\nHALT").unwrap();
+        mima.load(output);
+        assert_eq!(mima.read_symbol("counter"), None);
+        assert_eq!(mima.read_symbol("renamed"), Some(7));
+    }
+    #[test]
+    fn symbols_without_program_source() {
+        let mut mima = Mima::new();
+        mima.load(CompilerOutput::new(vec![0, 0xF00000], 1));
+        assert_eq!(mima.read_symbol("counter"), None);
+        assert!(!mima.write_symbol("counter", 1));
+    }
+    #[test]
+    fn nested_calls() {
+        let assembly_source = "x: DS 0
+one: DS 1
+START: CALL OUTER
+STV x
+HALT
+OUTER: CALL INNER
+CALL INNER
+RET
+INNER: LDV x
+ADD one
+STV x
+RET";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert_eq!(mima.get_debug().sp, super::MEMORY_SIZE);
+        // Inside INNER two return adresses are on the stack.
+        for _ in 0..3 {
+            mima.step();
+        }
+        assert_eq!(mima.sp, super::MEMORY_SIZE - 2);
+        assert_eq!(mima.read_adress(super::MEMORY_SIZE - 1), Some(3));
+        assert_eq!(mima.read_adress(super::MEMORY_SIZE - 2), Some(6));
+        mima.run();
+        assert_eq!(mima.read_adress(0), Some(2));
+        assert_eq!(mima.sp, super::MEMORY_SIZE);
+        assert_eq!(mima.iar, 4);
+    }
+    #[test]
+    fn ret_on_empty_stack_halts() {
+        let output = compiler::compile("RET\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.step();
+        assert!(mima.halt);
+        assert_eq!(mima.iar, 0);
+        assert_eq!(mima.get_debug().halt_reason, HaltReason::adress_out_of_range(Some(super::MEMORY_SIZE)));
+    }
+    #[test]
+    fn stack_frames() {
+        // Passes an argument on the stack to a function that doubles it in place.
+        let assembly_source = "arg: DS 21
+tmp: DS
+START: LDSP
+ADC -1
+STSP
+LDV arg
+STRS 0
+CALL DOUBLE
+LDRS 0
+STV arg
+LDSP
+ADC 1
+STSP
+HALT
+DOUBLE: LDSP
+STFP
+LDRS 1
+STV tmp
+ADD tmp
+STRS 1
+RET";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(mima.read_adress(0), Some(42));
+        assert_eq!(mima.sp, super::MEMORY_SIZE);
+        // The frame pointer was set inside DOUBLE below the return adress.
+        assert_eq!(mima.get_debug().fp, super::MEMORY_SIZE - 2);
+    }
+    #[test]
+    fn adc_negative_constant() {
+        let output = compiler::compile("LDC 2\nADC -5\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(to_signed(mima.akku), -3);
+    }
+    #[test]
+    fn jump_table() {
+        let assembly_source = "target: DS
+result: DS
+START: LDC SECOND
+STV target
+JIND target
+FIRST: LDC 1
+STV result
+HALT
+SECOND: LDC 2
+STV result
+HALT";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.run();
+        assert_eq!(mima.read_adress(1), Some(2));
+    }
+    #[test]
+    fn illegal_instruction_traps() {
+        let output = compiler::compile("LDC 1\nADC 1\nHALT").unwrap();
+        let mut mima = Mima::with_profile(MachineProfile::Basic);
+        mima.load(output);
+        mima.run();
+        // Execution stops in front of ADC
+        assert_eq!(mima.get_debug().iar, 1);
+        let adc = Command { instruction: Instruction::ADC, value: 1 }.to_usize();
+        assert_eq!(mima.get_debug().halt_reason, HaltReason::illegal_instruction(1, adc));
+        assert_eq!(mima.akku, 1);
+        assert!(MachineProfile::Extended.supports(Instruction::ADC));
+        assert!(!MachineProfile::Extended.supports(Instruction::CALL));
+    }
+    #[test]
+    fn micro_steps_match_steps() {
+        let assembly_source = "a: DS 5
+b: DS 7
+ptr: DS 1
+START: LDV a
+ADD b
+NOT
+RAR
+STIV ptr
+LDIV ptr
+JMN END
+LDC 3
+END: HALT";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut expected = Mima::new();
+        expected.load(compiler::compile(assembly_source).unwrap());
+        expected.run();
+        let mut mima = Mima::new();
+        mima.load(output);
+        // After the fetch phase IR holds the instruction and IAR points behind it.
+        for _ in 0..5 {
+            assert!(!mima.micro_step());
+        }
+        let debug = mima.get_micro_debug();
+        assert_eq!(debug.ir, Command { instruction: Instruction::LDV, value: 0 }.to_usize());
+        assert_eq!(debug.sar, 3);
+        assert_eq!(mima.get_debug().iar, 4);
+        let mut cycles = 5;
+        while !mima.get_debug().halt {
+            mima.micro_step();
+            cycles += 1;
+        }
+        assert_eq!(mima.akku, expected.akku);
+        assert_eq!(mima.read_adress(1), expected.read_adress(1));
+        assert_eq!(mima.get_debug().iar, expected.get_debug().iar);
+        assert!(cycles > 50);
+    }
+    #[test]
+    fn step_finishes_started_instruction() {
+        let output = compiler::compile("a: DS 2\nLDV a\nADD a\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.step();
+        for _ in 0..8 {
+            mima.micro_step();
         }
+        mima.step();
+        assert_eq!(mima.akku, 4);
+        assert_eq!(mima.get_micro_debug().phase, crate::microcode::MicroPhase::Fetch);
+        assert_eq!(mima.get_debug().iar, 3);
     }
-}
-
-#[wasm_bindgen]
-#[derive(Clone, Debug, PartialEq)]
-pub struct Command {
-    pub instruction: Instruction,
-    pub value: usize,
-}
-
-#[wasm_bindgen]
-impl Command {
-    pub fn from_usize(v: usize) -> Option<Command> {
-        if v > VALUE_SIZE {
-            None
-        } else {
-            // Convert the 4 most significant bits into opcode
-            let mut opcode: usize = (20..24)
-                .map(|n| (v >> n) & 1)
-                .enumerate()
-                .fold(0, |acc, (index, elem)| {
-                    acc + elem * (2 as usize).pow(index as u32)
-                });
-            // Convert the 20 least significant bits into argument
-            let mut value: usize = (0..20)
-                .map(|n| (v >> n) & 1)
-                .enumerate()
-                .fold(0, |acc, (index, elem)| {
-                    acc + elem * (2 as usize).pow(index as u32)
-                });
-            // Check if we are dealing with 8 bit opcodes
-            if opcode == 0b1111 {
-                opcode = (16..24)
-                .map(|n| (v >> n) & 1)
-                .enumerate()
-                .fold(0, |acc, (index, elem)| {
-                    acc + elem * (2 as usize).pow(index as u32)
-                });
-                dbg!(opcode);
-                value = (0..16)
-                .map(|n| (v >> n) & 1)
-                .enumerate()
-                .fold(0, |acc, (index, elem)| {
-                    acc + elem * (2 as usize).pow(index as u32)
-                });
-            }
-            Instruction::from_opcode(opcode).map(|instruction| Command { instruction, value })
+    #[test]
+    fn micro_trace() {
+        let output = compiler::compile("a: DS 2\nADD a\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert!(mima.get_micro_trace().is_none());
+        mima.micro_step();
+        let trace = mima.get_micro_trace().unwrap();
+        assert_eq!(trace.get_phase(), MicroPhase::Fetch);
+        assert_eq!(trace.get_drivers(), vec![Register::Iar]);
+        assert_eq!(trace.get_latches(), vec![Register::X, Register::Sar]);
+        assert_eq!(trace.get_bus(), Some(1));
+        assert!(trace.is_read());
+        // Fetch, decode and four cycles of loading the operand precede the addition.
+        for _ in 0..10 {
+            mima.micro_step();
         }
+        let trace = mima.get_micro_trace().unwrap();
+        assert_eq!(trace.get_phase(), MicroPhase::Execute);
+        assert_eq!(trace.get_alu(), AluOperation::Add);
+        assert_eq!(trace.get_bus(), None);
+        assert!(trace.get_latches().is_empty());
     }
-    pub fn to_usize(&self) -> usize {
-        let opcode = self.instruction.to_opcode();
-        let mut opcode_bytes: Vec<usize> = (0..4).map(|n| (opcode >> n) & 1).rev().collect();
-        let mut value_bytes: Vec<usize> = (0..20).map(|n| (self.value >> n) & 1).rev().collect();
-        // Check for 8bit opcode
-        if opcode >= 240 {
-            opcode_bytes = (0..8).map(|n| (opcode >> n) & 1).rev().collect();
-            value_bytes = (0..16).map(|n| (self.value >> n) & 1).rev().collect();
-        }
-        opcode_bytes
-            .iter()
-            .chain(value_bytes.iter())
-            .rev()
-            .enumerate()
-            .fold(0, |acc, (index, elem)| {
-                acc + elem * (2 as usize).pow(index as u32)
-            })
+    #[test]
+    fn modified_microprogram() {
+        let output = compiler::compile("a: DS 5\nb: DS 3\nLDV a\nADD b\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        // Turn ADD into a subtraction: X + NOT Y + 1
+        let mut store = mima.get_control_store();
+        store.set_microprogram(
+            Instruction::ADD,
+            vec![
+                microcode::IW | microcode::S | microcode::R,
+                microcode::R,
+                microcode::R,
+                microcode::DW | microcode::X,
+                microcode::ALU_NOT,
+                microcode::Z | microcode::X,
+                microcode::E | microcode::Y,
+                microcode::ALU_ADD,
+                microcode::Z | microcode::X,
+                microcode::AW | microcode::Y,
+                microcode::ALU_ADD,
+                microcode::Z | microcode::AR,
+            ],
+        );
+        assert!(!store.set_fetch(vec![]));
+        mima.set_control_store(store);
+        while !mima.get_debug().halt {
+            mima.micro_step();
+        }
+        assert_eq!(mima.akku, 2);
+        assert!(mima.get_control_store().to_table().contains("ADD   11  8800000  Ar Z"));
+    }
+    #[test]
+    fn control_store_swapped_during_fetch() {
+        let mut mima = Mima::new();
+        mima.load(compiler::compile("LDC 1\nHALT").unwrap());
+        for _ in 0..3 {
+            mima.micro_step();
+        }
+        let mut store = ControlStore::new();
+        let fetch = store.get_fetch();
+        assert!(store.set_fetch(fetch[..2].to_vec()));
+        mima.set_control_store(store);
+        assert!(!mima.micro_step());
+        assert_eq!(mima.get_micro_debug().phase, MicroPhase::Fetch);
+        // The interrupted fetch starts over, so LDC is still executed.
+        mima.set_control_store(ControlStore::new());
+        assert_eq!(mima.run(), HaltReason::halted());
+        assert_eq!(mima.akku, 1);
+    }
+    #[test]
+    fn halt_reasons() {
+        let mut mima = Mima::new();
+        assert_eq!(mima.get_debug().halt_reason.kind, HaltKind::NotStarted);
+        mima.load(compiler::compile("LDC 1\nHALT").unwrap());
+        assert_eq!(mima.run(), HaltReason::halted());
+        // Words with an unknown extended opcode don't decode
+        mima.load(compiler::compile("LDC 1\nHALT").unwrap());
+        mima.write_adress(1, 0xFF0000);
+        assert_eq!(mima.run(), HaltReason::illegal_instruction(1, 0xFF0000));
+        // The last cell of memory is executed, then the IAR points outside of memory.
+        let mut mima = Mima::new();
+        mima.write_adress(0, Command { instruction: Instruction::JMP, value: super::MEMORY_SIZE - 1 }.to_usize());
+        mima.write_adress(super::MEMORY_SIZE - 1, Command { instruction: Instruction::LDC, value: 7 }.to_usize());
+        assert_eq!(mima.run(), HaltReason::adress_out_of_range(Some(super::MEMORY_SIZE)));
+        assert_eq!(mima.akku, 7);
+    }
+    #[test]
+    fn step_results() {
+        let output = compiler::compile("a: DS 1\nLDC 5\nSTV a\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        let result = mima.step();
+        assert_eq!(result.get_command(), Some(Command { instruction: Instruction::LDC, value: 5 }));
+        assert_eq!(result.get_adress(), 1);
+        assert_eq!(result.get_written_adress(), None);
+        let result = mima.step();
+        assert_eq!(result.get_written_adress(), Some(0));
+        assert!(!result.is_halt());
+        let result = mima.step();
+        assert!(result.is_halt());
+        assert_eq!(result.get_halt_reason(), HaltReason::halted());
+        // Stepping a halted machine does nothing.
+        assert_eq!(mima.step().get_command(), None);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::{compiler::{compiler, CompilerOutput}, mima::{Command, Instruction}};
 
-    use super::Mima;
+    #[test]
+    fn format_commands() {
+        let ldv = Command::new(Instruction::LDV, 3).unwrap();
+        assert_eq!(ldv.format(WordStyle::Decimal), "LDV 3");
+        assert_eq!(ldv.format(WordStyle::Hex), "LDV 0x00003");
+        assert_eq!(ldv.format(WordStyle::Binary), "0001 | 0000 0000 0000 0000 0011");
+        let strs = Command::new(Instruction::STRS, 0xAB).unwrap();
+        assert_eq!(strs.format(WordStyle::Hex), "STRS 0x00AB");
+        assert_eq!(strs.format(WordStyle::Binary), "1111 1001 | 0000 0000 1010 1011");
+        assert_eq!(Command::new(Instruction::HALT, 0).unwrap().format(WordStyle::Hex), "HALT");
+        assert_eq!(format_word(42, WordStyle::Decimal), "42");
+        assert_eq!(format_word(42, WordStyle::Hex), "0x00002A");
+        assert_eq!(format_word(42, WordStyle::Binary), "0000 0000 0000 0000 0010 1010");
+    }
 
     #[test]
-    fn command_loading() {
-        //TODO: Expand tests
-        let testcode = 0b000100000000000000000001;
-        let cmd = Command::from_usize(testcode);
+    fn instruction_set() {
+        let instructions = get_instruction_set();
+        assert_eq!(instructions.len(), 26);
+        assert!(instructions.windows(2).all(|pair| pair[0].opcode < pair[1].opcode));
+        let find = |mnemonic: &str| instructions.iter().find(|info| info.get_mnemonic() == mnemonic).unwrap().to_owned();
+        let ldc = find("LDC");
+        assert_eq!((ldc.opcode, ldc.operand, ldc.operand_bits), (0, OperandKind::Constant, 20));
+        let call = find("CALL");
+        assert_eq!((call.operand, call.operand_bits), (OperandKind::Adress, 20));
+        let halt = find("HALT");
+        assert_eq!((halt.opcode, halt.operand, halt.operand_bits), (240, OperandKind::None, 0));
+        assert_eq!(find("STRS").operand_bits, 16);
+        assert_eq!(halt.get_description(), "Stops the machine");
+        assert_eq!(find("LDRS").get_description(), "Loads the value at the stack pointer plus the offset");
+    }
 
-        assert_eq!(
-            cmd,
-            Some(Command {
-                instruction: Instruction::LDV,
-                value: 1
-            })
-        );
+    #[test]
+    fn batch_steps() {
+        let output = compiler::compile("a: DS 1\nb: DS 1\nLDC 5\nSTV a\nSTV b\nADD a\nSTV a\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        let batch = mima.step_n(5);
+        assert_eq!((batch.steps, batch.akku, batch.iar, batch.halt), (5, 10, 7, false));
+        // Cells written more than once are reported with their final value.
+        assert_eq!(batch.get_writes(), vec![MemoryWrite { adress: 0, value: 10 }, MemoryWrite { adress: 1, value: 5 }]);
+        let batch = mima.step_n(100);
+        assert_eq!(batch.steps, 1);
+        assert!(batch.get_writes().is_empty());
+        assert_eq!(batch.halt_reason, HaltReason::halted());
+        assert_eq!(mima.step_n(10).steps, 0);
+    }
+    #[test]
+    fn trace_ring_buffer() {
+        let output = compiler::compile("LDC 1\nLDC 2\nLDC 3\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.step();
+        assert!(mima.get_trace().is_empty());
+        mima.set_trace_capacity(2);
+        mima.run();
+        let trace = mima.get_trace();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[0].get_adress(), 2);
+        assert_eq!(trace[0].get_akku_before(), 2);
+        assert_eq!(trace[0].get_akku_after(), 3);
+        assert_eq!(trace[1].get_command().instruction, Instruction::HALT);
+        mima.set_trace_capacity(0);
+        assert!(mima.get_trace().is_empty());
+    }
+    #[test]
+    fn profiler_hot_spots() {
+        let assembly_source = "count: DS 3
+minus: DS -1
+START: LDV count
+LOOP: ADD minus
+JMN END
+JMP LOOP
+END: HALT";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.set_profiling(true);
+        mima.run();
+        let profile = mima.get_profile();
+        assert_eq!(profile[0].get_adress(), 3);
+        assert_eq!(profile[0].get_count(), 4);
+        assert_eq!(profile[0].get_location(), Some("LOOP".to_string()));
+        assert_eq!(profile[1].get_location(), Some("LOOP+1".to_string()));
+        assert_eq!(profile.iter().map(|entry| entry.get_count()).sum::<u64>(), 1 + 4 * 2 + 3 + 1);
+        assert!(mima.get_profile_report().contains("00003           4  LOOP"));
+    }
+    #[test]
+    fn register_setters() {
+        let output = compiler::compile("a: DS 1\nSTV a\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert!(mima.set_akku(42));
+        assert!(!mima.set_akku(super::VALUE_SIZE));
+        assert!(!mima.set_iar(super::MEMORY_SIZE));
+        assert_eq!(mima.get_akku(), 42);
+        mima.run();
+        assert_eq!(mima.read_adress(0), Some(42));
+        // Run the program again after halting
+        mima.clear_halt();
+        assert!(mima.set_iar(1));
+        assert!(mima.set_akku(7));
+        assert_eq!(mima.get_iar(), 1);
+        mima.run();
+        assert_eq!(mima.read_adress(0), Some(7));
+    }
+    #[test]
+    fn relocated_loading() {
+        let assembly_source = "a: DS -1
+START: LDV a
+LOOP: ADD a
+JMN END
+JMP LOOP
+END: HALT";
+        let output = compiler::compile(assembly_source).unwrap();
+        assert_eq!(output.get_relocations(), vec![1, 2, 3, 4]);
+        let mut mima = Mima::new();
+        mima.write_adress(0, 99);
+        assert!(mima.load_at(output, 0x100));
+        assert_eq!(mima.get_iar(), 0x101);
+        assert_eq!(mima.read_symbol("a"), Some(0xFFFFFF));
+        mima.run();
+        assert_eq!(mima.get_debug().halt_reason, HaltReason::halted());
+        assert_eq!(mima.get_iar(), 0x105);
+        // Memory outside of the program is untouched
+        assert_eq!(mima.read_adress(0), Some(99));
+        // Relocated operands have to fit into the instruction.
+        let output = compiler::compile("a: DS\nSTART: LDV a\nHALT").unwrap();
+        assert!(!mima.load_at(output, super::MEMORY_SIZE - 2));
+        // Pointers move with the program as well.
+        let output = compiler::compile("ptr: DS tbl\ntbl: DS 5\nSTART: LDIV ptr\nHALT").unwrap();
+        assert_eq!(output.get_data_relocations(), vec![0]);
+        let mut mima = Mima::new();
+        assert!(mima.load_at(output, 100));
+        assert_eq!(mima.read_adress(100), Some(101));
+        mima.run();
+        assert_eq!(mima.get_akku(), 5);
+    }
+    #[test]
+    fn raw_loading() {
+        let mut mima = Mima::new();
+        let words = vec![5, Command { instruction: Instruction::LDV, value: 0 }.to_usize(), 0xF00000];
+        assert!(mima.load_raw(words, 1));
+        assert_eq!(mima.run(), HaltReason::halted());
+        assert_eq!(mima.get_akku(), 5);
+        assert!(!mima.load_raw(vec![super::VALUE_SIZE], 0));
+        assert!(!mima.load_raw(vec![], super::MEMORY_SIZE));
+    }
+    #[test]
+    fn dirty_addresses() {
+        let output = compiler::compile("a: DS 5\nb: DS\nLDV a\nSTV b\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert_eq!(mima.take_dirty_addresses(), vec![0, 1, 2, 3, 4]);
+        mima.run();
+        assert_eq!(mima.take_dirty_addresses(), vec![1]);
+        assert_eq!(mima.take_dirty_addresses(), Vec::<usize>::new());
+        mima.write_adress(7, 3);
+        mima.reset();
+        assert_eq!(mima.take_dirty_addresses(), vec![0, 1, 2, 3, 4, 7]);
+    }
+    #[test]
+    fn preserve_memory_on_load() {
+        let output = compiler::compile("n: DS\nk: DS 7\nresult: DS\nSTART: LDV n\nADD k\nSTV result\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.set_read_check(ReadCheck::Trap);
+        mima.write_adress(0, 5);
+        mima.write_adress(1, 100);
+        mima.write_adress(20, 9);
+        assert!(mima.load_preserving_memory(output.to_owned()));
+        // Initialized words of the program are written anyway.
+        assert_eq!(mima.memdump()[..3], [5, 7, 0]);
+        assert_eq!(mima.read_adress(20), Some(9));
+        assert_eq!(mima.run(), HaltReason::halted());
+        assert_eq!(mima.read_symbol("result"), Some(12));
+        // A plain load clears the input again.
+        mima.load(output);
+        assert_eq!(mima.run().kind, HaltKind::UninitializedRead);
+    }
+    #[test]
+    fn hot_reload() {
+        let program = "n: DS\nk: DS 1\nresult: DS\nSTART: LDV n\nADD n\nSTV result\nHALT";
+        let edited = "n: DS\nk: DS 1\nresult: DS\nSTART: LDV n\nADD k\nADC 10\nSTV result\nHALT";
+        let mut mima = Mima::new();
+        mima.load(compiler::compile(program).unwrap());
+        mima.write_symbol("n", 4);
+        mima.write_symbol("k", 2);
+        mima.step();
+        let patched = mima.hot_reload(compiler::compile(edited).unwrap(), true).unwrap();
+        assert_eq!(patched, vec![4, 5, 6, 7]);
+        // Still on line 5, with the akku and the variables set before
+        assert_eq!((mima.get_iar(), mima.get_akku()), (4, 4));
+        mima.run();
+        assert_eq!(mima.read_symbol("result"), Some(16));
+        // Variables are found by name when the layout changes.
+        let moved = format!("extra: DS 9\n{edited}");
+        mima.hot_reload(compiler::compile(&moved).unwrap(), false).unwrap();
+        assert_eq!(mima.get_iar(), 4);
+        assert_eq!((mima.read_symbol("extra"), mima.read_symbol("n"), mima.read_symbol("result")), (Some(9), Some(4), Some(16)));
+        assert_eq!(mima.run(), HaltReason::halted());
     }
 
     #[test]
-    fn command_dumping() {
-        let testcode = 0b000100000000000000000001;
-        let cmd = Command {
-            instruction: Instruction::LDV,
-            value: 1,
-        };
+    fn watches() {
+        let output = compiler::compile("tbl: DS 1, 2, 3, -4\ncounter: DS 5\nmax: DS 7\nSTART: LDC 3\nADD counter\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        for expression in ["counter", "mem[tbl+3]", "akku - max", "START", "mem[counter] * 2", "missing", "mem[0-1]"] {
+            mima.add_watch(expression).unwrap();
+        }
+        assert!(mima.add_watch("mem[1").is_err());
+        mima.step();
+        let values: Vec<Option<i64>> = mima.step().get_watches().iter().map(|watch| watch.value).collect();
+        assert_eq!(values, vec![Some(5), Some(-4), Some(1), Some(6), Some(10), None, None]);
+        let watches = mima.get_watches();
+        assert_eq!(watches[2].get_expression(), "akku - max");
+        assert_eq!(watches[5].get_error().as_deref(), Some("Couldn't find symbol 'missing'."));
+        assert_eq!(watches[6].get_error().as_deref(), Some("Adress '-1' is out of range."));
+        assert!(mima.remove_watch(0));
+        assert_eq!(mima.step_n(1).get_watches()[0].get_expression(), "mem[tbl+3]");
+        mima.clear_watches();
+        assert!(mima.step().get_watches().is_empty());
+    }
 
-        assert_eq!(cmd.to_usize(), testcode);
+    #[test]
+    fn callback_throttling() {
+        let output = compiler::compile("a: DS\nLDC 1\nSTV a\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        let steps = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&steps);
+        mima.set_step_hook(Some(Box::new(move |_| counter.set(counter.get() + 1))));
+        let writes = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&writes);
+        mima.set_memory_write_hook(Some(Box::new(move |adress, value| counter.set(counter.get() + adress + value))));
+        mima.set_run_callback_interval(0);
+        assert_eq!(mima.run(), HaltReason::halted());
+        assert_eq!((steps.get(), writes.get()), (0, 0));
+        // Single steps are reported again after run.
+        mima.load(compiler::compile("a: DS\nLDC 1\nSTV a\nHALT").unwrap());
+        mima.step();
+        mima.step();
+        assert_eq!((steps.get(), writes.get()), (2, 1));
+        mima.set_run_callback_interval(2);
+        mima.run();
+        assert_eq!(steps.get(), 3);
     }
     #[test]
-    fn long_opcodes() {
-        let halt_code = 0b111100000000000000000001;
-        let cmd = Command {
-            instruction: Instruction::HALT,
-            value: 1,
-        };
-        assert_eq!(Command::from_usize(halt_code).unwrap(), cmd);
-        assert_eq!(cmd.to_usize(), halt_code);
+    fn observers() {
+        #[derive(Default)]
+        struct Recorder {
+            events: Rc<RefCell<Vec<String>>>,
+        }
+        impl Observer for Recorder {
+            fn on_step(&mut self, result: &StepResult) {
+                self.events.borrow_mut().push(format!("step {}", result.get_adress()));
+            }
+            fn on_memory_write(&mut self, adress: usize, value: usize) {
+                self.events.borrow_mut().push(format!("write {adress} {value}"));
+            }
+            fn on_halt(&mut self, reason: HaltReason) {
+                self.events.borrow_mut().push(format!("halt {:?}", reason.kind));
+            }
+        }
+        let mut mima = Mima::new();
+        mima.load(compiler::compile("a: DS\nLDC 1\nSTV a\nHALT").unwrap());
+        let recorder = Recorder::default();
+        let events = Rc::clone(&recorder.events);
+        let id = mima.add_observer(Box::new(recorder));
+        // Observers that only implement some of the events
+        struct Nothing;
+        impl Observer for Nothing {}
+        mima.add_observer(Box::new(Nothing));
+        while !mima.step().is_halt() {}
+        assert_eq!(*events.borrow(), vec!["step 1", "write 0 1", "step 2", "halt Halted", "step 3"]);
+        assert!(mima.remove_observer(id).is_some());
+        assert!(mima.remove_observer(id).is_none());
+        mima.load(compiler::compile("HALT").unwrap());
+        mima.run();
+        assert_eq!(events.borrow().len(), 5);
     }
     #[test]
-    fn mima_add_program() {
-        let ldv = Command {instruction: crate::mima::Instruction::LDV, value: 0};
-        let add = Command {instruction: crate::mima::Instruction::ADD, value: 1};
-        let stv = Command {instruction: crate::mima::Instruction::STV, value: 2};
-        let halt = Command {instruction: crate::mima::Instruction::HALT, value: 0};
-        let mima_code = vec![22, 20, 0, ldv.to_usize(), add.to_usize(), stv.to_usize(), halt.to_usize()];
-        let compiler_output = CompilerOutput::new(mima_code, 3);
-
+    fn fuel() {
         let mut mima = Mima::new();
-        mima.load(compiler_output);
+        mima.load(compiler::compile("LOOP: JMP LOOP").unwrap());
+        mima.set_fuel(Some(10));
+        mima.step();
+        assert_eq!(mima.run_chunk(5), 5);
+        assert_eq!(mima.get_remaining_fuel(), Some(4));
+        assert_eq!(mima.run(), HaltReason::out_of_fuel());
+        assert_eq!(mima.get_instruction_count(), 10);
+        // Loading another program doesn't refill the tank.
+        mima.load(compiler::compile("HALT").unwrap());
+        assert_eq!(mima.run(), HaltReason::out_of_fuel());
+        mima.clear_halt();
+        mima.set_fuel(Some(1));
+        assert_eq!(mima.run(), HaltReason::halted());
+        assert_eq!(mima.get_remaining_fuel(), Some(0));
+        mima.set_fuel(None);
+        assert_eq!(mima.get_remaining_fuel(), None);
+    }
+    #[test]
+    fn write_protected_code() {
+        let assembly_source = "a: DS 0\nSTART: LDC 7\nSTV a\nSTV 4\nHALT\nHALT";
+        let mut mima = Mima::new();
+        mima.load(compiler::compile(assembly_source).unwrap());
+        assert_eq!(mima.run(), HaltReason::write_protected(4));
+        assert_eq!(mima.get_iar(), 3);
+        assert_eq!(mima.read_symbol("a"), Some(7));
+        // The frontend may still edit the program.
+        assert!(mima.write_adress(4, 0xF00000));
+        // Self-modifying programs overwrite the first HALT with LDC 7.
+        mima.load(compiler::compile(assembly_source).unwrap());
+        mima.set_self_modifying(true);
+        assert_eq!(mima.run(), HaltReason::halted());
+        assert_eq!(mima.get_iar(), 5);
+    }
+    #[test]
+    fn uninitialized_reads() {
+        let assembly_source = "counter: DS\none: DS 1\nSTART: LDV counter\nADD one\nSTV counter\nLDV counter\nADD 100\nHALT";
+        let mut mima = Mima::new();
+        mima.set_read_check(ReadCheck::Trap);
+        mima.load(compiler::compile(assembly_source).unwrap());
+        assert_eq!(mima.run(), HaltReason::uninitialized_read(0));
+        assert_eq!(mima.get_iar(), 2);
+        mima.set_read_check(ReadCheck::Warn);
+        mima.load(compiler::compile(assembly_source).unwrap());
+        assert_eq!(mima.run(), HaltReason::halted());
+        // Reading the counter after storing it is fine.
+        let reads: Vec<(usize, usize)> = mima.take_uninitialized_reads().iter().map(|read| (read.adress, read.instruction)).collect();
+        assert_eq!(reads, vec![(0, 2), (100, 6)]);
+        assert!(mima.take_uninitialized_reads().is_empty());
+        mima.set_read_check(ReadCheck::Off);
+        mima.load(compiler::compile(assembly_source).unwrap());
         mima.run();
-        assert_eq!(mima.halt, true);
-        assert_eq!(mima.akku, 42);
-        assert_eq!(mima.iar, 6);
-        assert_eq!(mima.read_adress(2), Some(42));
+        assert!(mima.take_uninitialized_reads().is_empty());
     }
     #[test]
-    fn mima_loop_program() {
-        // A simple loop program that counts to 100
-        let assembly_source = "one: DS 1
-max: DS 100
-counter: DS
-START: LDV one
-STV counter
-LOOP: LDV counter
+    fn loop_detection() {
+        let mut mima = Mima::new();
+        mima.set_loop_detection(true);
+        mima.load(compiler::compile("i: DS 0\nSTART: LDC 1\nSTV i\nLOOP: LDV i\nADD i\nJMP LOOP").unwrap());
+        assert_eq!(mima.run(), HaltReason::infinite_loop(3, 5));
+        // A loop that counts changes the memory every time and runs into the step limit instead.
+        mima.load(compiler::compile("i: DS 0\none: DS 1\nLOOP: LDV i\nADD one\nSTV i\nJMP LOOP").unwrap());
+        assert_eq!(mima.run_chunk(10_000), 10_000);
+        assert!(!mima.get_debug().halt);
+        // The loop detection only gets enabled on request.
+        mima.set_loop_detection(false);
+        mima.load(compiler::compile("LOOP: JMP LOOP").unwrap());
+        assert_eq!(mima.run_chunk(100), 100);
+        mima.set_loop_detection(true);
+        assert_eq!(mima.run(), HaltReason::infinite_loop(0, 0));
+    }
+    #[test]
+    fn call_routines() {
+        let source = "EXPORT DOUBLE, TRIANGLE\nn: DS 0\ntmp: DS 0\nDOUBLE: LDV n\nADD n\nRET\n\
+                      TRIANGLE: LDC 0\nSTV tmp\nLOOP: LDV tmp\nCALL ADDN\nSTV tmp\nLDV n\nADC -1\nSTV n\nJMN DONE\nJMP LOOP\n\
+                      DONE: LDV tmp\nRET\nADDN: ADD n\nRET\nSTOP: HALT";
+        let output = compiler::compile(source).unwrap();
+        let entries: Vec<String> = output.get_entry_points().iter().map(|symbol| symbol.get_name()).collect();
+        assert_eq!(entries, vec!["DOUBLE", "TRIANGLE"]);
+        let mut mima = Mima::new();
+        mima.load(output);
+        mima.write_symbol("n", 21);
+        let result = mima.call("DOUBLE", 100).unwrap();
+        assert_eq!((result.steps, result.halt_reason, mima.get_akku()), (2, HaltReason::not_started(), 42));
+        // Nested calls return to the routine, only its own RET ends the call.
+        mima.write_symbol("n", 3);
+        mima.call("TRIANGLE", 1000).unwrap();
+        assert_eq!(mima.get_akku(), 3 + 2 + 1);
+        assert_eq!(mima.get_call_stack(), vec![]);
+        assert_eq!(mima.call("STOP", 10).unwrap().halt_reason, HaltReason::halted());
+        assert_eq!(mima.call("n", 10), None);
+        assert!(mima.call("LOOP", 5).is_some_and(|result| result.steps == 5));
+    }
+    #[test]
+    fn chunked_running() {
+        let output = compiler::compile("LOOP: JMP LOOP").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert_eq!(mima.run_chunk(100), 100);
+        assert!(!mima.get_debug().halt);
+        let output = compiler::compile("LDC 1\nHALT").unwrap();
+        mima.load(output);
+        assert_eq!(mima.run_chunk(100), 2);
+        assert_eq!(mima.get_debug().halt_reason, HaltReason::halted());
+    }
+    #[test]
+    fn cancelled_run() {
+        let output = compiler::compile("LOOP: JMP LOOP").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        let handle = CancellationHandle::new();
+        mima.set_cancellation_handle(Some(handle.clone()));
+        handle.cancel();
+        assert_eq!(mima.run().kind, HaltKind::NotStarted);
+        assert!(!mima.get_debug().halt);
+        // The cancellation was used up by the run.
+        assert!(!handle.is_cancelled());
+        handle.cancel();
+        handle.reset();
+        assert_eq!(mima.run_chunk(1000), 1000);
+    }
+    #[test]
+    fn clock_ticks() {
+        let output = compiler::compile("LOOP: JMP LOOP").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert!(!mima.set_clock_rate(0.0));
+        assert!(mima.set_clock_rate(10.0));
+        assert_eq!(mima.tick(250.0), 2);
+        // The remaining half instruction is executed with the next tick.
+        assert_eq!(mima.tick(50.0), 1);
+        assert_eq!(mima.tick(60_000.0), 10);
+        assert!(mima.set_clock_rate(1_000_000.0));
+        assert_eq!(mima.tick(16.0), 16_000);
+        mima.load(compiler::compile("HALT").unwrap());
+        assert_eq!(mima.tick(16.0), 1);
+        assert_eq!(mima.tick(16.0), 0);
+    }
+    #[test]
+    fn configured_machine() {
+        assert!(Mima::with_config(12, 24).is_err());
+        assert!(Mima::with_config(16, 20).is_err());
+        assert!(Mima::with_config(16, 32).is_err());
+        let mut mima = Mima::with_config(16, 31).unwrap();
+        assert_eq!((mima.get_memory_size(), mima.get_word_bits()), (16, 31));
+        assert_eq!(mima.get_debug().sp, 16);
+        // Sums no longer wrap at 24 bits and ADC extends its sign to the whole word.
+        let output = compiler::compile("x: DS 0xFFFFFF\none: DS 1\nLDV x\nADD one\nADC -1\nADC -2\nHALT").unwrap();
+        assert!(mima.load(output));
+        mima.step();
+        mima.step();
+        assert_eq!(mima.akku, 0x1000000);
+        mima.run();
+        assert_eq!(mima.akku, 0xFFFFFD);
+        assert!(mima.set_akku(0x7FFFFFFF));
+        assert_eq!(mima.get_debug().akku_signed, -1);
+        // Adresses beyond the 16 words halt the machine without changing the akku.
+        assert!(mima.load(compiler::compile("LDC 7\nLDV 20\nHALT").unwrap()));
+        assert_eq!(mima.run(), HaltReason::adress_out_of_range(Some(20)));
+        assert_eq!(mima.akku, 7);
+        assert_eq!(mima.read_adress(16), None);
+        assert!(!mima.load(compiler::compile("ORG 15\nLDC 1\nHALT").unwrap()));
+    }
+    #[test]
+    fn indirection_out_of_range() {
+        // The pointer holds an adress far beyond memory.
+        let output = compiler::compile("ptr: DS 0xFFFFFF\nSTART: LDC 5\nLDIV ptr\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output.clone());
+        assert_eq!(mima.run(), HaltReason::adress_out_of_range(Some(0xFFFFFF)));
+        assert_eq!((mima.akku, mima.iar), (5, 2));
+        let output = compiler::compile("ptr: DS 0x100000\nSTART: LDC 5\nSTIV ptr\nHALT").unwrap();
+        mima.load(output);
+        mima.take_dirty_addresses();
+        assert_eq!(mima.run(), HaltReason::adress_out_of_range(Some(0x100000)));
+        assert!(mima.take_dirty_addresses().is_empty());
+        // On a small machine the pointer itself may be out of range, nothing is written then.
+        let mut mima = Mima::with_config(16, 24).unwrap();
+        mima.load(compiler::compile("LDC 5\nSTIV 20\nHALT").unwrap());
+        mima.take_dirty_addresses();
+        assert_eq!(mima.run(), HaltReason::adress_out_of_range(Some(20)));
+        assert_eq!(mima.read_adress(0), Some(Command::new(Instruction::LDC, 5).unwrap().to_usize()));
+        assert!(mima.take_dirty_addresses().is_empty());
+        mima.load(compiler::compile("LDC 5\nLDIV 20\nHALT").unwrap());
+        assert_eq!(mima.run(), HaltReason::adress_out_of_range(Some(20)));
+        assert_eq!(mima.akku, 5);
+    }
+    #[test]
+    fn words_keep_their_width() {
+        // Images built by hand may contain words wider than the machine.
+        let code = vec![Command::new(Instruction::LDV, 2).unwrap().to_usize(), 0xF00000, 0x1ABCDEF];
+        let mut mima = Mima::new();
+        mima.load(CompilerOutput::new(code, 0));
+        assert_eq!(mima.read_adress(2), Some(0xABCDEF));
+        mima.run();
+        assert_eq!(mima.akku, 0xABCDEF);
+        // Wider machines store all their bits.
+        let mut mima = Mima::with_config(16, 31).unwrap();
+        mima.load(compiler::compile("x: DS\nLDC 1\nNOT\nSTV x\nHALT").unwrap());
+        mima.run();
+        assert_eq!(mima.read_symbol("x"), Some(0x7FFFFFFE));
+    }
+    #[test]
+    fn decoded_debug_info() {
+        let output = compiler::compile("tbl: DS 4, 8\nptr: DS 1\nSTART: LDIV ptr\nLDC 3\nHALT").unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        let debug = mima.get_debug();
+        assert_eq!(debug.get_instruction(), Some("LDIV 2".to_string()));
+        // LDIV follows the pointer in ptr to tbl[1].
+        assert_eq!((debug.effective_adress, debug.effective_value), (Some(1), Some(8)));
+        mima.step();
+        let debug = mima.get_debug();
+        assert_eq!(debug.get_instruction(), Some("LDC 3".to_string()));
+        assert_eq!(debug.effective_adress, None);
+        mima.write_adress(5, 0xFF0000);
+        assert!(mima.set_iar(5));
+        assert_eq!(mima.get_debug().get_instruction(), None);
+    }
+    #[test]
+    fn step_over_and_run_to() {
+        let assembly_source = "n: DS 0
+one: DS 1
+START: CALL INC
+CALL INC
+LDV n
+HALT
+INC: LDV n
 ADD one
-STV counter
-LDV max
-EQL counter
-JMN FINISH
+STV n
+RET";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        let result = mima.step_over();
+        assert_eq!(result.steps, 5);
+        assert_eq!(mima.get_iar(), 3);
+        assert_eq!(mima.read_symbol("n"), Some(1));
+        // Other instructions are stepped normally.
+        mima.step_over();
+        let result = mima.step_over();
+        assert_eq!((result.steps, mima.get_iar()), (1, 5));
+        // Runs into the subroutine and stops in front of its second instruction
+        mima.load(compiler::compile(assembly_source).unwrap());
+        let result = mima.run_to(7);
+        assert_eq!((result.steps, mima.get_iar(), result.cancelled), (2, 7, false));
+        assert!(!mima.get_debug().halt);
+        // A target that is never reached runs until the machine halts.
+        assert_eq!(mima.run_to(0).halt_reason, HaltReason::halted());
+    }
+    #[test]
+    fn call_stack() {
+        let assembly_source = "arg: DS 9
+START: LDV arg
+CALL OUTER
+HALT
+OUTER: CALL INNER
+RET
+INNER: HALT";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert!(mima.get_call_stack().is_empty());
+        mima.run();
+        let stack = mima.get_call_stack();
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack[0].get_function(), Some("INNER".to_string()));
+        assert_eq!(stack[0].get_location(), Some("OUTER".to_string()));
+        assert_eq!(stack[0].get_return_adress(), 5);
+        assert_eq!(stack[1].get_call_site(), 2);
+        assert_eq!(stack[1].get_location(), Some("START+1".to_string()));
+        assert_eq!(stack[1].get_stack_adress(), super::MEMORY_SIZE - 1);
+    }
+    #[test]
+    fn input_replay() {
+        let assembly_source = "KEY EQU 0xFFF00
+sum: DS 0
+START: LDV KEY
+ADD sum
+STV sum
+LDV KEY
+ADD sum
+STV sum
+HALT";
+        let mut mima = Mima::new();
+        mima.load(compiler::compile(assembly_source).unwrap());
+        mima.attach_keyboard(0xFFF00);
+        mima.push_input("ab");
+        mima.run();
+        assert_eq!(mima.read_symbol("sum"), Some(97 + 98));
+        let log = mima.get_input_log();
+        assert_eq!(log.to_text(), "0 1048320 97\n3 1048320 98");
+        // The same result without any input
+        let log = InputLog::from_text(&log.to_text()).unwrap();
+        mima.load(compiler::compile(assembly_source).unwrap());
+        assert_eq!(mima.replay(&log), HaltReason::halted());
+        assert_eq!(mima.read_symbol("sum"), Some(97 + 98));
+        assert_eq!(mima.get_input_log(), log);
+        assert!(InputLog::from_text("1 2").is_err());
+    }
+    #[test]
+    fn random_device() {
+        let assembly_source = "RANDOM EQU 0xFFF10
+a: DS 0
+b: DS 0
+c: DS 0
+START: LDV RANDOM
+STV a
+LDV RANDOM
+STV b
+LDC 42
+STV RANDOM
+LDV RANDOM
+STV c
+HALT";
+        let run = |seed: u32| {
+            let mut mima = Mima::new();
+            mima.load(compiler::compile(assembly_source).unwrap());
+            assert!(mima.attach_random(0xFFF10, seed));
+            mima.run();
+            ["a", "b", "c"].map(|name| mima.read_symbol(name).unwrap())
+        };
+        let [a, b, c] = run(7);
+        assert_eq!(run(7), [a, b, c]);
+        assert_ne!(a, b);
+        assert!([a, b, c].iter().all(|value| *value <= 0xFFFFFF));
+        // Writing reseeds the generator.
+        assert_ne!(run(8)[..2], [a, b]);
+        assert_eq!(run(8)[2], c);
+        // Random values end up in the input log and can be replayed.
+        let mut mima = Mima::new();
+        mima.load(compiler::compile(assembly_source).unwrap());
+        mima.attach_random(0xFFF10, 7);
+        mima.run();
+        let log = mima.get_input_log();
+        mima.load(compiler::compile(assembly_source).unwrap());
+        mima.detach_devices();
+        mima.attach_random(0xFFF10, 1);
+        mima.replay(&log);
+        assert_eq!(mima.read_symbol("c"), Some(c));
+    }
+    #[test]
+    fn timer_interrupts() {
+        let assembly_source = "TIMER EQU 0xFFF20
+HANDLER_ADRESS EQU 0xFFF21
+saved: DS 0
+ticks: DS 0
+three: DS 3
+START: LDC HANDLER
+STV HANDLER_ADRESS
+LDC 10
+STV TIMER
+LOOP: LDV ticks
+EQL three
+JMN DONE
 JMP LOOP
-FINISH: HALT
-";
+DONE: HALT
+HANDLER: STV saved
+LDV ticks
+ADC 1
+STV ticks
+LDV saved
+RTI";
         let output = compiler::compile(assembly_source).unwrap();
+        assert_eq!(output.get_mima_code().last(), Some(&0xFA0000));
         let mut mima = Mima::new();
         mima.load(output);
+        assert!(mima.attach_timer(0xFFF20, 0, 0));
+        assert_eq!(mima.run(), HaltReason::halted());
+        // Another interrupt may come between loading and comparing the ticks.
+        assert!(mima.read_symbol("ticks").is_some_and(|ticks| ticks >= 3));
+        assert!(!mima.is_in_interrupt());
+        // Returning from an interrupt outside of a handler traps.
+        mima.load(compiler::compile("START: RTI").unwrap());
+        assert_eq!(mima.run(), HaltReason::illegal_instruction(0, 0xFA0000));
+    }
+    #[test]
+    fn screen_updates() {
+        let assembly_source = "SCREEN EQU 0xFFE00
+START: LDC 'H'
+STV 0xFFE05
+LDC 'i'
+STV 0xFFE06
+LDC '#'
+STV 0xFFE09
+HALT";
+        let mut mima = Mima::new();
+        mima.load(compiler::compile(assembly_source).unwrap());
+        assert!(!mima.attach_screen(0, usize::MAX, 2));
+        assert!(!mima.attach_screen(0, 1 << 20, 1 << 20));
+        assert!(mima.attach_screen(0xFFE00, 4, 4));
+        assert!(!mima.attach_screen(0xFFF00, 2, 2));
         mima.run();
-        assert_eq!(mima.halt, true);
-        // Check if the program counted to 100 at the given adress.
-        assert_eq!(mima.read_adress(2), Some(100));
+        assert_eq!(mima.get_screen_text(), "    \n Hi \n #  \n    ");
+        let update = mima.take_screen_update().unwrap();
+        assert_eq!((update.x, update.y, update.width, update.height), (1, 1, 2, 2));
+        assert_eq!(update.get_cells(), vec!['H' as usize, 'i' as usize, '#' as usize, 0]);
+        assert_eq!(mima.take_screen_update(), None);
+        // Writing the same value again doesn't count as a change.
+        mima.write_adress(0xFFE05, 'H' as usize);
+        assert_eq!(mima.take_screen_update(), None);
+        assert_eq!(mima.read_adress(0xFFE06), Some('i' as usize));
+        assert_eq!(mima.get_framebuffer().len(), 16);
+    }
+    #[test]
+    fn cycle_costs() {
+        let mut mima = Mima::new();
+        let fetch = mima.get_control_store().get_fetch().len();
+        assert_eq!(mima.get_instruction_cycles(Instruction::LDC), fetch + 2);
+        assert_eq!(mima.get_instruction_cycles(Instruction::ADD), fetch + 7);
+        assert_eq!(mima.get_instruction_cycles(Instruction::CALL), fetch + 1);
+        mima.load(compiler::compile("a: DS 1\nSTART: LDV a\nJMN START\nADD a\nHALT").unwrap());
+        mima.run();
+        assert_eq!(mima.get_instruction_count(), 4);
+        // The JMN doesn't jump, so only its fetch and decode count.
+        let expected = (fetch + 5) + (fetch + 1) + (fetch + 7) + (fetch + 1);
+        assert_eq!(mima.get_cycle_count(), expected as u64);
+        assert_eq!(mima.get_simulated_time(expected as f64 * 2.0), 0.5);
+        // Microprogramming changes the costs.
+        let mut micro = Mima::new();
+        micro.load(compiler::compile("a: DS 1\nSTART: LDV a\nJMN START\nADD a\nHALT").unwrap());
+        let mut store = micro.get_control_store();
+        store.remove_microprogram(Instruction::ADD);
+        micro.set_control_store(store);
+        micro.run();
+        assert_eq!(micro.get_cycle_count(), expected as u64 - 6);
+    }
+    #[test]
+    fn machine_diff() {
+        let reference = "x: DS 3\nresult: DS 0, 0\nSTART: LDV x\nADD x\nSTV result\nHALT";
+        let submission = "x: DS 3\nresult: DS 0, 0\nSTART: LDV x\nSTV result\nSTV result+1\nHALT";
+        let mut left = Mima::new();
+        left.load(compiler::compile(reference).unwrap());
+        left.run();
+        let mut right = Mima::new();
+        right.load(compiler::compile(reference).unwrap());
+        right.run();
+        assert!(left.diff(&right).is_empty());
+        right.load(compiler::compile(submission).unwrap());
+        right.run();
+        let diff = left.diff(&right);
+        let registers: Vec<(String, usize, usize)> =
+            diff.get_registers().iter().map(|register| (register.get_name(), register.left, register.right)).collect();
+        assert_eq!(registers, vec![("AKKU".to_string(), 6, 3)]);
+        let cells: Vec<(usize, Option<String>, usize, usize)> =
+            diff.get_cells().iter().map(|cell| (cell.adress, cell.get_location(), cell.left, cell.right)).collect();
+        // The instructions differ as well.
+        assert_eq!(cells[0], (1, Some("result".to_string()), 6, 3));
+        assert_eq!(cells[1], (2, Some("result+1".to_string()), 0, 3));
+        assert_eq!(cells.len(), 4);
+    }
+    #[test]
+    fn console_and_keyboard() {
+        // Echoes the input in upper case until the keyboard runs empty.
+        let assembly_source = "KEY EQU 0xFFF00
+WAITING EQU 0xFFF01
+OUT EQU 0xFFF02
+offset: DS -32
+START: LDV WAITING
+EQL zero
+JMN END
+LDV KEY
+ADD offset
+STV OUT
+JMP START
+END: HALT
+zero: DS 0";
+        let output = compiler::compile(assembly_source).unwrap();
+        let mut mima = Mima::new();
+        mima.load(output);
+        assert!(mima.attach_keyboard(0xFFF00));
+        assert!(mima.attach_console(0xFFF02));
+        // Devices can't overlap each other or the end of memory.
+        assert!(!mima.attach_console(0xFFF01));
+        assert!(!mima.attach_keyboard(super::MEMORY_SIZE - 1));
+        mima.push_input("mima");
+        assert_eq!(mima.read_adress(0xFFF01), Some(4));
+        assert_eq!(mima.run(), HaltReason::halted());
+        assert_eq!(mima.take_output(), "MIMA");
+        assert_eq!(mima.take_output(), "");
+        // Written characters never reach RAM.
+        mima.detach_devices();
+        assert_eq!(mima.read_adress(0xFFF02), Some(0));
     }
 }
@@ -0,0 +1,666 @@
+use crate::compiler::{CompilerError, Parameter};
+
+// Byte offsets of a lexeme within its line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexemeKind {
+    Identifier(String),
+    // Unsigned value of a number literal, the sign is a separate lexeme
+    Number(i64),
+    String(String),
+    Colon,
+    Comma,
+    Star,
+    Equals,
+    Minus,
+    Plus,
+    Slash,
+    Percent,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lexeme {
+    pub kind: LexemeKind,
+    pub span: Span,
+}
+
+// A number literal with its sign and the text it was written as
+#[derive(Clone, Debug, PartialEq)]
+pub struct Literal {
+    pub value: i64,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Number(i64),
+    // Constants stand for their value, variables and labels for their adress.
+    Symbol(String),
+    Negate(Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+    // "mem[adress]" reads a memory word, only in watch expressions
+    Memory(Box<Expression>),
+}
+
+// A constant expression like "BUFSIZE-1", evaluated during assembly
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    pub node: Node,
+    // Source text of the expression for messages
+    pub text: String,
+    pub span: Span,
+}
+
+impl Expression {
+    // Names of all symbols the expression refers to
+    pub fn symbols(&self) -> Vec<&str> {
+        match &self.node {
+            Node::Number(_) => vec![],
+            Node::Symbol(name) => vec![name.as_str()],
+            Node::Negate(operand) | Node::Memory(operand) => operand.symbols(),
+            Node::Binary(_, left, right) => left.symbols().into_iter().chain(right.symbols()).collect(),
+        }
+    }
+
+    /*
+     * Computes the value with the given values of the symbols. Overflowing the 64 bit intermediate
+     * values and dividing by zero are errors, the range of the result is checked by the caller.
+     */
+    pub fn evaluate(&self, line: usize, symbol: &mut dyn FnMut(&str) -> Result<i64, CompilerError>) -> Result<i64, CompilerError> {
+        let overflow = || CompilerError::ExpressionOverflow { line, expression: self.text.to_owned() };
+        match &self.node {
+            Node::Number(value) => Ok(*value),
+            Node::Symbol(name) => symbol(name),
+            Node::Negate(operand) => operand.evaluate(line, symbol)?.checked_neg().ok_or_else(overflow),
+            Node::Memory(_) => unreachable!("memory accesses are only parsed in watch expressions"),
+            Node::Binary(operator, left, right) => {
+                let (left, right) = (left.evaluate(line, symbol)?, right.evaluate(line, symbol)?);
+                if right == 0 && matches!(operator, BinaryOperator::Divide | BinaryOperator::Remainder) {
+                    return Err(CompilerError::DivisionByZero { line, expression: self.text.to_owned() });
+                }
+                match operator {
+                    BinaryOperator::Add => left.checked_add(right),
+                    BinaryOperator::Subtract => left.checked_sub(right),
+                    BinaryOperator::Multiply => left.checked_mul(right),
+                    BinaryOperator::Divide => left.checked_div(right),
+                    BinaryOperator::Remainder => left.checked_rem(right),
+                }
+                .ok_or_else(overflow)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Initializer {
+    // DS without values reserves a single word set to zero.
+    Default,
+    List(Vec<Expression>),
+    // "10 * 0" repeats the value
+    Repeat { count: Expression, value: Expression },
+}
+
+// The syntax of a single line. Whether names and values are valid is checked by the compiler.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Syntax {
+    Origin(Literal),
+    Variable { name: String, parameter: Option<Parameter>, initializer: Initializer },
+    Constant { name: String, value: Expression },
+    Export(Vec<String>),
+    Title(String),
+    // END with the optional entry label
+    End(Option<String>),
+    Instruction { label: Option<String>, name: String, operand: Option<Expression> },
+}
+
+/*
+ * Splits a line without comment into lexemes. Numbers and identifiers have to be separated from
+ * the following word, so "10abc" is an invalid literal instead of the number 10.
+ */
+pub fn lex(text: &str, line: usize) -> Result<Vec<Lexeme>, CompilerError> {
+    let mut lexemes = vec![];
+    let mut position = skip_whitespace(text, 0);
+    while position < text.len() {
+        let (kind, end) = lexeme_at(text, position, line)?;
+        lexemes.push(Lexeme { kind, span: Span { start: position, end } });
+        position = skip_whitespace(text, end);
+    }
+    Ok(lexemes)
+}
+
+/*
+ * Like lex, but keeps going after invalid text for highlighting lines that are being edited. The
+ * invalid text reaches up to the next whitespace, or to the end of the line for strings.
+ */
+pub fn lex_lenient(text: &str) -> Vec<(Option<LexemeKind>, Span)> {
+    let mut lexemes = vec![];
+    let mut position = skip_whitespace(text, 0);
+    while position < text.len() {
+        let (kind, end) = match lexeme_at(text, position, 0) {
+            Ok((kind, end)) => (Some(kind), end),
+            Err(_) if matches!(text.as_bytes()[position], b'"' | b'\'') => (None, text.len()),
+            Err(_) => (None, text[position..].find(|character: char| character.is_ascii_whitespace()).map_or(text.len(), |length| position + length)),
+        };
+        lexemes.push((kind, Span { start: position, end }));
+        position = skip_whitespace(text, end);
+    }
+    lexemes
+}
+
+fn skip_whitespace(text: &str, position: usize) -> usize {
+    text[position..].find(|character: char| !character.is_ascii_whitespace()).map_or(text.len(), |length| position + length)
+}
+
+// Reads the lexeme starting at the offset and returns it with the offset behind it.
+fn lexeme_at(text: &str, start: usize, line: usize) -> Result<(LexemeKind, usize), CompilerError> {
+    let bytes = text.as_bytes();
+    // Length of the word starting at the offset
+    let word_end = |start: usize| {
+        bytes[start..]
+            .iter()
+            .position(|byte| !(byte.is_ascii_alphanumeric() || *byte == b'_' || *byte == b'$'))
+            .map_or(bytes.len(), |length| start + length)
+    };
+    let column = start + 1;
+    let (kind, end) = match bytes[start] {
+        b':' => (LexemeKind::Colon, start + 1),
+        b',' => (LexemeKind::Comma, start + 1),
+        b'*' => (LexemeKind::Star, start + 1),
+        b'=' => (LexemeKind::Equals, start + 1),
+        b'-' => (LexemeKind::Minus, start + 1),
+        b'+' => (LexemeKind::Plus, start + 1),
+        b'/' => (LexemeKind::Slash, start + 1),
+        b'%' => (LexemeKind::Percent, start + 1),
+        b'(' => (LexemeKind::LeftParen, start + 1),
+        b')' => (LexemeKind::RightParen, start + 1),
+        b'[' => (LexemeKind::LeftBracket, start + 1),
+        b']' => (LexemeKind::RightBracket, start + 1),
+        b'"' => {
+            let (content, end) = quoted(text, start, line)?;
+            (LexemeKind::String(content), end)
+        }
+        // A character literal is the number of its code point.
+        b'\'' => {
+            let (content, end) = quoted(text, start, line)?;
+            let mut characters = content.chars();
+            match (characters.next(), characters.next()) {
+                (Some(character), None) => (LexemeKind::Number(character as i64), end),
+                _ => return Err(CompilerError::InvalidLiteral { line, column, value: text[start..end].to_string() }),
+            }
+        }
+        byte if byte.is_ascii_alphabetic() => {
+            let end = word_end(start);
+            let name = &text[start..end];
+            if name.contains('$') {
+                return Err(CompilerError::InvalidIdentifier { line, column, name: name.to_string() });
+            }
+            (LexemeKind::Identifier(name.to_string()), end)
+        }
+        byte if byte.is_ascii_digit() || byte == b'$' => {
+            let end = word_end(start);
+            (LexemeKind::Number(parse_number(&text[start..end], line, column)?), end)
+        }
+        _ => {
+            let character = text[start..].chars().next().unwrap();
+            return Err(CompilerError::UnexpectedCharacter { line, column, character });
+        }
+    };
+    Ok((kind, end))
+}
+
+/*
+ * Reads the string or character literal starting with the quote at the offset. Returns its content
+ * with the escape sequences \n, \t, \0, \\, \' and \" replaced and the offset behind the closing quote.
+ */
+fn quoted(text: &str, start: usize, line: usize) -> Result<(String, usize), CompilerError> {
+    let quote = text[start..].chars().next().unwrap();
+    let mut content = String::new();
+    let mut characters = text[start + 1..].char_indices();
+    while let Some((offset, character)) = characters.next() {
+        let unescaped = match character {
+            _ if character == quote => return Ok((content, start + 1 + offset + 1)),
+            '\\' => match characters.next() {
+                Some((_, 'n')) => '\n',
+                Some((_, 't')) => '\t',
+                Some((_, '0')) => '\0',
+                Some((_, escaped @ ('\\' | '\'' | '"'))) => escaped,
+                Some((position, escaped)) => {
+                    return Err(CompilerError::InvalidLiteral {
+                        line,
+                        column: start + 1 + position,
+                        value: format!("\\{escaped}"),
+                    })
+                }
+                None => break,
+            },
+            _ => character,
+        };
+        content.push(unescaped);
+    }
+    Err(CompilerError::UnterminatedString { line, column: start + 1 })
+}
+
+// Parses a decimal, hexadecimal (0x1F or $1F) or binary (0b1010) number.
+fn parse_number(literal: &str, line: usize, column: usize) -> Result<i64, CompilerError> {
+    let (digits, radix) = if let Some(hex) = literal.strip_prefix("0x").or(literal.strip_prefix("0X")).or(literal.strip_prefix('$')) {
+        (hex, 16)
+    } else if let Some(binary) = literal.strip_prefix("0b").or(literal.strip_prefix("0B")) {
+        (binary, 2)
+    } else {
+        (literal, 10)
+    };
+    if digits.is_empty() || !digits.chars().all(|digit| digit.is_digit(radix)) {
+        return Err(CompilerError::InvalidLiteral { line, column, value: literal.to_string() });
+    }
+    i64::from_str_radix(digits, radix).map_err(|_| CompilerError::LiteralOutOfRange { line, value: literal.to_string() })
+}
+
+pub fn parse_line(text: &str, line: usize) -> Result<Syntax, CompilerError> {
+    let lexemes = lex(text, line)?;
+    let mut parser = Parser { text, line, lexemes, position: 0, watch: false };
+    let syntax = parser.statement()?;
+    parser.expect_end()?;
+    Ok(syntax)
+}
+
+// Parses an expression to watch while debugging, which may read memory with "mem[adress]".
+pub fn parse_watch(text: &str) -> Result<Expression, CompilerError> {
+    let lexemes = lex(text, 1)?;
+    let mut parser = Parser { text, line: 1, lexemes, position: 0, watch: true };
+    let expression = parser.expression(false)?;
+    parser.expect_end()?;
+    Ok(expression)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    line: usize,
+    lexemes: Vec<Lexeme>,
+    position: usize,
+    // Whether memory accesses are allowed in expressions
+    watch: bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self, offset: usize) -> Option<&LexemeKind> {
+        self.lexemes.get(self.position + offset).map(|lexeme| &lexeme.kind)
+    }
+
+    // Whether the lexeme at the offset is the keyword, keywords are case-insensitive.
+    fn is_keyword(&self, offset: usize, keyword: &str) -> bool {
+        matches!(self.peek(offset), Some(LexemeKind::Identifier(name)) if name.eq_ignore_ascii_case(keyword))
+    }
+
+    fn unexpected(&self) -> CompilerError {
+        match self.lexemes.get(self.position) {
+            Some(lexeme) => CompilerError::UnexpectedToken {
+                line: self.line,
+                column: lexeme.span.start + 1,
+                found: self.text[lexeme.span.start..lexeme.span.end].to_string(),
+            },
+            None => CompilerError::UnexpectedToken {
+                line: self.line,
+                column: self.text.len() + 1,
+                found: "end of line".to_string(),
+            },
+        }
+    }
+
+    fn expect(&mut self, kind: LexemeKind) -> Result<(), CompilerError> {
+        if self.peek(0) != Some(&kind) {
+            return Err(self.unexpected());
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn expect_end(&self) -> Result<(), CompilerError> {
+        match self.peek(0) {
+            Some(_) => Err(self.unexpected()),
+            None => Ok(()),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<(String, Span), CompilerError> {
+        match self.lexemes.get(self.position) {
+            Some(Lexeme { kind: LexemeKind::Identifier(name), span }) => {
+                self.position += 1;
+                Ok((name.to_owned(), *span))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Literal, CompilerError> {
+        let start = self.lexemes.get(self.position).map(|lexeme| lexeme.span.start);
+        let negative = self.peek(0) == Some(&LexemeKind::Minus);
+        if negative {
+            self.position += 1;
+        }
+        match self.lexemes.get(self.position) {
+            Some(Lexeme { kind: LexemeKind::Number(value), span }) => {
+                let span = Span { start: start.unwrap(), end: span.end };
+                self.position += 1;
+                Ok(Literal {
+                    value: if negative { -value } else { *value },
+                    text: self.text[span.start..span.end].to_string(),
+                    span,
+                })
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Syntax, CompilerError> {
+        if self.peek(0) == Some(&LexemeKind::Star) {
+            self.position += 1;
+            self.expect(LexemeKind::Equals)?;
+            return Ok(Syntax::Origin(self.literal()?));
+        }
+        if self.is_keyword(0, "ORG") {
+            self.position += 1;
+            return Ok(Syntax::Origin(self.literal()?));
+        }
+        if self.is_keyword(0, "EXPORT") {
+            self.position += 1;
+            let mut names = vec![self.identifier()?.0];
+            while self.peek(0) == Some(&LexemeKind::Comma) {
+                self.position += 1;
+                names.push(self.identifier()?.0);
+            }
+            return Ok(Syntax::Export(names));
+        }
+        // Labels and constants may still be called TITLE or END.
+        let directive = |keyword: &str| self.is_keyword(0, keyword) && self.peek(1) != Some(&LexemeKind::Colon) && !self.is_keyword(1, "EQU");
+        if directive("TITLE") {
+            self.position += 1;
+            return match self.lexemes.get(self.position) {
+                Some(Lexeme { kind: LexemeKind::String(title), .. }) => {
+                    let title = title.to_owned();
+                    self.position += 1;
+                    Ok(Syntax::Title(title))
+                }
+                _ => Err(self.unexpected()),
+            };
+        }
+        if directive("END") {
+            self.position += 1;
+            let entry = match self.peek(0) {
+                Some(_) => Some(self.identifier()?.0),
+                None => None,
+            };
+            return Ok(Syntax::End(entry));
+        }
+        // Constants are defined as "NAME EQU value" or "NAME: EQU value".
+        let colon = self.peek(1) == Some(&LexemeKind::Colon);
+        if self.is_keyword(1, "EQU") || (colon && self.is_keyword(2, "EQU")) {
+            let name = self.identifier()?.0;
+            self.position += if colon { 2 } else { 1 };
+            return Ok(Syntax::Constant { name, value: self.expression(false)? });
+        }
+        let label = match self.peek(1) {
+            Some(LexemeKind::Colon) => {
+                let label = self.identifier()?.0;
+                self.position += 1;
+                Some(label)
+            }
+            _ => None,
+        };
+        if let (Some(name), true) = (&label, self.is_keyword(0, "DS")) {
+            self.position += 1;
+            // "DS INPUT 5" marks the variable, the initializer follows as usual.
+            let parameter = [("INPUT", Parameter::Input), ("OUTPUT", Parameter::Output)]
+                .into_iter()
+                .find(|(keyword, _)| self.is_keyword(0, keyword))
+                .map(|(_, parameter)| parameter);
+            self.position += usize::from(parameter.is_some());
+            return Ok(Syntax::Variable { name: name.to_owned(), parameter, initializer: self.initializer()? });
+        }
+        let name = self.identifier()?.0;
+        let operand = match self.peek(0) {
+            None => None,
+            Some(_) => Some(self.expression(false)?),
+        };
+        Ok(Syntax::Instruction { label, name, operand })
+    }
+
+    fn node(&self, node: Node, start: usize) -> Expression {
+        let end = self.lexemes[self.position - 1].span.end;
+        Expression { node, text: self.text[start..end].to_string(), span: Span { start, end } }
+    }
+
+    fn start(&self) -> usize {
+        self.lexemes.get(self.position).map_or(self.text.len(), |lexeme| lexeme.span.start)
+    }
+
+    /*
+     * Sums and differences of products. In DS initializers a '*' at the top level repeats the
+     * value, so products have to be put into parentheses there.
+     */
+    fn expression(&mut self, repetition: bool) -> Result<Expression, CompilerError> {
+        let start = self.start();
+        let mut left = self.product(repetition)?;
+        loop {
+            let operator = match self.peek(0) {
+                Some(LexemeKind::Plus) => BinaryOperator::Add,
+                Some(LexemeKind::Minus) => BinaryOperator::Subtract,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.product(repetition)?;
+            left = self.node(Node::Binary(operator, Box::new(left), Box::new(right)), start);
+        }
+    }
+
+    fn product(&mut self, repetition: bool) -> Result<Expression, CompilerError> {
+        let start = self.start();
+        let mut left = self.unary()?;
+        loop {
+            let operator = match self.peek(0) {
+                Some(LexemeKind::Star) if !repetition => BinaryOperator::Multiply,
+                Some(LexemeKind::Slash) => BinaryOperator::Divide,
+                Some(LexemeKind::Percent) => BinaryOperator::Remainder,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.unary()?;
+            left = self.node(Node::Binary(operator, Box::new(left), Box::new(right)), start);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, CompilerError> {
+        let start = self.start();
+        match self.peek(0) {
+            Some(LexemeKind::Minus) => {
+                self.position += 1;
+                let operand = self.unary()?;
+                Ok(self.node(Node::Negate(Box::new(operand)), start))
+            }
+            Some(LexemeKind::Number(value)) => {
+                let value = *value;
+                self.position += 1;
+                Ok(self.node(Node::Number(value), start))
+            }
+            Some(LexemeKind::Identifier(name)) if self.watch && name.eq_ignore_ascii_case("mem") && self.peek(1) == Some(&LexemeKind::LeftBracket) => {
+                self.position += 2;
+                let adress = self.expression(false)?;
+                self.expect(LexemeKind::RightBracket)?;
+                Ok(self.node(Node::Memory(Box::new(adress)), start))
+            }
+            Some(LexemeKind::Identifier(name)) => {
+                let name = name.to_owned();
+                self.position += 1;
+                Ok(self.node(Node::Symbol(name), start))
+            }
+            Some(LexemeKind::LeftParen) => {
+                self.position += 1;
+                let inner = self.expression(false)?;
+                self.expect(LexemeKind::RightParen)?;
+                Ok(self.node(inner.node, start))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn initializer(&mut self) -> Result<Initializer, CompilerError> {
+        if self.peek(0).is_none() {
+            return Ok(Initializer::Default);
+        }
+        let first = self.values()?;
+        if let (Some(LexemeKind::Star), [count]) = (self.peek(0), first.as_slice()) {
+            let count = count.to_owned();
+            self.position += 1;
+            return Ok(Initializer::Repeat { count, value: self.expression(true)? });
+        }
+        let mut values = first;
+        while self.peek(0) == Some(&LexemeKind::Comma) {
+            self.position += 1;
+            values.extend(self.values()?);
+        }
+        if values.is_empty() {
+            return Err(CompilerError::InvalidLiteral { line: self.line, column: self.text.len() + 1, value: "\"\"".to_string() });
+        }
+        Ok(Initializer::List(values))
+    }
+
+    // A number or a string, which stands for one value per character
+    fn values(&mut self) -> Result<Vec<Expression>, CompilerError> {
+        match self.lexemes.get(self.position) {
+            Some(Lexeme { kind: LexemeKind::String(content), span }) => {
+                let (content, span) = (content.to_owned(), *span);
+                self.position += 1;
+                let text = &self.text[span.start..span.end];
+                Ok(content
+                    .chars()
+                    .map(|character| Expression { node: Node::Number(character as i64), text: text.to_string(), span })
+                    .collect())
+            }
+            _ => Ok(vec![self.expression(true)?]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lex, parse_line, parse_watch, BinaryOperator, Initializer, LexemeKind, Node, Parameter, Span, Syntax};
+
+    #[test]
+    fn lexing() {
+        let lexemes = lex("loop:\tJMN -$1F", 1).unwrap();
+        let kinds: Vec<LexemeKind> = lexemes.iter().map(|lexeme| lexeme.kind.to_owned()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                LexemeKind::Identifier("loop".to_string()),
+                LexemeKind::Colon,
+                LexemeKind::Identifier("JMN".to_string()),
+                LexemeKind::Minus,
+                LexemeKind::Number(31),
+            ]
+        );
+        assert_eq!(lexemes[2].span, Span { start: 6, end: 9 });
+        assert!(lex("JMP 10abc", 3).is_err_and(|err| err.to_string().contains("'10abc'")));
+        assert!(lex("LDC 0b102", 1).is_err());
+        assert!(lex("LDC #5", 1).is_err_and(|err| err.to_string().contains("column '5'")));
+        let kinds: Vec<LexemeKind> = lex(r#"'A' '\n' "a\"b;""#, 1).unwrap().into_iter().map(|lexeme| lexeme.kind).collect();
+        assert_eq!(kinds, vec![LexemeKind::Number(65), LexemeKind::Number(10), LexemeKind::String("a\"b;".to_string())]);
+        assert!(lex("LDC 'AB'", 1).is_err_and(|err| err.to_string().contains("'AB'")));
+        assert!(lex("DS \"open", 1).is_err_and(|err| err.to_string().contains("isn't terminated")));
+    }
+
+    #[test]
+    fn parsing() {
+        match parse_line("  a : DS 3 * -1  ", 1).unwrap() {
+            Syntax::Variable { name, parameter: None, initializer: Initializer::Repeat { count, value } } => {
+                assert_eq!(name, "a");
+                assert_eq!(count.node, Node::Number(3));
+                assert_eq!((value.text.as_str(), value.span), ("-1", Span { start: 13, end: 15 }));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        match parse_line("n: DS output", 1).unwrap() {
+            Syntax::Variable { name, parameter, initializer } => {
+                assert_eq!((name.as_str(), parameter, initializer), ("n", Some(Parameter::Output), Initializer::Default));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(parse_line("TITLE \"Sort\"", 1).unwrap(), Syntax::Title("Sort".to_string()));
+        assert_eq!(parse_line("end MAIN", 1).unwrap(), Syntax::End(Some("MAIN".to_string())));
+        assert!(matches!(parse_line("END: HALT", 1).unwrap(), Syntax::Instruction { .. }));
+        assert!(parse_line("TITLE Sort", 1).is_err());
+        match parse_line("START: ldv counter", 1).unwrap() {
+            Syntax::Instruction { label, name, operand } => {
+                assert_eq!(label.as_deref(), Some("START"));
+                assert_eq!(name, "ldv");
+                let operand = operand.unwrap();
+                assert_eq!((operand.node, operand.span), (Node::Symbol("counter".to_string()), Span { start: 11, end: 18 }));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(matches!(parse_line("* = $10", 1).unwrap(), Syntax::Origin(origin) if origin.value == 16));
+        assert!(matches!(parse_line("ONE equ 1", 1).unwrap(), Syntax::Constant { .. }));
+        assert!(matches!(parse_line("tbl_end: EQU tbl+10", 1).unwrap(), Syntax::Constant { name, .. } if name == "tbl_end"));
+        assert!(parse_line("LDV a b", 2).is_err_and(|err| err.to_string().contains("Unexpected 'b'")));
+        assert!(parse_line("a: DS 1,", 2).is_err_and(|err| err.to_string().contains("end of line")));
+    }
+
+    // Parses the operand of LDC and evaluates it, symbols are 100
+    fn evaluate(operand: &str) -> Result<i64, String> {
+        let Syntax::Instruction { operand: Some(expression), .. } = parse_line(&format!("LDC {operand}"), 1).map_err(|err| err.to_string())? else {
+            panic!("no operand");
+        };
+        expression.evaluate(1, &mut |_| Ok(100)).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(evaluate("1+2*3"), Ok(7));
+        assert_eq!(evaluate("(1+2)*3"), Ok(9));
+        assert_eq!(evaluate("10-4-3"), Ok(3));
+        assert_eq!(evaluate("-a+1"), Ok(-99));
+        assert_eq!(evaluate("a/7 % 5"), Ok(4));
+        assert_eq!(evaluate("--'A'"), Ok(65));
+        assert_eq!(evaluate("1/(a-100)"), Err("Division by zero in expression '1/(a-100)' in line '1'.".to_string()));
+        assert!(evaluate("0x7FFFFFFFFFFFFFFF+1").is_err_and(|err| err.contains("overflows")));
+        assert!(evaluate("(1+2").is_err_and(|err| err.contains("end of line")));
+        match parse_line("a: DS 2 * (3*4), 5", 1) {
+            Err(err) => assert!(err.to_string().contains("Unexpected ','")),
+            Ok(other) => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn watch_expressions() {
+        let expression = parse_watch("MEM[tbl + 3] - 1").unwrap();
+        let Node::Binary(BinaryOperator::Subtract, memory, _) = expression.node else {
+            panic!("unexpected {expression:?}");
+        };
+        let Node::Memory(adress) = memory.node else {
+            panic!("unexpected {memory:?}");
+        };
+        assert_eq!((adress.text.as_str(), adress.symbols()), ("tbl + 3", vec!["tbl"]));
+        assert!(parse_watch("mem[1").is_err_and(|err| err.to_string().contains("end of line")));
+        assert!(parse_watch("counter counter").is_err());
+        // The assembler has no memory accesses.
+        assert!(parse_line("LDC mem[1]", 1).is_err_and(|err| err.to_string().contains("Unexpected '['")));
+    }
+}
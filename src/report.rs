@@ -0,0 +1,204 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::CompilerOutput;
+use crate::mima::{HaltReason, Mima, TraceEntry};
+
+// Instructions at the end of the run kept in the report
+const TRACE_LENGTH: usize = 20;
+
+// A memory cell selected for the report, the adress and value are missing if the name doesn't exist
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportCell {
+    name: String,
+    pub adress: Option<usize>,
+    pub value: Option<usize>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ReportCell {
+    pub fn get_name(&self) -> String {
+        self.name.to_owned()
+    }
+}
+
+/*
+ * Everything about a finished run in one place, e.g. to hand it to a grading backend or to put
+ * it into a link. The hash identifies the compiled program, so reports of different versions of
+ * a program can be told apart.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunReport {
+    program_hash: String,
+    pub steps: usize,
+    pub halt_reason: HaltReason,
+    pub akku: usize,
+    pub iar: usize,
+    pub sp: usize,
+    pub fp: usize,
+    cells: Vec<ReportCell>,
+    trace: Vec<TraceEntry>,
+    // Wall clock time of the run
+    pub duration_ms: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl RunReport {
+    pub fn get_program_hash(&self) -> String {
+        self.program_hash.to_owned()
+    }
+    pub fn get_cells(&self) -> Vec<ReportCell> {
+        self.cells.to_owned()
+    }
+    // The last executed instructions, oldest first
+    pub fn get_trace(&self) -> Vec<TraceEntry> {
+        self.trace.to_owned()
+    }
+    // The report as a JSON object, the keys always come in the same order.
+    pub fn to_json(&self) -> String {
+        let reason = self.halt_reason;
+        let cells: Vec<String> = self
+            .cells
+            .iter()
+            .map(|cell| format!(r#"{{"name":{},"adress":{},"value":{}}}"#, json_string(&cell.name), json_number(cell.adress), json_number(cell.value)))
+            .collect();
+        let trace: Vec<String> = self
+            .trace
+            .iter()
+            .map(|entry| {
+                format!(
+                    r#"{{"adress":{},"instruction":{},"akku_before":{},"akku_after":{}}}"#,
+                    entry.get_adress(),
+                    json_string(&entry.get_command().to_string()),
+                    entry.get_akku_before(),
+                    entry.get_akku_after()
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"program_hash":{},"steps":{},"halt_reason":{{"kind":{},"adress":{},"word":{},"end":{}}},"registers":{{"akku":{},"iar":{},"sp":{},"fp":{}}},"cells":[{}],"trace":[{}],"duration_ms":{}}}"#,
+            json_string(&self.program_hash),
+            self.steps,
+            json_string(&format!("{:?}", reason.kind)),
+            json_number(reason.adress),
+            json_number(reason.word),
+            json_number(reason.end),
+            self.akku,
+            self.iar,
+            self.sp,
+            self.fp,
+            cells.join(","),
+            trace.join(","),
+            self.duration_ms
+        )
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Mima {
+    /*
+     * Loads the program, runs it for at most max_steps instructions and reports the outcome.
+     * Cells are names of variables or labels or decimal adresses. Returns None if the program
+     * doesn't fit into memory.
+     */
+    pub fn run_with_report(&mut self, program: &CompilerOutput, cells: Vec<String>, max_steps: usize) -> Option<RunReport> {
+        self.set_trace_capacity(TRACE_LENGTH);
+        if !self.load(program.to_owned()) {
+            return None;
+        }
+        let start = now_ms();
+        let steps = self.run_chunk(max_steps);
+        let duration_ms = now_ms() - start;
+        let cells = cells
+            .into_iter()
+            .map(|name| {
+                let adress = name.parse::<usize>().ok().or_else(|| {
+                    program.get_symbols().iter().find(|symbol| symbol.get_name() == name).map(|symbol| symbol.get_adress())
+                });
+                ReportCell { name, adress, value: adress.and_then(|adress| self.read_adress(adress)) }
+            })
+            .collect();
+        let debug = self.get_debug();
+        Some(RunReport {
+            program_hash: program_hash(program),
+            steps,
+            halt_reason: debug.halt_reason,
+            akku: debug.akku,
+            iar: debug.iar,
+            sp: debug.sp,
+            fp: debug.fp,
+            cells,
+            trace: self.get_trace(),
+            duration_ms,
+        })
+    }
+}
+
+// FNV-1a over the start adress and the words, which stays the same across versions of Rust.
+fn program_hash(program: &CompilerOutput) -> String {
+    let words = std::iter::once(program.get_start_adress()).chain(program.get_mima_code());
+    let hash = words
+        .flat_map(|word| (word as u64).to_le_bytes())
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{hash:016x}")
+}
+
+// Also used by the other JSON exports, like the control flow graph
+pub(crate) fn json_string(text: &str) -> String {
+    let mut escaped = String::from('"');
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            character if character.is_control() => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+            character => escaped.push(character),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+pub(crate) fn json_number(value: Option<usize>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+// Milliseconds since some fixed point in time, the clock of the browser in wasm
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+fn now_ms() -> f64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0.0, |duration| duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compiler;
+    use crate::mima::{HaltReason, Mima};
+
+    #[test]
+    fn run_reports() {
+        let program = compiler::compile("x: DS 4\nresult: DS\nSTART: LDV x\nADD x\nSTV result\nHALT").unwrap();
+        let mut mima = Mima::new();
+        let cells = vec!["result".to_string(), "0".to_string(), "missing".to_string()];
+        let report = mima.run_with_report(&program, cells.to_owned(), 100).unwrap();
+        assert_eq!((report.steps, report.halt_reason, report.akku, report.iar), (4, HaltReason::halted(), 8, 5));
+        let values: Vec<(String, Option<usize>)> = report.get_cells().iter().map(|cell| (cell.get_name(), cell.value)).collect();
+        assert_eq!(values, vec![("result".to_string(), Some(8)), ("0".to_string(), Some(4)), ("missing".to_string(), None)]);
+        assert_eq!(report.get_trace().len(), 4);
+        let json = report.to_json();
+        assert!(json.starts_with(&format!(r#"{{"program_hash":"{}","steps":4,"halt_reason":{{"kind":"Halted","adress":null"#, report.get_program_hash())));
+        assert!(json.contains(r#"{"name":"missing","adress":null,"value":null}"#));
+        assert!(json.contains(r#"{"adress":2,"instruction":"LDV 0","akku_before":0,"akku_after":4}"#));
+        // The hash only depends on the program.
+        let again = mima.run_with_report(&program, cells, 2).unwrap();
+        assert_eq!(again.get_program_hash(), report.get_program_hash());
+        let other = compiler::compile("x: DS 5\nresult: DS\nSTART: LDV x\nADD x\nSTV result\nHALT").unwrap();
+        assert_ne!(mima.run_with_report(&other, vec![], 100).unwrap().get_program_hash(), report.get_program_hash());
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::compiler;
+use crate::linker::ObjectFile;
+
+/*
+ * Library routines, linked with the program that uses them. Arguments are passed in exported
+ * variables and the result is returned in the akku. The routines are called with CALL and return
+ * with RET, so they need the MIMA-X profile.
+ */
+
+// MULA * MULB modulo 2^24 by shifting and adding
+const MUL: &str = "EXPORT MUL, MULA, MULB
+MULA: DS
+MULB: DS
+MULX: DS
+MULY: DS
+MULR: DS
+MULC: DS
+MULONE: DS 1
+MULMINUS: DS -1
+MULMASK: DS 0x7FFFFF
+MUL: LDV MULA
+STV MULX
+LDV MULB
+STV MULY
+LDC 0
+STV MULR
+LDC 23
+STV MULC
+MULLOOP: LDV MULY
+AND MULONE
+EQL MULONE
+JMN MULADD
+JMP MULSHIFT
+MULADD: LDV MULR
+ADD MULX
+STV MULR
+MULSHIFT: LDV MULX
+ADD MULX
+STV MULX
+LDV MULY
+RAR
+AND MULMASK
+STV MULY
+LDV MULC
+ADD MULMINUS
+STV MULC
+JMN MULEND
+JMP MULLOOP
+MULEND: LDV MULR
+RET";
+
+/*
+ * DIVA / DIVB and DIVA mod DIVB for non-negative arguments by long division. The remainder is
+ * also left in DIVR. Dividing by zero yields -1.
+ */
+const DIV: &str = "EXPORT DIV, MOD, DIVA, DIVB, DIVR
+DIVA: DS
+DIVB: DS
+DIVR: DS
+DIVN: DS
+DIVQ: DS
+DIVC: DS
+DIVONE: DS 1
+DIVMINUS: DS -1
+DIV: LDV DIVA
+STV DIVN
+LDC 0
+STV DIVQ
+STV DIVR
+LDC 23
+STV DIVC
+DIVLOOP: LDV DIVR
+ADD DIVR
+STV DIVR
+LDV DIVN
+JMN DIVBIT
+JMP DIVSHIFT
+DIVBIT: LDV DIVR
+ADD DIVONE
+STV DIVR
+DIVSHIFT: LDV DIVN
+ADD DIVN
+STV DIVN
+LDV DIVQ
+ADD DIVQ
+STV DIVQ
+LDV DIVB
+NOT
+ADD DIVONE
+ADD DIVR
+JMN DIVNEXT
+STV DIVR
+LDV DIVQ
+ADD DIVONE
+STV DIVQ
+DIVNEXT: LDV DIVC
+ADD DIVMINUS
+STV DIVC
+JMN DIVEND
+JMP DIVLOOP
+DIVEND: LDV DIVQ
+RET
+MOD: CALL DIV
+LDV DIVR
+RET";
+
+// Sum of the SUMLEN words starting at the adress in SUMPTR
+const SUM: &str = "EXPORT SUM, SUMPTR, SUMLEN
+SUMPTR: DS
+SUMLEN: DS
+SUMP: DS
+SUMN: DS
+SUMACC: DS
+SUMONE: DS 1
+SUMMINUS: DS -1
+SUM: LDV SUMPTR
+STV SUMP
+LDV SUMLEN
+STV SUMN
+LDC 0
+STV SUMACC
+SUMLOOP: LDV SUMN
+ADD SUMMINUS
+JMN SUMEND
+STV SUMN
+LDIV SUMP
+ADD SUMACC
+STV SUMACC
+LDV SUMP
+ADD SUMONE
+STV SUMP
+JMP SUMLOOP
+SUMEND: LDV SUMACC
+RET";
+
+// Length of the zero terminated string at the adress in STRPTR, one character per word
+const STRLEN: &str = "EXPORT STRLEN, STRPTR
+STRPTR: DS
+STRP: DS
+STRN: DS
+STRZERO: DS 0
+STRONE: DS 1
+STRLEN: LDV STRPTR
+STV STRP
+LDC 0
+STV STRN
+STRLOOP: LDIV STRP
+EQL STRZERO
+JMN STREND
+LDV STRN
+ADD STRONE
+STV STRN
+LDV STRP
+ADD STRONE
+STV STRP
+JMP STRLOOP
+STREND: LDV STRN
+RET";
+
+// Every routine is assembled once, DIV and MOD share their object.
+fn routines() -> &'static BTreeMap<&'static str, ObjectFile> {
+    static ROUTINES: OnceLock<BTreeMap<&'static str, ObjectFile>> = OnceLock::new();
+    ROUTINES.get_or_init(|| {
+        let object = |source: &str| compiler::compile_object(source).unwrap();
+        let division = object(DIV);
+        BTreeMap::from([
+            ("MUL", object(MUL)),
+            ("DIV", division.to_owned()),
+            ("MOD", division),
+            ("SUM", object(SUM)),
+            ("STRLEN", object(STRLEN)),
+        ])
+    })
+}
+
+// Returns the object containing the routine, to be passed to link together with the program.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn get_routine(name: &str) -> Option<ObjectFile> {
+    routines().get(name.to_uppercase().as_str()).cloned()
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn get_routine_names() -> Vec<String> {
+    routines().keys().map(|name| name.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compiler;
+    use crate::linker::link;
+    use crate::mima::{HaltReason, Mima, ReadCheck};
+
+    use super::{get_routine, get_routine_names};
+
+    // Links the program with the routine and returns the akku after running it. The routines must not read uninitialized memory.
+    fn run_with(source: &str, routine: &str) -> usize {
+        let main = compiler::compile_object(source).unwrap();
+        let linked = link(vec![main, get_routine(routine).unwrap()]).unwrap();
+        let mut mima = Mima::new();
+        mima.set_read_check(ReadCheck::Trap);
+        mima.load(linked);
+        assert_eq!(mima.run(), HaltReason::halted());
+        mima.get_akku()
+    }
+
+    #[test]
+    fn arithmetic_routines() {
+        let call = |routine: &str, prefix: &str, a: usize, b: usize| {
+            let source = format!("a: DS {a}\nb: DS {b}\nSTART: LDV a\nSTV {prefix}A\nLDV b\nSTV {prefix}B\nCALL {routine}\nHALT");
+            run_with(&source, routine)
+        };
+        assert_eq!(call("MUL", "MUL", 123, 456), 123 * 456);
+        assert_eq!(call("MUL", "MUL", 0, 456), 0);
+        assert_eq!(call("DIV", "DIV", 1000, 7), 142);
+        assert_eq!(call("MOD", "DIV", 1000, 7), 6);
+        assert_eq!(call("DIV", "DIV", 0x7FFFFF, 1), 0x7FFFFF);
+    }
+
+    #[test]
+    fn array_routines() {
+        let sum = "array: DS 1, 2, 3, 4\nSTART: LDC array\nSTV SUMPTR\nLDC 4\nSTV SUMLEN\nCALL SUM\nHALT";
+        assert_eq!(run_with(sum, "sum"), 10);
+        let strlen = "text: DS 72, 105, 33, 0\nSTART: LDC text\nSTV STRPTR\nCALL STRLEN\nHALT";
+        assert_eq!(run_with(strlen, "STRLEN"), 3);
+        assert!(get_routine("SQRT").is_none());
+        assert_eq!(get_routine_names(), vec!["DIV", "MOD", "MUL", "STRLEN", "SUM"]);
+    }
+}
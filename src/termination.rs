@@ -0,0 +1,186 @@
+use std::collections::BTreeSet;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::cfg::{analyze_cfg, BasicBlock, Edge, EdgeKind};
+use crate::compiler::CompilerOutput;
+use crate::mima::{Command, Instruction};
+
+// A loop that can't end once it repeats
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopWarning {
+    // Adresses of the instructions of the loop
+    adresses: Vec<usize>,
+    // The JMN leaving the loop, missing if nothing leads out of it
+    pub condition: Option<usize>,
+    // Memory cells the condition is computed from, the loop never writes them
+    cells: Vec<usize>,
+    // Source line of the first instruction of the loop
+    pub line: Option<usize>,
+    message: String,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl LoopWarning {
+    pub fn get_adresses(&self) -> Vec<usize> {
+        self.adresses.to_owned()
+    }
+    pub fn get_cells(&self) -> Vec<usize> {
+        self.cells.to_owned()
+    }
+    pub fn get_message(&self) -> String {
+        self.message.to_owned()
+    }
+}
+
+/*
+ * Finds loops that can't terminate once they repeat: loops without a way out and loops whose JMNs
+ * leading out test an akku computed from cells the loop never writes, so the test turns out the
+ * same in every iteration. The analysis is conservative, loops calling subroutines, writing
+ * through pointers or using RET, RTI or JIND are never reported. Writes by devices and interrupt
+ * handlers aren't considered.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn analyze_termination(program: &CompilerOutput) -> Vec<LoopWarning> {
+    let graph = analyze_cfg(program);
+    let (blocks, edges) = (graph.get_blocks(), graph.get_edges());
+    let code = program.get_mima_code();
+    // Blocks reached by following at least one edge. Subroutines return without an edge, so calls are left out.
+    let reach: Vec<BTreeSet<usize>> = (0..blocks.len())
+        .map(|index| {
+            let mut reached = BTreeSet::new();
+            let mut pending = successors(&edges, index);
+            while let Some(next) = pending.pop() {
+                if reached.insert(next) {
+                    pending.extend(successors(&edges, next));
+                }
+            }
+            reached
+        })
+        .collect();
+    let mut warnings = vec![];
+    let mut visited = BTreeSet::new();
+    for index in 0..blocks.len() {
+        if !blocks[index].reachable || !reach[index].contains(&index) || visited.contains(&index) {
+            continue;
+        }
+        // The strongly connected component of the block is the loop.
+        let members: Vec<usize> = (0..blocks.len()).filter(|other| reach[index].contains(other) && reach[*other].contains(&index)).collect();
+        visited.extend(members.iter().copied());
+        if let Some(warning) = check_loop(&members, &blocks, &edges, &code) {
+            warnings.push(warning);
+        }
+    }
+    warnings
+}
+
+fn successors(edges: &[Edge], index: usize) -> Vec<usize> {
+    edges.iter().filter(|edge| edge.from == index && edge.kind != EdgeKind::Call).map(|edge| edge.to).collect()
+}
+
+fn check_loop(members: &[usize], blocks: &[BasicBlock], edges: &[Edge], code: &[usize]) -> Option<LoopWarning> {
+    let adresses: Vec<usize> = members.iter().flat_map(|member| blocks[*member].start..=blocks[*member].end).collect();
+    let commands = adresses.iter().map(|adress| Command::from_usize(code[*adress])).collect::<Option<Vec<Command>>>()?;
+    let mut written = BTreeSet::new();
+    for command in commands.iter() {
+        match command.instruction {
+            Instruction::STV => {
+                written.insert(command.value);
+            }
+            Instruction::STIV | Instruction::STRS | Instruction::CALL | Instruction::RET | Instruction::RTI | Instruction::JIND | Instruction::HALT => {
+                return None;
+            }
+            _ => (),
+        }
+    }
+    for member in members {
+        // Edges are missing for jumps out of the code or running into data, where anything may happen.
+        let expected = match Command::from_usize(code[blocks[*member].end])?.instruction {
+            Instruction::JMN => 2,
+            _ => 1,
+        };
+        if edges.iter().filter(|edge| edge.from == *member).count() < expected {
+            return None;
+        }
+    }
+    let exits: BTreeSet<usize> = edges
+        .iter()
+        .filter(|edge| members.contains(&edge.from) && !members.contains(&edge.to))
+        .map(|edge| edge.from)
+        .collect();
+    let start = adresses[0];
+    let line = blocks[members[0]].line;
+    if exits.is_empty() {
+        let message = format!("The loop at adress {start} never ends, none of its jumps lead out of it.");
+        return Some(LoopWarning { adresses, condition: None, cells: vec![], line, message });
+    }
+    let mut cells = BTreeSet::new();
+    for exit in exits.iter() {
+        cells.extend(invariant_condition(&blocks[*exit], code, &written)?);
+    }
+    // Only a JMN can lead out of a loop and stay in it.
+    let condition = blocks[*exits.first().unwrap()].end;
+    let message = format!("The loop at adress {start} can't end once it repeats, the JMN at adress {condition} tests a value the loop never changes.");
+    Some(LoopWarning { adresses, condition: Some(condition), cells: cells.into_iter().collect(), line, message })
+}
+
+/*
+ * Follows the akku tested by the JMN at the end of the block back to the instruction loading it.
+ * Returns the cells it was computed from, if none of them are written by the loop.
+ */
+fn invariant_condition(block: &BasicBlock, code: &[usize], written: &BTreeSet<usize>) -> Option<Vec<usize>> {
+    let mut cells = vec![];
+    for adress in (block.start..block.end).rev() {
+        let command = Command::from_usize(code[adress])?;
+        match command.instruction {
+            Instruction::LDC => break,
+            Instruction::LDV => {
+                cells.push(command.value);
+                break;
+            }
+            Instruction::ADD | Instruction::AND | Instruction::OR | Instruction::XOR | Instruction::EQL => cells.push(command.value),
+            Instruction::ADC | Instruction::NOT | Instruction::RAR | Instruction::STV => (),
+            _ => return None,
+        }
+        // The akku comes from another block.
+        if adress == block.start {
+            return None;
+        }
+    }
+    if block.start == block.end || cells.iter().any(|cell| written.contains(cell)) {
+        return None;
+    }
+    cells.sort();
+    Some(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compiler;
+    use crate::lint::{lint, LintRule};
+
+    use super::analyze_termination;
+
+    #[test]
+    fn endless_loops() {
+        // The decremented counter is never stored.
+        let source = "counter: DS 3\nSTART: LDV counter\nADC -1\nJMN DONE\nJMP START\nDONE: HALT";
+        let warnings = analyze_termination(&compiler::compile(source).unwrap());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].get_adresses(), warnings[0].condition, warnings[0].get_cells(), warnings[0].line), (vec![1, 2, 3, 4], Some(3), vec![0], Some(2)));
+        assert_eq!(
+            warnings[0].get_message(),
+            "The loop at adress 1 can't end once it repeats, the JMN at adress 3 tests a value the loop never changes."
+        );
+        assert!(lint(source).iter().any(|warning| warning.rule == LintRule::EndlessLoop && warning.line == 2));
+        let fixed = "counter: DS 3\nSTART: LDV counter\nADC -1\nSTV counter\nJMN DONE\nJMP START\nDONE: HALT";
+        assert!(analyze_termination(&compiler::compile(fixed).unwrap()).is_empty());
+        let endless = analyze_termination(&compiler::compile("LDC 0\nLOOP: ADC 1\nJMP LOOP").unwrap());
+        assert_eq!((endless[0].get_adresses(), endless[0].condition), (vec![1, 2], None));
+        // Subroutines might change the counter.
+        let call = "counter: DS 3\nSTART: LDV counter\nJMN DONE\nCALL STEP\nJMP START\nDONE: HALT\nSTEP: RET";
+        assert!(analyze_termination(&compiler::compile(call).unwrap()).is_empty());
+    }
+}
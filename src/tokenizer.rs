@@ -0,0 +1,211 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::compiler::split_comment;
+use crate::parser::{lex_lenient, LexemeKind};
+
+const DIRECTIVES: [&str; 9] = ["DS", "EQU", "ORG", "EXPORT", "INCLUDE", "MACRO", "ENDM", "TITLE", "END"];
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Label,
+    Mnemonic,
+    Number,
+    SymbolReference,
+    Directive,
+    Comment,
+    String,
+    // Arithmetic, parentheses and brackets in expressions
+    Operator,
+    // Text the compiler rejects, like "10abc"
+    Invalid,
+}
+
+/*
+ * A classified span of the source. Columns are offsets into the line in UTF-16 code units, so
+ * they can be used on JavaScript strings directly.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    kind: TokenKind,
+    line: usize,
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Token {
+    pub fn get_kind(&self) -> TokenKind {
+        self.kind
+    }
+    // Line of the token, starting at 1 like in compiler messages
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+    pub fn get_end(&self) -> usize {
+        self.end
+    }
+    pub fn get_text(&self) -> String {
+        self.text.to_owned()
+    }
+}
+
+// Splits the source into tokens following the rules of the compiler.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn tokenize(source: &str) -> Vec<Token> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| tokenize_line(line, index + 1))
+        .collect()
+}
+
+pub(crate) fn tokenize_line(line: &str, line_number: usize) -> Vec<Token> {
+    let column = |offset: usize| line[..offset].encode_utf16().count();
+    let token = |kind: TokenKind, start: usize, end: usize| Token {
+        kind,
+        line: line_number,
+        start: column(start),
+        end: column(end),
+        text: line[start..end].to_string(),
+    };
+    let code = split_comment(line).0;
+    let lexemes = lex_lenient(code);
+    let word = |index: usize| match lexemes.get(index) {
+        Some((Some(LexemeKind::Identifier(name)), _)) => Some(name.to_uppercase()),
+        _ => None,
+    };
+    let mut tokens: Vec<Token> = vec![];
+    // The first word after the labels is the operation of the line.
+    let mut operation = true;
+    let mut index = 0;
+    while index < lexemes.len() {
+        let (lexeme, span) = &lexemes[index];
+        let mut end = span.end;
+        let previous = index.checked_sub(1).and_then(|previous| lexemes[previous].0.as_ref());
+        // A minus after an operand subtracts, otherwise it is the sign of the following number.
+        let follows_operand = match previous {
+            Some(LexemeKind::Number(_) | LexemeKind::String(_) | LexemeKind::RightParen | LexemeKind::RightBracket) => true,
+            Some(LexemeKind::Identifier(_)) => tokens.last().is_some_and(|token| token.kind == TokenKind::SymbolReference),
+            _ => false,
+        };
+        let kind = match lexeme {
+            None => TokenKind::Invalid,
+            Some(LexemeKind::Colon | LexemeKind::Comma) => {
+                index += 1;
+                continue;
+            }
+            Some(LexemeKind::String(_)) => TokenKind::String,
+            Some(LexemeKind::Number(_)) => TokenKind::Number,
+            Some(LexemeKind::Minus) if !follows_operand && matches!(lexemes.get(index + 1), Some((Some(LexemeKind::Number(_)), next)) if next.start == span.end) => {
+                index += 1;
+                end = lexemes[index].1.end;
+                TokenKind::Number
+            }
+            // "* = $10" places the following code like ORG.
+            Some(LexemeKind::Star) if operation => {
+                operation = false;
+                TokenKind::Directive
+            }
+            Some(LexemeKind::Identifier(name)) => {
+                let name = name.to_uppercase();
+                if matches!(lexemes.get(index + 1), Some((Some(LexemeKind::Colon), _))) {
+                    TokenKind::Label
+                } else if DIRECTIVES.contains(&name.as_str()) {
+                    operation = false;
+                    TokenKind::Directive
+                } else if index.checked_sub(1).and_then(word).as_deref() == Some("DS") && ["INPUT", "OUTPUT"].contains(&name.as_str()) {
+                    // "DS INPUT" and "DS OUTPUT" mark parameters.
+                    TokenKind::Directive
+                } else if operation && word(index + 1).as_deref() == Some("EQU") {
+                    // Name of a constant
+                    TokenKind::Label
+                } else if operation {
+                    // Instructions and macro invocations
+                    operation = false;
+                    TokenKind::Mnemonic
+                } else {
+                    TokenKind::SymbolReference
+                }
+            }
+            Some(_) => TokenKind::Operator,
+        };
+        tokens.push(token(kind, span.start, end));
+        index += 1;
+    }
+    if code.len() < line.len() {
+        tokens.push(token(TokenKind::Comment, code.len(), line.len()));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, TokenKind};
+
+    #[test]
+    fn classify_tokens() {
+        let tokens = tokenize("a: DS 5, -0x3\nONE EQU 1\nSTART: ldv a ; Schleife für ä\n* = $10\nINCLUDE \"lib\"\nn: DS INPUT");
+        let kinds: Vec<(TokenKind, String)> = tokens.iter().map(|token| (token.get_kind(), token.get_text())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (TokenKind::Label, "a".to_string()),
+                (TokenKind::Directive, "DS".to_string()),
+                (TokenKind::Number, "5".to_string()),
+                (TokenKind::Number, "-0x3".to_string()),
+                (TokenKind::Label, "ONE".to_string()),
+                (TokenKind::Directive, "EQU".to_string()),
+                (TokenKind::Number, "1".to_string()),
+                (TokenKind::Label, "START".to_string()),
+                (TokenKind::Mnemonic, "ldv".to_string()),
+                (TokenKind::SymbolReference, "a".to_string()),
+                (TokenKind::Comment, "; Schleife für ä".to_string()),
+                (TokenKind::Directive, "*".to_string()),
+                (TokenKind::Operator, "=".to_string()),
+                (TokenKind::Number, "$10".to_string()),
+                (TokenKind::Directive, "INCLUDE".to_string()),
+                (TokenKind::String, "\"lib\"".to_string()),
+                (TokenKind::Label, "n".to_string()),
+                (TokenKind::Directive, "DS".to_string()),
+                (TokenKind::Directive, "INPUT".to_string()),
+            ]
+        );
+        let comment = &tokens[10];
+        assert_eq!((comment.get_line(), comment.get_start(), comment.get_end()), (3, 13, 29));
+        // Expressions are split like the compiler does.
+        let kinds = |source: &str| tokenize(source).iter().map(|token| (token.get_kind(), token.get_text())).collect::<Vec<(TokenKind, String)>>();
+        let token = |kind: TokenKind, text: &str| (kind, text.to_string());
+        assert_eq!(
+            kinds("LDC (A+2)*3"),
+            vec![
+                token(TokenKind::Mnemonic, "LDC"),
+                token(TokenKind::Operator, "("),
+                token(TokenKind::SymbolReference, "A"),
+                token(TokenKind::Operator, "+"),
+                token(TokenKind::Number, "2"),
+                token(TokenKind::Operator, ")"),
+                token(TokenKind::Operator, "*"),
+                token(TokenKind::Number, "3"),
+            ]
+        );
+        assert_eq!(
+            kinds("tbl_end: EQU tbl-10"),
+            vec![
+                token(TokenKind::Label, "tbl_end"),
+                token(TokenKind::Directive, "EQU"),
+                token(TokenKind::SymbolReference, "tbl"),
+                token(TokenKind::Operator, "-"),
+                token(TokenKind::Number, "10"),
+            ]
+        );
+        assert_eq!(kinds("LDC 10abc ; x"), vec![token(TokenKind::Mnemonic, "LDC"), token(TokenKind::Invalid, "10abc"), token(TokenKind::Comment, "; x")]);
+        assert_eq!(kinds("LDC -1"), vec![token(TokenKind::Mnemonic, "LDC"), token(TokenKind::Number, "-1")]);
+    }
+}